@@ -1,4 +1,7 @@
 fn main() {
+    // cc emits rerun-if-env-changed, which turns off cargo's default "any file changed"
+    // check, so name the file holding the cpp! blocks explicitly
+    println!("cargo:rerun-if-changed=src/main.rs");
     // Build inline C++ code from src files
     cpp_build::build("src/main.rs");
 }
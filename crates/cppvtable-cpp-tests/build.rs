@@ -1,3 +1,6 @@
 fn main() {
+    // cc emits rerun-if-env-changed, which turns off cargo's default "any file changed"
+    // check, so name the file holding the cpp! blocks explicitly
+    println!("cargo:rerun-if-changed=src/lib.rs");
     cpp_build::build("src/lib.rs");
 }
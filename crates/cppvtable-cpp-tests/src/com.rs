@@ -0,0 +1,89 @@
+//! COM interop tests: `ComPtr` holding a C++ COM object
+//!
+//! `CppCounter` implements `ICppCounter` and `ICppResettable` with stdcall IUnknown
+//! methods, and deletes itself on the last `Release`. The Rust `Counter` is a
+//! `#[com_implement(ICounter, heap)]` object, freed the same way, and `Tally` implements
+//! both interfaces.
+
+use super::*;
+use cppvtable::com::{ComPtr, E_NOINTERFACE};
+use std::sync::atomic::Ordering;
+
+/// Test that cloning and dropping ComPtrs balances the C++ reference count
+#[test]
+fn test_com_ptr_releases_cpp_object() {
+    let before = cpp_counters_alive();
+    unsafe {
+        let counter = ComPtr::<ICounter>::from_raw(create_cpp_counter()).unwrap();
+        assert_eq!(cpp_counters_alive(), before + 1);
+        assert_eq!(counter.next(), 1);
+
+        let copy = counter.clone();
+        drop(counter);
+        assert_eq!(copy.next(), 2);
+        assert_eq!(cpp_counters_alive(), before + 1);
+        drop(copy);
+    }
+    assert_eq!(cpp_counters_alive(), before);
+}
+
+/// Test that cast() goes through the C++ QueryInterface to a secondary interface
+#[test]
+fn test_com_ptr_casts_cpp_object() {
+    let before = cpp_counters_alive();
+    unsafe {
+        let counter = ComPtr::<ICounter>::from_raw(create_cpp_counter()).unwrap();
+        let resettable: ComPtr<IResettable> = counter.cast().unwrap();
+        assert_ne!(resettable.as_ptr(), counter.as_ptr());
+
+        resettable.reset(41);
+        assert_eq!(counter.next(), 42);
+
+        let unknown: ComPtr<IUnknown> = resettable.cast().unwrap();
+        assert_eq!(unknown.as_ptr(), counter.as_ptr());
+    }
+    assert_eq!(cpp_counters_alive(), before);
+}
+
+/// Test that a Rust `#[com_implement]` object answers cast() like a C++ one
+#[test]
+fn test_com_ptr_holds_rust_object() {
+    let before = COUNTERS_DROPPED.load(Ordering::SeqCst);
+    unsafe {
+        let ptr = ComPtr::<ICounter>::new(Counter::new(0));
+        let unknown: ComPtr<IUnknown> = ptr.cast().unwrap();
+        assert_eq!(ptr.cast::<IResettable>().unwrap_err(), E_NOINTERFACE);
+
+        drop(unknown);
+        assert_eq!(ptr.next(), 1);
+    }
+    assert_eq!(COUNTERS_DROPPED.load(Ordering::SeqCst), before + 1);
+}
+
+/// Test that the last Release, made by C++, frees a `heap` Rust object
+#[test]
+fn test_cpp_releases_rust_object() {
+    let before = COUNTERS_DROPPED.load(Ordering::SeqCst);
+    let counter = ComPtr::<ICounter>::new(Counter::new(4));
+    let copy = counter.clone();
+
+    assert_eq!(cpp_use_and_release_counter(ComPtr::into_raw(copy)), 56);
+    assert_eq!(COUNTERS_DROPPED.load(Ordering::SeqCst), before);
+
+    assert_eq!(cpp_use_and_release_counter(ComPtr::into_raw(counter)), 78);
+    assert_eq!(COUNTERS_DROPPED.load(Ordering::SeqCst), before + 1);
+}
+
+/// Test that C++ QueryInterface moves between the interfaces of a Rust object
+#[test]
+fn test_cpp_queries_rust_object() {
+    let tally = ComPtr::<ICounter>::new(Tally::new(0));
+    assert_eq!(cpp_reset_counter(tally.as_ptr(), 9), 10);
+
+    let resettable: ComPtr<IResettable> = tally.cast().unwrap();
+    let unknown: ComPtr<IUnknown> = resettable.cast().unwrap();
+    assert_eq!(unknown.as_ptr(), tally.as_ptr());
+
+    unsafe { resettable.reset(0) };
+    assert_eq!(cpp_reset_counter(tally.as_ptr(), 1), 2);
+}
@@ -0,0 +1,53 @@
+//! Calling convention C++ interop tests
+//!
+//! `win64` vtables must match g++'s `__attribute__((ms_abi))` methods on Linux and the
+//! native convention on Windows. On i686 Linux (`--target i686-unknown-linux-gnu`, needs
+//! a multilib g++) default vtables are cdecl, which every other interop test relies on,
+//! and `thiscall` matches `__attribute__((thiscall))`.
+
+use super::*;
+
+/// Test that Rust calls ms_abi C++ methods through a `win64` interface
+#[test]
+fn test_rust_calls_cpp_win64() {
+    unsafe {
+        let cpp_gauge = create_cpp_gauge();
+        let gauge = IGauge::from_ptr_mut(cpp_gauge);
+
+        assert_eq!(gauge.read(3), 63);
+        assert_eq!(gauge.mix(1, 2, 3, 4, 5.5), 12345);
+
+        delete_cpp_gauge(cpp_gauge);
+    }
+}
+
+/// Test that C++ calls a Rust `win64` implementation as ms_abi methods
+#[test]
+fn test_cpp_calls_rust_win64() {
+    let gauge = Gauge::new(21);
+    let gauge_ptr = &gauge as *const Gauge as *mut c_void;
+
+    assert_eq!(cpp_use_gauge(gauge_ptr), 42 + 12345);
+}
+
+/// Test that Rust calls C++ through a forced `thiscall` interface
+#[test]
+fn test_rust_calls_cpp_thiscall() {
+    unsafe {
+        let cpp_scale = create_cpp_scale();
+        let scale = IScale::from_ptr_mut(cpp_scale);
+
+        assert_eq!(scale.weigh(7000), 7);
+
+        delete_cpp_scale(cpp_scale);
+    }
+}
+
+/// Test that C++ calls a Rust `thiscall` implementation
+#[test]
+fn test_cpp_calls_rust_thiscall() {
+    let scale = Scale::new(1);
+    let scale_ptr = &scale as *const Scale as *mut c_void;
+
+    assert_eq!(cpp_use_scale(scale_ptr), 6);
+}
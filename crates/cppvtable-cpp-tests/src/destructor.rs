@@ -0,0 +1,67 @@
+//! Virtual destructor C++ interop tests
+
+use super::*;
+use std::sync::atomic::Ordering;
+
+/// Test that C++ `delete` on a Rust object runs Drop and frees the Box
+#[test]
+fn test_cpp_deletes_rust_object() {
+    let triangle = Box::into_raw(Box::new(Triangle::new("heap")));
+    let shape = triangle as *mut c_void;
+
+    assert_eq!(cpp_call_sides(shape), 3);
+
+    let before = TRIANGLES_DROPPED.load(Ordering::SeqCst);
+    delete_cpp_shape(shape);
+    assert_eq!(TRIANGLES_DROPPED.load(Ordering::SeqCst), before + 1);
+}
+
+/// Test that C++ `delete` frees an object handed over from a CppBox
+#[test]
+fn test_cpp_deletes_cpp_box() {
+    let triangle = cppvtable::CppBox::new(Triangle::new("boxed"));
+    assert_eq!(
+        cpp_call_sides(triangle.as_interface_ptr::<IShape>() as *mut c_void),
+        3
+    );
+
+    let shape = cppvtable::CppBox::into_raw_interface::<IShape>(triangle);
+    let before = TRIANGLES_DROPPED.load(Ordering::SeqCst);
+    delete_cpp_shape(shape as *mut c_void);
+    assert_eq!(TRIANGLES_DROPPED.load(Ordering::SeqCst), before + 1);
+}
+
+/// Test that Rust can destroy and free a C++ object through its virtual destructor
+#[test]
+fn test_rust_deletes_cpp_object() {
+    unsafe {
+        let square = create_cpp_square();
+        let shape = IShape::from_ptr_mut(square);
+        assert_eq!(shape.sides(), 4);
+
+        let before = cpp_shapes_destroyed();
+        shape.destructor_deleting();
+        assert_eq!(cpp_shapes_destroyed(), before + 1);
+    }
+}
+
+/// Test that CppOwned deletes a C++ object through its virtual destructor on drop
+#[test]
+fn test_cpp_owned_deletes_cpp_object() {
+    let square = unsafe { cppvtable::CppOwned::<IShape>::from_raw(create_cpp_square()) };
+    assert_eq!(square.sides(), 4);
+
+    let before = cpp_shapes_destroyed();
+    drop(square);
+    assert_eq!(cpp_shapes_destroyed(), before + 1);
+}
+
+/// Test vtable size: one destructor slot on MSVC, the D1/D0 pair on Itanium
+#[test]
+fn test_destructor_vtable_size() {
+    let ptr_size = std::mem::size_of::<*const ()>();
+    #[cfg(target_env = "msvc")]
+    assert_eq!(std::mem::size_of::<IShapeVTable>(), 2 * ptr_size);
+    #[cfg(not(target_env = "msvc"))]
+    assert_eq!(std::mem::size_of::<IShapeVTable>(), 3 * ptr_size);
+}
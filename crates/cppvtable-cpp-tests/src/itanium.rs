@@ -0,0 +1,230 @@
+//! Itanium ABI (g++) interop tests
+
+use super::*;
+use cppvtable::CppAbi;
+use cppvtable::itanium;
+use cppvtable::itanium::rtti::{self, TypeInfoKind};
+use std::ffi::{CStr, c_void};
+
+/// Test the interfaces picked the Itanium layout on this target
+#[test]
+fn test_native_abi_is_itanium() {
+    assert_eq!(CppAbi::NATIVE, CppAbi::Itanium);
+    assert_eq!(<IFlyer as cppvtable::VTableLayout>::ABI, CppAbi::Itanium);
+}
+
+/// Test g++ puts offset-to-top in front of each vtable the same way we do
+#[test]
+fn test_offset_to_top_matches_gxx() {
+    let cpp_duck = create_cpp_duck(10);
+    let cpp_flyer = cpp_duck_as_flyer(cpp_duck);
+
+    let rust_duck = Duck::new(10);
+    let rust_flyer = unsafe {
+        (&rust_duck as *const Duck as *const u8).add(std::mem::offset_of!(Duck, vtable_i_flyer))
+    };
+
+    unsafe {
+        assert_eq!(itanium::offset_to_top(cpp_duck), 0);
+        assert_eq!(
+            itanium::offset_to_top(cpp_flyer),
+            itanium::offset_to_top(rust_flyer as *const c_void)
+        );
+        assert_eq!(
+            itanium::complete_object(cpp_flyer),
+            cpp_duck as *const c_void
+        );
+    }
+
+    delete_cpp_duck(cpp_duck);
+}
+
+/// Test our vtable group is laid out like g++'s: IFlyer's prefix right after ISwimmer's
+/// methods. g++'s primary vtable also repeats CppDuck's overrides of IFlyer's methods
+/// (reached from IFlyer's vtable through thunks), which a Rust interface doesn't have.
+#[test]
+fn test_vtable_group_matches_gxx() {
+    let cpp_duck = create_cpp_duck(10);
+    let cpp_flyer = cpp_duck_as_flyer(cpp_duck);
+    let ptr_size = std::mem::size_of::<*const c_void>() as isize;
+
+    unsafe {
+        let cpp_distance = *(cpp_flyer as *const isize) - *(cpp_duck as *const isize);
+        let rust_distance = Duck::VTABLE_I_FLYER as isize - Duck::VTABLE_I_SWIMMER as isize;
+        let prefix = 2 * ptr_size;
+        assert_eq!(
+            rust_distance,
+            std::mem::size_of::<ISwimmerVTable>() as isize + prefix
+        );
+        assert_eq!(
+            cpp_distance,
+            std::mem::size_of::<ISwimmerVTable>() as isize
+                + std::mem::size_of::<IFlyerVTable>() as isize
+                + prefix
+        );
+    }
+
+    delete_cpp_duck(cpp_duck);
+}
+
+/// Test g++ emits a typeinfo pointer at slot -1. We emit null: C++ RTTI on Rust objects
+/// is unsupported, and g++'s `typeid` would dereference it
+#[test]
+fn test_type_info_slot() {
+    let cpp_duck = create_cpp_duck(10);
+    let rust_duck = Duck::new(10);
+
+    unsafe {
+        assert!(!itanium::type_info_ptr(cpp_duck).is_null());
+        assert!(itanium::type_info_ptr(&rust_duck as *const Duck as *const c_void).is_null());
+    }
+
+    delete_cpp_duck(cpp_duck);
+}
+
+/// Test C++ `dynamic_cast<void*>` finds the start of a Rust object from a secondary interface
+#[test]
+fn test_cpp_dynamic_cast_void_on_rust_object() {
+    let rust_duck = Duck::new(15);
+    let flyer_ptr = unsafe {
+        (&rust_duck as *const Duck as *const u8).add(std::mem::offset_of!(Duck, vtable_i_flyer))
+            as *mut c_void
+    };
+
+    let complete = cpp_dynamic_cast_flyer_to_void(flyer_ptr);
+    assert_eq!(complete as *const Duck, &rust_duck as *const Duck);
+}
+
+// =============================================================================
+// Reading g++'s RTTI (itanium::rtti)
+// =============================================================================
+
+/// Test the type name matches `typeid(*p).name()` and demangles
+#[test]
+fn test_type_name_matches_typeid() {
+    let cpp_duck = create_cpp_duck(1);
+    let cpp_flyer = cpp_duck_as_flyer(cpp_duck);
+    let robot = create_cpp_robot();
+
+    unsafe {
+        let name = rtti::type_name(cpp_flyer).unwrap();
+        assert_eq!(name, c"7CppDuck");
+        assert_eq!(name, CStr::from_ptr(cpp_flyer_typeid_name(cpp_flyer)));
+        assert_eq!(cpp_demangle(name), "CppDuck");
+
+        let name = rtti::type_name(robot).unwrap();
+        assert_eq!(name, c"N17cppvtable_fixture5RobotE");
+        assert_eq!(cpp_demangle(name), "cppvtable_fixture::Robot");
+
+        // Rust vtables carry no C++ type_info
+        let rust_duck = Duck::new(1);
+        assert!(rtti::type_info_of(&rust_duck as *const Duck as *const c_void).is_none());
+    }
+
+    delete_cpp_robot(robot);
+    delete_cpp_duck(cpp_duck);
+}
+
+/// Test the three class type_info kinds and their bases
+#[test]
+fn test_type_info_kinds_and_bases() {
+    let dog = create_cpp_dog("Rex");
+    let cpp_duck = create_cpp_duck(1);
+    let badge = create_cpp_badge(3);
+
+    unsafe {
+        // CppDog : ICppAnimal
+        let dog_info = rtti::type_info_of(dog).unwrap();
+        assert_eq!(dog_info.kind(), TypeInfoKind::SingleInheritance);
+        let bases = dog_info.bases();
+        assert_eq!(bases.len(), 1);
+        assert_eq!(bases[0].type_info.name(), c"10ICppAnimal");
+        assert_eq!(bases[0].type_info.kind(), TypeInfoKind::Class);
+        assert!(bases[0].type_info.bases().is_empty());
+
+        // CppDuck : ISwimmer, IFlyer
+        let duck_info = rtti::type_info_of(cpp_duck).unwrap();
+        assert_eq!(duck_info.kind(), TypeInfoKind::VirtualMultipleInheritance);
+        let bases: Vec<_> = duck_info
+            .bases()
+            .iter()
+            .map(|b| (b.type_info.name(), b.offset, b.is_virtual, b.is_public))
+            .collect();
+        let flyer_offset =
+            (cpp_duck_as_flyer(cpp_duck) as *const u8).offset_from(cpp_duck as *const u8);
+        assert_eq!(
+            bases,
+            [
+                (c"8ISwimmer", 0, false, true),
+                (c"6IFlyer", flyer_offset, false, true)
+            ]
+        );
+
+        // CppBadge : ICppLabeled, ICppTagged, both `virtual ICppNamed`
+        let labeled = rtti::type_info_of(badge).unwrap().bases()[0].type_info;
+        assert_eq!(labeled.name(), c"11ICppLabeled");
+        assert_eq!(labeled.kind(), TypeInfoKind::VirtualMultipleInheritance);
+        let named = labeled.bases()[0];
+        assert_eq!(named.type_info.name(), c"9ICppNamed");
+        assert!(named.is_virtual && named.offset < 0);
+    }
+
+    delete_cpp_badge(badge);
+    delete_cpp_duck(cpp_duck);
+    delete_cpp_dog(dog);
+}
+
+/// Test dynamic_cast to a named base agrees with C++'s, including cross-casts
+#[test]
+fn test_dynamic_cast_to_named_base() {
+    let cpp_duck = create_cpp_duck(6);
+    let cpp_flyer = cpp_duck_as_flyer(cpp_duck);
+
+    unsafe {
+        let swimmer = rtti::dynamic_cast(cpp_flyer, c"8ISwimmer");
+        assert_eq!(swimmer, cpp_dynamic_cast_flyer_to_swimmer(cpp_flyer));
+        assert_eq!(swimmer, cpp_duck_as_swimmer(cpp_duck));
+        assert_eq!(
+            ISwimmer::from_ptr_mut(swimmer as *mut c_void).swim_speed(),
+            6
+        );
+
+        assert_eq!(rtti::dynamic_cast(cpp_duck, c"6IFlyer"), cpp_flyer);
+        assert_eq!(rtti::dynamic_cast(cpp_flyer, c"7CppDuck"), cpp_duck);
+        assert!(rtti::dynamic_cast(cpp_flyer, c"10ICppAnimal").is_null());
+    }
+
+    delete_cpp_duck(cpp_duck);
+}
+
+/// Test dynamic_cast through virtual bases, and that private and ambiguous bases fail
+#[test]
+fn test_dynamic_cast_virtual_private_and_ambiguous_bases() {
+    let badge = create_cpp_badge(4);
+    let tagged = cpp_badge_as_tagged(badge);
+    let robot = create_cpp_robot();
+    let pair = create_cpp_pair();
+
+    unsafe {
+        let named = rtti::dynamic_cast(tagged, c"9ICppNamed");
+        assert_eq!(named, cpp_tagged_as_named(tagged));
+        assert_eq!(named, cpp_labeled_as_named(badge));
+        assert_eq!(rtti::dynamic_cast(badge, c"10ICppTagged"), tagged);
+
+        assert_eq!(
+            rtti::dynamic_cast(robot, c"N17cppvtable_fixture7IWalkerE"),
+            robot
+        );
+        assert!(rtti::dynamic_cast(robot, c"N17cppvtable_fixture7BatteryE").is_null());
+
+        assert!(rtti::dynamic_cast(pair, c"N17cppvtable_fixture5IPartE").is_null());
+        assert_eq!(
+            rtti::dynamic_cast(pair, c"N17cppvtable_fixture8LeftPartE"),
+            cpp_pair_as_left(pair)
+        );
+    }
+
+    delete_cpp_pair(pair);
+    delete_cpp_robot(robot);
+    delete_cpp_badge(badge);
+}
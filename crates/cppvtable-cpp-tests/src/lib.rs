@@ -1,7 +1,7 @@
 //! C++ interop tests for cppvtable
 //!
-//! This crate verifies that cppvtable's vtable layout matches MSVC's C++ vtable layout.
-//! Requires MSVC to build and run.
+//! This crate verifies that cppvtable's vtable layout matches the C++ compiler's layout:
+//! MSVC on `*-msvc` targets, g++ (Itanium ABI) on Linux and other targets.
 //!
 //! Run with: `cargo test -p cppvtable-cpp-tests`
 
@@ -11,13 +11,27 @@ use cpp::cpp;
 use cppvtable::proc::{cppvtable, cppvtable_impl};
 use std::ffi::c_void;
 
+#[cfg(test)]
+mod com;
+#[cfg(test)]
+mod conventions;
+#[cfg(test)]
+mod destructor;
+#[cfg(all(test, not(target_env = "msvc")))]
+mod itanium;
 #[cfg(test)]
 mod multi;
 #[cfg(test)]
+mod overloads;
+#[cfg(test)]
+mod returns;
+#[cfg(test)]
 mod single;
+#[cfg(test)]
+mod virtual_bases;
 
 // =============================================================================
-// C++ code compiled by MSVC
+// C++ code compiled by MSVC or g++
 // =============================================================================
 
 cpp! {{
@@ -25,6 +39,8 @@ cpp! {{
     #include <cstring>
 
     // Pure virtual interface - should match our Rust IAnimal layout
+    // No virtual destructor: it would add slots IAnimal doesn't have, so the
+    // concrete classes are final and always deleted through their own type
     class ICppAnimal {
     public:
         virtual void speak() = 0;
@@ -32,12 +48,12 @@ cpp! {{
     };
 
     // Concrete C++ implementation
-    class CppDog : public ICppAnimal {
+    class CppDog final : public ICppAnimal {
     public:
         char name[32];
 
         CppDog(const char* n) {
-            strncpy_s(name, sizeof(name), n, _TRUNCATE);
+            snprintf(name, sizeof(name), "%s", n);
         }
 
         void speak() override {
@@ -49,7 +65,7 @@ cpp! {{
         }
     };
 
-    class CppCat : public ICppAnimal {
+    class CppCat final : public ICppAnimal {
     public:
         int lives;
 
@@ -64,6 +80,12 @@ cpp! {{
         }
     };
 
+    // Derived interface: ICppAnimal's slots, then its own
+    class ICppPet : public ICppAnimal {
+    public:
+        virtual int tricks() = 0;
+    };
+
     // ==========================================================================
     // Multiple inheritance interfaces and classes
     // ==========================================================================
@@ -81,7 +103,7 @@ cpp! {{
     };
 
     // Duck implements both ISwimmer and IFlyer (multiple inheritance)
-    class CppDuck : public ISwimmer, public IFlyer {
+    class CppDuck final : public ISwimmer, public IFlyer {
     public:
         int speed;
 
@@ -95,6 +117,267 @@ cpp! {{
         int fly_speed() override { return speed * 2; }
         void fly() override { printf("Duck flying at %d\n", speed * 2); }
     };
+
+    // ==========================================================================
+    // Virtual destructor
+    // ==========================================================================
+
+    static int g_shapes_destroyed = 0;
+
+    class ICppShape {
+    public:
+        virtual ~ICppShape() {}
+        virtual int sides() = 0;
+    };
+
+    class CppSquare : public ICppShape {
+    public:
+        ~CppSquare() override { g_shapes_destroyed++; }
+        int sides() override { return 4; }
+    };
+
+    // ==========================================================================
+    // Struct return values (hidden return pointer on MSVC)
+    // ==========================================================================
+
+    struct CppSize {
+        int width;
+        int height;
+    };
+
+    class ICppWidget {
+    public:
+        virtual CppSize size() = 0;
+        virtual int id() = 0;
+    };
+
+    class CppWidget final : public ICppWidget {
+    public:
+        CppSize size() override { return CppSize{3, 4}; }
+        int id() override { return 9; }
+    };
+
+    // ==========================================================================
+    // Overloaded virtual methods (MSVC reverses them, g++ keeps them in order)
+    // ==========================================================================
+
+    class ICppPen {
+    public:
+        virtual int draw(int x) = 0;
+        virtual int width() = 0;
+        virtual int draw(int x, int y) = 0;
+    };
+
+    class CppPen final : public ICppPen {
+    public:
+        int draw(int x) override { return x; }
+        int width() override { return 2; }
+        int draw(int x, int y) override { return x * 100 + y; }
+    };
+}}
+
+// A second block keeps each cpp! closure under the recursion limit
+cpp! {{
+    // ==========================================================================
+    // Microsoft x64 convention outside Windows (Wine/DXVK-style ms_abi vtables)
+    // ==========================================================================
+
+    #if defined(__x86_64__) && !defined(_MSC_VER)
+    #define CPP_MS_ABI __attribute__((ms_abi))
+    #else
+    #define CPP_MS_ABI
+    #endif
+
+    class ICppGauge {
+    public:
+        CPP_MS_ABI virtual int read(int scale) = 0;
+        CPP_MS_ABI virtual int mix(int a, int b, int c, int d, float e) = 0;
+    };
+
+    class CppGauge final : public ICppGauge {
+    public:
+        CPP_MS_ABI int read(int scale) override { return 21 * scale; }
+        CPP_MS_ABI int mix(int a, int b, int c, int d, float e) override {
+            return a * 10000 + b * 1000 + c * 100 + d * 10 + static_cast<int>(e);
+        }
+    };
+
+    // ==========================================================================
+    // thiscall outside Windows (g++ uses cdecl for members on i686 Linux by default)
+    // ==========================================================================
+
+    #if defined(__i386__) && !defined(_MSC_VER)
+    #define CPP_THISCALL __attribute__((thiscall))
+    #else
+    #define CPP_THISCALL
+    #endif
+
+    class ICppScale {
+    public:
+        CPP_THISCALL virtual int weigh(int grams) = 0;
+    };
+
+    class CppScale final : public ICppScale {
+    public:
+        CPP_THISCALL int weigh(int grams) override { return grams / 1000; }
+    };
+}}
+
+cpp! {{
+    // ==========================================================================
+    // Diamond through a virtual base (vbptr/vbtable on MSVC, vbase offset on g++)
+    // ==========================================================================
+
+    class ICppNamed {
+    public:
+        virtual int id() = 0;
+    };
+
+    class ICppLabeled : public virtual ICppNamed {
+    public:
+        virtual int label() = 0;
+    };
+
+    class ICppTagged : public virtual ICppNamed {
+    public:
+        virtual int tag() = 0;
+    };
+
+    class CppBadge final : public ICppLabeled, public ICppTagged {
+        int value;
+    public:
+        CppBadge(int v) : value(v) {}
+        int id() override { return value; }
+        int label() override { return value * 10; }
+        int tag() override { return value + 1; }
+    };
+}}
+
+cpp! {{
+    #include <cstdint>
+    #include <cstring>
+
+    // ==========================================================================
+    // COM object (stdcall IUnknown, deletes itself on the last Release)
+    // ==========================================================================
+
+    #if defined(_MSC_VER)
+    #define CPP_STDCALL __stdcall
+    #elif defined(__i386__)
+    #define CPP_STDCALL __attribute__((stdcall))
+    #else
+    #define CPP_STDCALL
+    #endif
+
+    struct CppGuid {
+        uint32_t data1;
+        uint16_t data2;
+        uint16_t data3;
+        uint8_t data4[8];
+    };
+
+    static const CppGuid CPP_IID_IUNKNOWN =
+        {0x00000000, 0x0000, 0x0000, {0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46}};
+    static const CppGuid CPP_IID_ICOUNTER =
+        {0x6a1c7b52, 0x3f0e, 0x4d8a, {0x9b, 0x21, 0x5c, 0x0e, 0x7d, 0x44, 0x18, 0x01}};
+    static const CppGuid CPP_IID_IRESETTABLE =
+        {0x6a1c7b52, 0x3f0e, 0x4d8a, {0x9b, 0x21, 0x5c, 0x0e, 0x7d, 0x44, 0x18, 0x02}};
+
+    static int cpp_counters_alive = 0;
+
+    class ICppUnknown {
+    public:
+        virtual int32_t CPP_STDCALL QueryInterface(const CppGuid* riid, void** ppv) = 0;
+        virtual uint32_t CPP_STDCALL AddRef() = 0;
+        virtual uint32_t CPP_STDCALL Release() = 0;
+    };
+
+    class ICppCounter : public ICppUnknown {
+    public:
+        virtual int32_t CPP_STDCALL Next() = 0;
+    };
+
+    class ICppResettable : public ICppUnknown {
+    public:
+        virtual void CPP_STDCALL Reset(int32_t value) = 0;
+    };
+
+    class CppCounter : public ICppCounter, public ICppResettable {
+        uint32_t refs = 1;
+        int32_t value = 0;
+    public:
+        CppCounter() { cpp_counters_alive++; }
+        virtual ~CppCounter() { cpp_counters_alive--; }
+
+        int32_t CPP_STDCALL QueryInterface(const CppGuid* riid, void** ppv) override {
+            if (!memcmp(riid, &CPP_IID_IUNKNOWN, sizeof(CppGuid))
+                || !memcmp(riid, &CPP_IID_ICOUNTER, sizeof(CppGuid))) {
+                *ppv = static_cast<ICppCounter*>(this);
+            } else if (!memcmp(riid, &CPP_IID_IRESETTABLE, sizeof(CppGuid))) {
+                *ppv = static_cast<ICppResettable*>(this);
+            } else {
+                *ppv = nullptr;
+                return static_cast<int32_t>(0x80004002u);
+            }
+            AddRef();
+            return 0;
+        }
+        uint32_t CPP_STDCALL AddRef() override { return ++refs; }
+        uint32_t CPP_STDCALL Release() override {
+            uint32_t left = --refs;
+            if (left == 0) delete this;
+            return left;
+        }
+        int32_t CPP_STDCALL Next() override { return ++value; }
+        void CPP_STDCALL Reset(int32_t v) override { value = v; }
+    };
+}}
+
+cpp! {{
+    #include <typeinfo>
+    #if !defined(_MSC_VER)
+    #include <cxxabi.h>
+    #endif
+    #include <cstdlib>
+
+    // ==========================================================================
+    // RTTI: a namespaced class with a private base, and a non-virtual diamond
+    // ==========================================================================
+
+    namespace cppvtable_fixture {
+        class IWalker {
+        public:
+            virtual int walk() = 0;
+        };
+
+        class Battery {
+        public:
+            virtual int charge() { return 100; }
+        };
+
+        class Robot final : public IWalker, private Battery {
+        public:
+            int walk() override { return charge() / 10; }
+        };
+
+        class IPart {
+        public:
+            virtual int part() = 0;
+        };
+
+        class LeftPart : public IPart {
+        public:
+            int part() override { return 1; }
+        };
+
+        class RightPart : public IPart {
+        public:
+            int part() override { return 2; }
+        };
+
+        // Two IPart subobjects: dynamic_cast<IPart*> is ambiguous
+        class Pair final : public LeftPart, public RightPart {};
+    }
 }}
 
 // =============================================================================
@@ -129,9 +412,16 @@ fn cpp_call_legs(animal: *mut c_void) -> i32 {
 }
 
 #[allow(dead_code)]
-fn delete_cpp_animal(animal: *mut c_void) {
-    cpp!(unsafe [animal as "ICppAnimal*"] {
-        delete animal;
+fn delete_cpp_dog(dog: *mut c_void) {
+    cpp!(unsafe [dog as "CppDog*"] {
+        delete dog;
+    })
+}
+
+#[allow(dead_code)]
+fn delete_cpp_cat(cat: *mut c_void) {
+    cpp!(unsafe [cat as "CppCat*"] {
+        delete cat;
     })
 }
 
@@ -142,6 +432,14 @@ fn cpp_call_rust_legs(rust_animal: *mut c_void) -> i32 {
     })
 }
 
+/// Calls an ICppAnimal slot and an ICppPet slot, packed as `legs * 100 + tricks`
+#[allow(dead_code)]
+fn cpp_call_rust_pet(rust_pet: *mut c_void) -> i32 {
+    cpp!(unsafe [rust_pet as "ICppPet*"] -> i32 as "int" {
+        return rust_pet->legs() * 100 + rust_pet->tricks();
+    })
+}
+
 // Multiple inheritance helpers
 #[allow(dead_code)]
 fn create_cpp_duck(speed: i32) -> *mut c_void {
@@ -171,6 +469,13 @@ fn cpp_duck_as_flyer(duck: *mut c_void) -> *mut c_void {
     })
 }
 
+#[allow(dead_code)]
+fn cpp_dynamic_cast_flyer_to_void(flyer: *mut c_void) -> *mut c_void {
+    cpp!(unsafe [flyer as "IFlyer*"] -> *mut c_void as "void*" {
+        return dynamic_cast<void*>(flyer);
+    })
+}
+
 #[allow(dead_code)]
 fn cpp_call_swim_speed(swimmer: *mut c_void) -> i32 {
     cpp!(unsafe [swimmer as "ISwimmer*"] -> i32 as "int" {
@@ -185,6 +490,285 @@ fn cpp_call_fly_speed(flyer: *mut c_void) -> i32 {
     })
 }
 
+// Virtual destructor helpers
+#[allow(dead_code)]
+fn create_cpp_square() -> *mut c_void {
+    cpp!(unsafe [] -> *mut c_void as "void*" {
+        return static_cast<ICppShape*>(new CppSquare());
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_call_sides(shape: *mut c_void) -> i32 {
+    cpp!(unsafe [shape as "ICppShape*"] -> i32 as "int" {
+        return shape->sides();
+    })
+}
+
+#[allow(dead_code)]
+fn delete_cpp_shape(shape: *mut c_void) {
+    cpp!(unsafe [shape as "ICppShape*"] {
+        delete shape;
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_shapes_destroyed() -> i32 {
+    cpp!(unsafe [] -> i32 as "int" {
+        return g_shapes_destroyed;
+    })
+}
+
+// Struct return helpers
+#[allow(dead_code)]
+fn create_cpp_widget() -> *mut c_void {
+    cpp!(unsafe [] -> *mut c_void as "void*" {
+        return static_cast<ICppWidget*>(new CppWidget());
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_widget_area(widget: *mut c_void) -> i32 {
+    cpp!(unsafe [widget as "ICppWidget*"] -> i32 as "int" {
+        CppSize size = widget->size();
+        return size.width * size.height;
+    })
+}
+
+#[allow(dead_code)]
+fn delete_cpp_widget(widget: *mut c_void) {
+    cpp!(unsafe [widget as "ICppWidget*"] {
+        delete static_cast<CppWidget*>(widget);
+    })
+}
+
+// Overload helpers
+#[allow(dead_code)]
+fn create_cpp_pen() -> *mut c_void {
+    cpp!(unsafe [] -> *mut c_void as "void*" {
+        return static_cast<ICppPen*>(new CppPen());
+    })
+}
+
+/// Calls every slot and packs the results, so a swapped slot shows up in the value
+#[allow(dead_code)]
+fn cpp_use_pen(pen: *mut c_void) -> i32 {
+    cpp!(unsafe [pen as "ICppPen*"] -> i32 as "int" {
+        return pen->draw(7) * 10000 + pen->draw(3, 4) * 10 + pen->width();
+    })
+}
+
+#[allow(dead_code)]
+fn delete_cpp_pen(pen: *mut c_void) {
+    cpp!(unsafe [pen as "ICppPen*"] {
+        delete static_cast<CppPen*>(pen);
+    })
+}
+
+// ms_abi helpers
+#[allow(dead_code)]
+fn create_cpp_gauge() -> *mut c_void {
+    cpp!(unsafe [] -> *mut c_void as "void*" {
+        return static_cast<ICppGauge*>(new CppGauge());
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_use_gauge(gauge: *mut c_void) -> i32 {
+    cpp!(unsafe [gauge as "ICppGauge*"] -> i32 as "int" {
+        return gauge->read(2) + gauge->mix(1, 2, 3, 4, 5.5f);
+    })
+}
+
+#[allow(dead_code)]
+fn delete_cpp_gauge(gauge: *mut c_void) {
+    cpp!(unsafe [gauge as "ICppGauge*"] {
+        delete static_cast<CppGauge*>(gauge);
+    })
+}
+
+// thiscall helpers
+#[allow(dead_code)]
+fn create_cpp_scale() -> *mut c_void {
+    cpp!(unsafe [] -> *mut c_void as "void*" {
+        return static_cast<ICppScale*>(new CppScale());
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_use_scale(scale: *mut c_void) -> i32 {
+    cpp!(unsafe [scale as "ICppScale*"] -> i32 as "int" {
+        return scale->weigh(5000);
+    })
+}
+
+#[allow(dead_code)]
+fn delete_cpp_scale(scale: *mut c_void) {
+    cpp!(unsafe [scale as "ICppScale*"] {
+        delete static_cast<CppScale*>(scale);
+    })
+}
+
+// virtual base helpers (pointers are ICppLabeled*)
+#[allow(dead_code)]
+fn create_cpp_badge(value: i32) -> *mut c_void {
+    cpp!(unsafe [value as "int"] -> *mut c_void as "void*" {
+        return static_cast<ICppLabeled*>(new CppBadge(value));
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_badge_as_tagged(labeled: *mut c_void) -> *mut c_void {
+    cpp!(unsafe [labeled as "ICppLabeled*"] -> *mut c_void as "void*" {
+        return static_cast<ICppTagged*>(static_cast<CppBadge*>(labeled));
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_labeled_as_named(labeled: *mut c_void) -> *mut c_void {
+    cpp!(unsafe [labeled as "ICppLabeled*"] -> *mut c_void as "void*" {
+        return static_cast<ICppNamed*>(labeled);
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_tagged_as_named(tagged: *mut c_void) -> *mut c_void {
+    cpp!(unsafe [tagged as "ICppTagged*"] -> *mut c_void as "void*" {
+        return static_cast<ICppNamed*>(tagged);
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_use_labeled(labeled: *mut c_void) -> i32 {
+    cpp!(unsafe [labeled as "ICppLabeled*"] -> i32 as "int" {
+        return labeled->label() + labeled->id();
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_use_tagged(tagged: *mut c_void) -> i32 {
+    cpp!(unsafe [tagged as "ICppTagged*"] -> i32 as "int" {
+        return tagged->tag() * 100 + tagged->id();
+    })
+}
+
+#[allow(dead_code)]
+fn delete_cpp_badge(labeled: *mut c_void) {
+    cpp!(unsafe [labeled as "ICppLabeled*"] {
+        delete static_cast<CppBadge*>(labeled);
+    })
+}
+
+// RTTI helpers
+#[allow(dead_code)]
+fn create_cpp_robot() -> *mut c_void {
+    cpp!(unsafe [] -> *mut c_void as "void*" {
+        return static_cast<cppvtable_fixture::IWalker*>(new cppvtable_fixture::Robot());
+    })
+}
+
+#[allow(dead_code)]
+fn delete_cpp_robot(walker: *mut c_void) {
+    cpp!(unsafe [walker as "cppvtable_fixture::IWalker*"] {
+        delete static_cast<cppvtable_fixture::Robot*>(walker);
+    })
+}
+
+#[allow(dead_code)]
+fn create_cpp_pair() -> *mut c_void {
+    cpp!(unsafe [] -> *mut c_void as "void*" {
+        return static_cast<cppvtable_fixture::RightPart*>(new cppvtable_fixture::Pair());
+    })
+}
+
+#[allow(dead_code)]
+fn delete_cpp_pair(right: *mut c_void) {
+    cpp!(unsafe [right as "cppvtable_fixture::RightPart*"] {
+        delete static_cast<cppvtable_fixture::Pair*>(right);
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_pair_as_left(right: *mut c_void) -> *mut c_void {
+    cpp!(unsafe [right as "cppvtable_fixture::RightPart*"] -> *mut c_void as "void*" {
+        return static_cast<cppvtable_fixture::LeftPart*>(static_cast<cppvtable_fixture::Pair*>(right));
+    })
+}
+
+/// `typeid(*flyer).name()`
+#[allow(dead_code)]
+fn cpp_flyer_typeid_name(flyer: *mut c_void) -> *const std::ffi::c_char {
+    cpp!(unsafe [flyer as "IFlyer*"] -> *const std::ffi::c_char as "const char*" {
+        return typeid(*flyer).name();
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_dynamic_cast_flyer_to_swimmer(flyer: *mut c_void) -> *mut c_void {
+    cpp!(unsafe [flyer as "IFlyer*"] -> *mut c_void as "void*" {
+        return dynamic_cast<ISwimmer*>(flyer);
+    })
+}
+
+/// Demangles a type name with `abi::__cxa_demangle`
+#[cfg(not(target_env = "msvc"))]
+#[allow(dead_code)]
+fn cpp_demangle(name: &std::ffi::CStr) -> String {
+    let name = name.as_ptr();
+    let mut buf = [0u8; 128];
+    let buf_ptr = buf.as_mut_ptr();
+    let len = buf.len();
+    cpp!(unsafe [name as "const char*", buf_ptr as "char*", len as "size_t"] {
+        int status = 0;
+        char* demangled = abi::__cxa_demangle(name, nullptr, nullptr, &status);
+        snprintf(buf_ptr, len, "%s", status == 0 ? demangled : "");
+        free(demangled);
+    });
+    std::ffi::CStr::from_bytes_until_nul(&buf)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+// COM helpers (pointers are ICppCounter*, owning one reference)
+#[allow(dead_code)]
+fn create_cpp_counter() -> *mut c_void {
+    cpp!(unsafe [] -> *mut c_void as "void*" {
+        return static_cast<ICppCounter*>(new CppCounter());
+    })
+}
+
+#[allow(dead_code)]
+fn cpp_counters_alive() -> i32 {
+    cpp!(unsafe [] -> i32 as "int" {
+        return cpp_counters_alive;
+    })
+}
+
+/// Queries `ICppResettable` from a counter, resets it, then calls `Next()`
+#[allow(dead_code)]
+fn cpp_reset_counter(counter: *mut c_void, value: i32) -> i32 {
+    cpp!(unsafe [counter as "ICppCounter*", value as "int32_t"] -> i32 as "int" {
+        void* resettable = nullptr;
+        if (counter->QueryInterface(&CPP_IID_IRESETTABLE, &resettable) != 0) return -1;
+        static_cast<ICppResettable*>(resettable)->Reset(value);
+        static_cast<ICppResettable*>(resettable)->Release();
+        return counter->Next();
+    })
+}
+
+/// Calls `Next()` twice, then releases the caller's reference
+#[allow(dead_code)]
+fn cpp_use_and_release_counter(counter: *mut c_void) -> i32 {
+    cpp!(unsafe [counter as "ICppCounter*"] -> i32 as "int" {
+        int32_t first = counter->Next();
+        int32_t second = counter->Next();
+        counter->Release();
+        return first * 10 + second;
+    })
+}
+
 // =============================================================================
 // Rust interface matching C++ ICppAnimal
 // =============================================================================
@@ -219,7 +803,7 @@ pub struct Duck {
     pub speed: i32,
 }
 
-#[cppvtable_impl(ISwimmer)]
+#[cppvtable_impl(ISwimmer, vtable_group(IFlyer))]
 impl Duck {
     fn swim_speed(&self) -> i32 {
         self.speed
@@ -229,7 +813,7 @@ impl Duck {
     }
 }
 
-#[cppvtable_impl(IFlyer)]
+#[cppvtable_impl(IFlyer, grouped)]
 impl Duck {
     fn fly_speed(&self) -> i32 {
         self.speed * 2
@@ -310,3 +894,392 @@ impl Cat {
         }
     }
 }
+
+// =============================================================================
+// Derived interface (matches C++ ICppPet)
+// =============================================================================
+
+#[cppvtable(extends(IAnimal))]
+pub trait IPet {
+    fn tricks(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Parrot {
+    vtable_i_pet: *const IPetVTable,
+    pub tricks: i32,
+}
+
+#[cppvtable_impl(IPet, extends(IAnimal))]
+impl Parrot {
+    fn speak(&self) {
+        println!("Parrot says: Hello!");
+    }
+
+    fn legs(&self) -> i32 {
+        2
+    }
+
+    fn tricks(&self) -> i32 {
+        self.tricks
+    }
+}
+
+impl Parrot {
+    pub fn new(tricks: i32) -> Self {
+        Parrot {
+            vtable_i_pet: Self::VTABLE_I_PET,
+            tricks,
+        }
+    }
+}
+
+// =============================================================================
+// Virtual destructor (matches C++ ICppShape)
+// =============================================================================
+
+#[cppvtable]
+pub trait IShape {
+    #[destructor]
+    fn destructor(&mut self);
+    fn sides(&self) -> i32;
+}
+
+/// Number of `Triangle` values dropped so far
+pub static TRIANGLES_DROPPED: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(0);
+
+#[repr(C)]
+pub struct Triangle {
+    vtable_i_shape: *const IShapeVTable,
+    pub label: String,
+}
+
+#[cppvtable_impl(IShape)]
+impl Triangle {
+    #[destructor]
+    fn destructor(&mut self);
+
+    fn sides(&self) -> i32 {
+        3
+    }
+}
+
+impl Triangle {
+    pub fn new(label: &str) -> Self {
+        Triangle {
+            vtable_i_shape: Self::VTABLE_I_SHAPE,
+            label: label.to_string(),
+        }
+    }
+}
+
+impl Drop for Triangle {
+    fn drop(&mut self) {
+        TRIANGLES_DROPPED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+// =============================================================================
+// Struct return values (matches C++ ICppWidget)
+// =============================================================================
+
+/// Matches C++ `CppSize`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Size {
+    pub width: i32,
+    pub height: i32,
+}
+
+#[cppvtable]
+pub trait IWidget {
+    fn size(&self) -> Size;
+    fn id(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Widget {
+    vtable_i_widget: *const IWidgetVTable,
+    pub size: Size,
+}
+
+#[cppvtable_impl(IWidget)]
+impl Widget {
+    fn size(&self) -> Size {
+        self.size
+    }
+
+    fn id(&self) -> i32 {
+        1
+    }
+}
+
+impl Widget {
+    pub fn new(width: i32, height: i32) -> Self {
+        Widget {
+            vtable_i_widget: Self::VTABLE_I_WIDGET,
+            size: Size { width, height },
+        }
+    }
+}
+
+// =============================================================================
+// Overloaded virtual methods (matches C++ ICppPen)
+// =============================================================================
+
+#[cppvtable]
+pub trait IPen {
+    #[overload_of = "draw"]
+    fn draw(&self, x: i32) -> i32;
+    fn width(&self) -> i32;
+    #[overload_of = "draw"]
+    fn draw_at(&self, x: i32, y: i32) -> i32;
+}
+
+#[repr(C)]
+pub struct Pen {
+    vtable_i_pen: *const IPenVTable,
+    pub width: i32,
+}
+
+#[cppvtable_impl(IPen)]
+impl Pen {
+    #[overload_of = "draw"]
+    fn draw(&self, x: i32) -> i32 {
+        x
+    }
+
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    #[overload_of = "draw"]
+    fn draw_at(&self, x: i32, y: i32) -> i32 {
+        x * 100 + y
+    }
+}
+
+impl Pen {
+    pub fn new(width: i32) -> Self {
+        Pen {
+            vtable_i_pen: Self::VTABLE_I_PEN,
+            width,
+        }
+    }
+}
+
+// =============================================================================
+// Microsoft x64 convention on every target (matches C++ ICppGauge)
+// =============================================================================
+
+#[cppvtable(win64)]
+pub trait IGauge {
+    fn read(&self, scale: i32) -> i32;
+    fn mix(&self, a: i32, b: i32, c: i32, d: i32, e: f32) -> i32;
+}
+
+#[repr(C)]
+pub struct Gauge {
+    vtable_i_gauge: *const IGaugeVTable,
+    pub base: i32,
+}
+
+#[cppvtable_impl(IGauge, win64)]
+impl Gauge {
+    fn read(&self, scale: i32) -> i32 {
+        self.base * scale
+    }
+
+    fn mix(&self, a: i32, b: i32, c: i32, d: i32, e: f32) -> i32 {
+        a * 10000 + b * 1000 + c * 100 + d * 10 + e as i32
+    }
+}
+
+impl Gauge {
+    pub fn new(base: i32) -> Self {
+        Gauge {
+            vtable_i_gauge: Self::VTABLE_I_GAUGE,
+            base,
+        }
+    }
+}
+
+// =============================================================================
+// thiscall on every x86 target (matches C++ ICppScale)
+// =============================================================================
+
+#[cppvtable(thiscall)]
+pub trait IScale {
+    fn weigh(&self, grams: i32) -> i32;
+}
+
+#[repr(C)]
+pub struct Scale {
+    vtable_i_scale: *const IScaleVTable,
+    pub offset: i32,
+}
+
+#[cppvtable_impl(IScale, thiscall)]
+impl Scale {
+    fn weigh(&self, grams: i32) -> i32 {
+        grams / 1000 + self.offset
+    }
+}
+
+impl Scale {
+    pub fn new(offset: i32) -> Self {
+        Scale {
+            vtable_i_scale: Self::VTABLE_I_SCALE,
+            offset,
+        }
+    }
+}
+
+// =============================================================================
+// Diamond through a virtual base (matches C++ ICppNamed/ICppLabeled/ICppTagged)
+// =============================================================================
+
+#[cppvtable]
+pub trait INamed {
+    fn id(&self) -> i32;
+}
+
+#[cppvtable(extends(virtual INamed))]
+pub trait ILabeled {
+    fn label(&self) -> i32;
+}
+
+#[cppvtable(extends(virtual INamed))]
+pub trait ITagged {
+    fn tag(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Badge {
+    vtable_i_labeled: *const ILabeledVTable,
+    vbptr_i_labeled: *const i32,
+    vtable_i_tagged: *const ITaggedVTable,
+    vbptr_i_tagged: *const i32,
+    pub value: i32,
+    vtable_i_named: *const INamedVTable,
+}
+
+#[cppvtable_impl(INamed)]
+impl Badge {
+    fn id(&self) -> i32 {
+        self.value
+    }
+}
+
+#[cppvtable_impl(ILabeled, extends(virtual INamed))]
+impl Badge {
+    fn label(&self) -> i32 {
+        self.value * 10
+    }
+}
+
+#[cppvtable_impl(ITagged, extends(virtual INamed))]
+impl Badge {
+    fn tag(&self) -> i32 {
+        self.value + 1
+    }
+}
+
+impl Badge {
+    pub fn new(value: i32) -> Self {
+        Badge {
+            vtable_i_labeled: Self::VTABLE_I_LABELED,
+            vbptr_i_labeled: Self::VBTABLE_I_LABELED,
+            vtable_i_tagged: Self::VTABLE_I_TAGGED,
+            vbptr_i_tagged: Self::VBTABLE_I_TAGGED,
+            value,
+            vtable_i_named: Self::VTABLE_I_NAMED,
+        }
+    }
+}
+
+// =============================================================================
+// COM interfaces (match C++ ICppCounter/ICppResettable)
+// =============================================================================
+
+use cppvtable::com::{ComRefCount, IUnknown, IUnknownVTable};
+use cppvtable::proc::{com_implement, com_interface};
+
+#[com_interface("6a1c7b52-3f0e-4d8a-9b21-5c0e7d441801")]
+pub trait ICounter {
+    fn next(&self) -> i32;
+}
+
+#[com_interface("6a1c7b52-3f0e-4d8a-9b21-5c0e7d441802")]
+pub trait IResettable {
+    fn reset(&self, value: i32);
+}
+
+/// Number of `Counter` values dropped so far
+pub static COUNTERS_DROPPED: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(0);
+
+/// Rust implementation of ICounter, freed by the last Release
+#[repr(C)]
+pub struct Counter {
+    vtable_i_counter: *const ICounterVTable,
+    ref_count: ComRefCount,
+    value: std::cell::Cell<i32>,
+}
+
+#[com_implement(ICounter, heap)]
+impl Counter {
+    fn next(&self) -> i32 {
+        self.value.set(self.value.get() + 1);
+        self.value.get()
+    }
+}
+
+impl Counter {
+    pub fn new(start: i32) -> Self {
+        Counter {
+            vtable_i_counter: Self::VTABLE_I_COUNTER,
+            ref_count: ComRefCount::new(),
+            value: std::cell::Cell::new(start),
+        }
+    }
+}
+
+impl Drop for Counter {
+    fn drop(&mut self) {
+        COUNTERS_DROPPED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+/// Rust implementation of both ICounter and IResettable, answering QueryInterface for each
+#[repr(C)]
+pub struct Tally {
+    vtable_i_counter: *const ICounterVTable,
+    vtable_i_resettable: *const IResettableVTable,
+    ref_count: ComRefCount,
+    value: std::cell::Cell<i32>,
+}
+
+#[com_implement(ICounter, IResettable, heap)]
+impl Tally {
+    fn next(&self) -> i32 {
+        self.value.set(self.value.get() + 1);
+        self.value.get()
+    }
+
+    fn reset(&self, value: i32) {
+        self.value.set(value);
+    }
+}
+
+impl Tally {
+    pub fn new(start: i32) -> Self {
+        Tally {
+            vtable_i_counter: Self::VTABLE_I_COUNTER,
+            vtable_i_resettable: Self::VTABLE_I_RESETTABLE,
+            ref_count: ComRefCount::new(),
+            value: std::cell::Cell::new(start),
+        }
+    }
+}
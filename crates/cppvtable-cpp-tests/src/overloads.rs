@@ -0,0 +1,30 @@
+//! Overloaded virtual method C++ interop tests
+//!
+//! MSVC groups same-name overloads and reverses them, g++ keeps declaration order;
+//! `#[overload_of]` must pick the same slots as the C++ compiler.
+
+use super::*;
+
+/// Test that Rust calls each C++ overload through the right slot
+#[test]
+fn test_rust_calls_cpp_overloads() {
+    unsafe {
+        let cpp_pen = create_cpp_pen();
+        let pen = IPen::from_ptr_mut(cpp_pen);
+
+        assert_eq!(pen.draw(7), 7);
+        assert_eq!(pen.draw_at(3, 4), 304);
+        assert_eq!(pen.width(), 2);
+
+        delete_cpp_pen(cpp_pen);
+    }
+}
+
+/// Test that C++ calls each Rust overload through the right slot
+#[test]
+fn test_cpp_calls_rust_overloads() {
+    let pen = Pen::new(5);
+    let pen_ptr = &pen as *const Pen as *mut c_void;
+
+    assert_eq!(cpp_use_pen(pen_ptr), 7 * 10000 + 304 * 10 + 5);
+}
@@ -0,0 +1,35 @@
+//! Struct return value C++ interop tests
+//!
+//! MSVC returns user-defined types from member functions through a hidden pointer,
+//! g++ follows the C rules; both must round-trip.
+
+use super::*;
+
+/// Test that Rust receives a struct returned by a C++ virtual method
+#[test]
+fn test_rust_calls_cpp_struct_return() {
+    unsafe {
+        let cpp_widget = create_cpp_widget();
+        let widget = IWidget::from_ptr_mut(cpp_widget);
+
+        assert_eq!(
+            widget.size(),
+            Size {
+                width: 3,
+                height: 4
+            }
+        );
+        assert_eq!(widget.id(), 9);
+
+        delete_cpp_widget(cpp_widget);
+    }
+}
+
+/// Test that C++ receives a struct returned by a Rust implementation
+#[test]
+fn test_cpp_calls_rust_struct_return() {
+    let widget = Widget::new(6, 7);
+    let widget_ptr = &widget as *const Widget as *mut c_void;
+
+    assert_eq!(cpp_widget_area(widget_ptr), 42);
+}
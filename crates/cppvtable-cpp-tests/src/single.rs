@@ -17,8 +17,8 @@ fn test_rust_calls_cpp_objects() {
         assert_eq!(dog_ref.legs(), 4);
         assert_eq!(cat_ref.legs(), 4);
 
-        delete_cpp_animal(cpp_dog);
-        delete_cpp_animal(cpp_cat);
+        delete_cpp_dog(cpp_dog);
+        delete_cpp_cat(cpp_cat);
     }
 }
 
@@ -36,6 +36,17 @@ fn test_cpp_calls_rust_objects() {
     assert_eq!(cpp_call_rust_legs(cat_ptr), 4);
 }
 
+/// Test that C++ calls base and derived slots of a Rust derived interface
+#[test]
+fn test_cpp_calls_rust_derived_interface() {
+    let parrot = Parrot::new(5);
+    let parrot_ptr = &parrot as *const Parrot as *mut c_void;
+
+    // ICppPet* is also a valid ICppAnimal*
+    assert_eq!(cpp_call_rust_legs(parrot_ptr), 2);
+    assert_eq!(cpp_call_rust_pet(parrot_ptr), 205);
+}
+
 /// Test vtable pointer is at offset 0
 #[test]
 fn test_vtable_at_offset_zero() {
@@ -74,6 +85,6 @@ fn test_cpp_rust_cpp_roundtrip() {
         assert_eq!(legs_via_rust, legs_via_cpp);
         assert_eq!(legs_via_rust, 4);
 
-        delete_cpp_animal(cpp_dog);
+        delete_cpp_dog(cpp_dog);
     }
 }
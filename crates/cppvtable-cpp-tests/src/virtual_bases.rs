@@ -0,0 +1,45 @@
+//! Virtual inheritance C++ interop tests
+//!
+//! A diamond `ICppLabeled`/`ICppTagged : public virtual ICppNamed`. Casts to the shared
+//! base read its offset from the object: the vbtable on MSVC, slot -3 of the vtable on g++.
+
+use super::*;
+use std::mem::offset_of;
+
+/// Test that Rust reaches the C++ virtual base through both sides of the diamond
+#[test]
+fn test_rust_casts_cpp_virtual_base() {
+    unsafe {
+        let cpp_labeled = create_cpp_badge(4);
+        let cpp_tagged = cpp_badge_as_tagged(cpp_labeled);
+
+        let labeled = ILabeled::from_ptr_mut(cpp_labeled);
+        assert_eq!(labeled.label(), 40);
+        let named: *mut INamed = &mut **labeled;
+        assert_eq!(named as *mut c_void, cpp_labeled_as_named(cpp_labeled));
+        assert_eq!(labeled.id(), 4);
+
+        let tagged = ITagged::from_ptr_mut(cpp_tagged);
+        assert_eq!(tagged.tag(), 5);
+        let named: *mut INamed = &mut **tagged;
+        assert_eq!(named as *mut c_void, cpp_tagged_as_named(cpp_tagged));
+        assert_eq!(tagged.id(), 4);
+
+        delete_cpp_badge(cpp_labeled);
+    }
+}
+
+/// Test that C++ finds the virtual base of a Rust object through both sides of the diamond
+#[test]
+fn test_cpp_casts_rust_virtual_base() {
+    let badge = Badge::new(3);
+    let base = &badge as *const Badge as *mut u8;
+    let named = unsafe { base.add(offset_of!(Badge, vtable_i_named)) } as *mut c_void;
+    let labeled = base as *mut c_void;
+    let tagged = unsafe { base.add(offset_of!(Badge, vtable_i_tagged)) } as *mut c_void;
+
+    assert_eq!(cpp_labeled_as_named(labeled), named);
+    assert_eq!(cpp_tagged_as_named(tagged), named);
+    assert_eq!(cpp_use_labeled(labeled), 30 + 3);
+    assert_eq!(cpp_use_tagged(tagged), 400 + 3);
+}
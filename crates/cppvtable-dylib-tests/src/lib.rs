@@ -0,0 +1,94 @@
+//! Interfaces and an implementation shared by a host and a plugin, for the cross-library
+//! RTTI tests.
+//!
+//! The tests link this crate (the host) and load its cdylib build (the plugin), so each
+//! side has its own copy of every interface ID. Stable IDs (`stable_id`, `id = "..."`)
+//! still agree, pointer-based ones don't.
+
+use cppvtable::proc::{cppvtable, cppvtable_impl};
+
+#[cppvtable(stable_id)]
+pub trait IShape {
+    fn area(&self) -> i32;
+}
+
+#[cppvtable(id = "cppvtable.tests.INamed")]
+pub trait INamed {
+    fn name_len(&self) -> i32;
+}
+
+/// Pointer-based ID: differs between the host and the plugin
+#[cppvtable]
+pub trait ILocal {
+    fn local(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Square {
+    vtable_i_shape: *const IShapeVTable,
+    vtable_i_named: *const INamedVTable,
+    vtable_i_local: *const ILocalVTable,
+    side: i32,
+}
+
+#[cppvtable_impl(IShape, rtti(IShape, INamed, ILocal))]
+impl Square {
+    fn area(&self) -> i32 {
+        self.side * self.side
+    }
+}
+
+#[cppvtable_impl(INamed, rtti)]
+impl Square {
+    fn name_len(&self) -> i32 {
+        "square".len() as i32
+    }
+}
+
+#[cppvtable_impl(ILocal, rtti)]
+impl Square {
+    fn local(&self) -> i32 {
+        self.side
+    }
+}
+
+impl Square {
+    pub fn new(side: i32) -> Self {
+        Self {
+            vtable_i_shape: Self::VTABLE_I_SHAPE,
+            vtable_i_named: Self::VTABLE_I_NAMED,
+            vtable_i_local: Self::VTABLE_I_LOCAL,
+            side,
+        }
+    }
+}
+
+/// Only stable interfaces, so its `TypeInfo` is equal on both sides
+#[repr(C)]
+pub struct Circle {
+    vtable_i_shape: *const IShapeVTable,
+    radius: i32,
+}
+
+#[cppvtable_impl(IShape, rtti(IShape))]
+impl Circle {
+    fn area(&self) -> i32 {
+        3 * self.radius * self.radius
+    }
+}
+
+/// Create a `Square` in this copy of the crate, as its `IShape` pointer
+#[unsafe(no_mangle)]
+pub extern "C" fn cppvtable_create_square(side: i32) -> *mut IShape {
+    Box::into_raw(Box::new(Square::new(side))) as *mut IShape
+}
+
+/// Create a `Circle` in this copy of the crate, as its `IShape` pointer
+#[unsafe(no_mangle)]
+pub extern "C" fn cppvtable_create_circle(radius: i32) -> *mut IShape {
+    let circle = Circle {
+        vtable_i_shape: Circle::VTABLE_I_SHAPE,
+        radius,
+    };
+    Box::into_raw(Box::new(circle)) as *mut IShape
+}
@@ -0,0 +1,84 @@
+//! Tests for stable interface IDs across libraries: the host (this test) casts objects
+//! created by the cdylib build of `cppvtable_dylib_tests`
+
+#![cfg(unix)]
+
+use cppvtable::rtti::{HasTypeInfo, type_info_of};
+use cppvtable_dylib_tests::{Circle, ILocal, INamed, IShape, Square};
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+
+unsafe extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlerror() -> *const c_char;
+}
+
+const RTLD_NOW: c_int = 2;
+
+/// Look up a constructor in the plugin, loaded from next to the test binary.
+///
+/// The objects it creates are leaked rather than freed across the library boundary.
+fn plugin_fn(name: &str) -> extern "C" fn(i32) -> *mut IShape {
+    let mut path = std::env::current_exe().unwrap();
+    path.set_file_name(format!(
+        "{}cppvtable_dylib_tests{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    ));
+    let path = CString::new(path.to_str().unwrap()).unwrap();
+    let name = CString::new(name).unwrap();
+
+    unsafe {
+        let handle = dlopen(path.as_ptr(), RTLD_NOW);
+        assert!(
+            !handle.is_null(),
+            "dlopen failed: {:?}",
+            CStr::from_ptr(dlerror())
+        );
+        let symbol = dlsym(handle, name.as_ptr());
+        assert!(!symbol.is_null(), "missing symbol {:?}", name);
+        std::mem::transmute::<*mut c_void, extern "C" fn(i32) -> *mut IShape>(symbol)
+    }
+}
+
+#[test]
+fn test_plugin_is_a_separate_copy() {
+    let shape = plugin_fn("cppvtable_create_square")(3);
+
+    unsafe {
+        let type_info = type_info_of(shape as *const c_void);
+        assert_eq!(type_info.type_name, "Square");
+        assert!(!std::ptr::eq(type_info, Square::TYPE_INFO));
+        assert_ne!(type_info, Square::TYPE_INFO);
+    }
+}
+
+#[test]
+fn test_stable_ids_match_across_libraries() {
+    let shape = plugin_fn("cppvtable_create_square")(3);
+
+    unsafe {
+        let shape = &mut *shape;
+        assert_eq!(shape.area(), 9);
+        assert!(shape.implements::<IShape>());
+        assert!(shape.implements::<INamed>());
+        assert_eq!(shape.query_mut::<INamed>().unwrap().name_len(), 6);
+
+        // The host's pointer-based ID is not the plugin's
+        assert!(!shape.implements::<ILocal>());
+        assert!(shape.query::<ILocal>().is_none());
+    }
+}
+
+#[test]
+fn test_type_info_compares_by_value() {
+    let shape = plugin_fn("cppvtable_create_circle")(2);
+
+    unsafe {
+        assert_eq!((*shape).area(), 12);
+        let type_info = type_info_of(shape as *const c_void);
+        assert!(!std::ptr::eq(type_info, Circle::TYPE_INFO));
+        assert_eq!(type_info, Circle::TYPE_INFO);
+        assert_eq!(type_info.interfaces[0], Circle::INTERFACE_INFO_I_SHAPE);
+    }
+}
@@ -1,4 +1,4 @@
-//! Procedural macros for C++ vtable interop (MSVC ABI)
+//! Procedural macros for C++ vtable interop (MSVC and Itanium ABIs)
 //!
 //! Provides:
 //! - `#[cppvtable]` - Define a C++ interface (generates vtable struct)
@@ -9,14 +9,49 @@
 //! ## Calling Conventions
 //!
 //! **C++ vtables (`cppvtable`):**
-//! - x86: `thiscall` (this in ECX)
+//! - x86: `thiscall` (this in ECX) for the MSVC ABI and on Windows (MSVC, MinGW),
+//!   `cdecl` (this on stack) elsewhere, like g++ on i686 Linux. `thiscall`/`cdecl` force one.
 //! - x64: `C` (this as first param)
 //!
 //! **COM interfaces (`com_interface`):**
 //! - x86: `stdcall` (this on stack)
 //! - x64: `C` (this as first param)
 //!
-//! Supports explicit slot indices via `#[slot(N)]` attribute on methods.
+//! On x86_64, `win64` or `sysv64` forces the Microsoft x64 or System V convention on every
+//! target (e.g. `ms_abi` vtables under Wine). The default is `extern "system"`, which is
+//! Microsoft x64 on Windows and System V elsewhere.
+//!
+//! Supports explicit slot indices via `#[slot(N)]` attribute on interface methods.
+//! Implementations take slots from the interface and are checked against its signatures.
+//!
+//! ## C++ ABI
+//!
+//! `abi = "msvc"` or `abi = "itanium"` selects the vtable layout. Without it, both
+//! layouts are generated behind `#[cfg(target_env = "msvc")]` so the target decides.
+//! Itanium static vtables get the offset-to-top/typeinfo prefix before slot 0.
+//!
+//! ## Virtual Destructors
+//!
+//! `#[destructor] fn destructor(&mut self);` marks the virtual destructor slot. It becomes
+//! the MSVC scalar deleting destructor (one slot, `flags & 1` frees) or the Itanium D1/D0
+//! pair (two slots), and runs the implementing struct's `Drop`. The implementation repeats
+//! the bodiless declaration; heap objects are expected to live in a `Box`.
+//!
+//! ## Struct Return Values
+//!
+//! MSVC member functions return user-defined types through a hidden pointer passed after
+//! `this`. Under the MSVC ABI, methods whose return type is not a primitive, pointer or
+//! known scalar alias get that signature in the vtable; the wrappers hide it on both
+//! sides. `NonNull<T>` and `Option` of a reference, `NonNull<T>` or function pointer count
+//! as pointers; any other `Option` is returned like a struct. `#[scalar_return]` on an
+//! interface method opts out for type aliases of scalars; implementations follow it.
+//!
+//! ## Overloaded Methods
+//!
+//! Rust has no overloading, so C++ overloads get distinct Rust names tagged with
+//! `#[overload_of = "draw"]`. MSVC puts an overload set together at its first declaration
+//! in reverse order; Itanium keeps declaration order. `#[slot(N)]` can't be combined with
+//! overloads.
 //!
 //! ## RTTI Support
 //!
@@ -26,7 +61,7 @@
 
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, quote_spanned};
 use syn::{
     Attribute, Expr, FnArg, Ident, ImplItem, ItemImpl, ItemTrait, Lit, Meta, Pat, TraitItem, Type,
     parse_macro_input, spanned::Spanned,
@@ -46,16 +81,46 @@ fn crate_path(internal: bool) -> TokenStream2 {
     }
 }
 
-/// Transforms a type to use `$crate::` prefix for cppvtable types.
+/// Returns the path prefix for invoking an interface's vtable macro, which is
+/// re-exported under the interface's own name (see [`vtable_macro_name`]).
+///
+/// IUnknown's is named through cppvtable. Those of user interfaces are expanded inside
+/// the user crate, where `#[macro_export]` macros can't be named by absolute path, so
+/// they are invoked by the interface's name, in scope wherever the interface is.
+fn base_macro_path(base: &Ident, krate: &TokenStream2) -> TokenStream2 {
+    if is_iunknown(base) {
+        quote! { #krate:: }
+    } else {
+        quote! {}
+    }
+}
+
+/// Name of the `#[macro_export]` macro that builds an interface's forwarders
+/// (`forwarders` arm) and vtable value (`base_vtable` arm) for an implementing struct.
+///
+/// It is re-exported next to the interface under the interface's name, so importing
+/// `IFoo` also imports the macro and `IFoo!(...)` resolves like any path, in any module
+/// or crate. Its first argument is `[path]` of the cppvtable crate at the call site.
+fn vtable_macro_name(interface: &Ident) -> Ident {
+    format_ident!("__cppvtable_{}", interface.to_string().to_lowercase())
+}
+
+/// Whether `base` is one of cppvtable's IUnknown flavours (`IUnknown`, `IUnknownWin64`,
+/// `IUnknownSysv64`), which only differ in their x86_64 calling convention.
+fn is_iunknown(base: &Ident) -> bool {
+    base == "IUnknown" || base == "IUnknownWin64" || base == "IUnknownSysv64"
+}
+
+/// Transforms a type to use the cppvtable crate path for cppvtable types.
 ///
 /// This is used when generating declarative macros that will be invoked from user code.
-/// In that context, `$crate` resolves to the crate where the macro is defined (cppvtable).
+/// `krate` is the cppvtable path inside the macro body, the `$($krate)*` its caller passes.
 ///
 /// Types transformed:
 /// - `GUID` -> `$crate::GUID`
 /// - `HRESULT` -> `$crate::HRESULT`
 /// - `c_void` -> `::std::ffi::c_void`
-fn qualify_type_for_macro(ty: &Type) -> TokenStream2 {
+fn qualify_type_for_macro(ty: &Type, krate: &TokenStream2) -> TokenStream2 {
     match ty {
         Type::Path(type_path) => {
             // Check if it's a simple identifier we need to qualify
@@ -63,8 +128,8 @@ fn qualify_type_for_macro(ty: &Type) -> TokenStream2 {
                 let name = ident.to_string();
                 match name.as_str() {
                     "GUID" | "HRESULT" => {
-                        // These are cppvtable types, need $crate:: prefix
-                        return quote! { $crate::#ident };
+                        // These are cppvtable types, need the crate prefix
+                        return quote! { #krate::#ident };
                     }
                     "c_void" => {
                         // Use fully qualified std path
@@ -77,7 +142,7 @@ fn qualify_type_for_macro(ty: &Type) -> TokenStream2 {
             quote! { #ty }
         }
         Type::Ptr(type_ptr) => {
-            let inner = qualify_type_for_macro(&type_ptr.elem);
+            let inner = qualify_type_for_macro(&type_ptr.elem, krate);
             if type_ptr.const_token.is_some() {
                 if type_ptr.mutability.is_some() {
                     quote! { *const mut #inner }
@@ -91,7 +156,7 @@ fn qualify_type_for_macro(ty: &Type) -> TokenStream2 {
             }
         }
         Type::Reference(type_ref) => {
-            let inner = qualify_type_for_macro(&type_ref.elem);
+            let inner = qualify_type_for_macro(&type_ref.elem, krate);
             let lifetime = &type_ref.lifetime;
             if type_ref.mutability.is_some() {
                 quote! { &#lifetime mut #inner }
@@ -108,22 +173,361 @@ fn qualify_type_for_macro(ty: &Type) -> TokenStream2 {
 // Configuration types for vtable generation
 // =============================================================================
 
-/// Calling convention for vtable methods
+/// Calling convention for vtable methods on x86
 #[derive(Clone, Copy, PartialEq, Eq, Default)]
 enum CallingConvention {
-    /// C++ thiscall: this in ECX on x86, first param on x64
+    /// Follow the C++ compiler: thiscall for the MSVC ABI and on Windows (MSVC, MinGW),
+    /// cdecl elsewhere (g++/clang on i686 Linux pass this on the stack)
     #[default]
+    Auto,
+    /// C++ thiscall: this in ECX on x86, first param on x64
     Thiscall,
+    /// cdecl: this on stack on x86, caller cleans up
+    Cdecl,
     /// COM stdcall: this on stack on x86, first param on x64
     Stdcall,
 }
 
+/// Calling convention for vtable methods on x86_64
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum X64CallingConvention {
+    /// `extern "system"`: Microsoft x64 on Windows, System V everywhere else
+    #[default]
+    System,
+    /// `extern "win64"`: Microsoft x64 on every target (`__attribute__((ms_abi))`)
+    Win64,
+    /// `extern "sysv64"`: System V on every target (`__attribute__((sysv_abi))`)
+    Sysv64,
+}
+
+impl X64CallingConvention {
+    /// The IUnknown flavour COM interfaces with this convention extend
+    fn iunknown(self) -> Ident {
+        match self {
+            X64CallingConvention::System => format_ident!("IUnknown"),
+            X64CallingConvention::Win64 => format_ident!("IUnknownWin64"),
+            X64CallingConvention::Sysv64 => format_ident!("IUnknownSysv64"),
+        }
+    }
+}
+
+/// Calling conventions of the cfg-gated copies of each function pointer/forwarder.
+///
+/// x86 uses the thiscall/cdecl/stdcall choice (`Auto` resolved per ABI and target OS);
+/// x86_64 gets its own copy only when `win64` or `sysv64` is selected, everything else
+/// uses `extern "system"`.
+fn arch_conventions(
+    calling_convention: CallingConvention,
+    x64_convention: X64CallingConvention,
+    abi: CppAbi,
+) -> Vec<(TokenStream2, TokenStream2)> {
+    let mut conventions = match (calling_convention, abi) {
+        // MSVC only targets Windows, where member functions are thiscall
+        (CallingConvention::Thiscall, _) | (CallingConvention::Auto, CppAbi::Msvc) => {
+            vec![(quote! { target_arch = "x86" }, quote! { "thiscall" })]
+        }
+        (CallingConvention::Cdecl, _) => {
+            vec![(quote! { target_arch = "x86" }, quote! { "C" })]
+        }
+        (CallingConvention::Stdcall, _) => {
+            vec![(quote! { target_arch = "x86" }, quote! { "stdcall" })]
+        }
+        // MinGW uses thiscall too; g++/clang elsewhere use cdecl
+        (CallingConvention::Auto, CppAbi::Auto | CppAbi::Itanium) => vec![
+            (
+                quote! { all(target_arch = "x86", target_os = "windows") },
+                quote! { "thiscall" },
+            ),
+            (
+                quote! { all(target_arch = "x86", not(target_os = "windows")) },
+                quote! { "C" },
+            ),
+        ],
+    };
+
+    let x64_cc = match x64_convention {
+        X64CallingConvention::System => None,
+        X64CallingConvention::Win64 => Some(quote! { "win64" }),
+        X64CallingConvention::Sysv64 => Some(quote! { "sysv64" }),
+    };
+    match x64_cc {
+        None => conventions.push((quote! { not(target_arch = "x86") }, quote! { "system" })),
+        Some(x64_cc) => {
+            conventions.push((quote! { target_arch = "x86_64" }, x64_cc));
+            conventions.push((
+                quote! { not(any(target_arch = "x86", target_arch = "x86_64")) },
+                quote! { "system" },
+            ));
+        }
+    }
+
+    conventions
+        .into_iter()
+        .map(|(predicate, cc)| (quote! { #[cfg(#predicate)] }, cc))
+        .collect()
+}
+
+/// Options after the GUID of `#[com_interface]` or the interfaces of `#[com_implement]`
+#[derive(Default)]
+struct ComOptions {
+    /// `win64` / `sysv64`
+    x64_convention: X64CallingConvention,
+    /// `heap` (`#[com_implement]` only)
+    heap: bool,
+    /// `aggregatable` (`#[com_implement]` only)
+    aggregatable: bool,
+    /// `aggregate(field: IInner, ...)` (`#[com_implement]` only), repeatable
+    aggregates: Vec<(Ident, Vec<Ident>)>,
+    /// `tear_off(IRare, ...)` (`#[com_implement]` only)
+    tear_offs: Vec<Ident>,
+    /// `weak` (`#[com_implement]` only)
+    weak: bool,
+    /// `extends(IBase)` (`#[com_interface]` only)
+    base_interface: Option<Ident>,
+    /// `internal` (`#[com_interface]` only): interfaces defined inside cppvtable
+    internal: bool,
+    /// `panic = "..."` (`#[com_interface]` only)
+    panic_policy: PanicPolicy,
+}
+
+/// Parse the options of `#[com_interface]` (`win64`, `sysv64`, `extends(IBase)`,
+/// `panic = "..."`) or of
+/// `#[com_implement]` (`win64`, `sysv64`, `heap`, `aggregatable`, `aggregate(...)`,
+/// `tear_off(...)`, `weak`, when `for_impl` is set).
+fn parse_com_options(
+    tokens: &[proc_macro2::TokenTree],
+    for_impl: bool,
+) -> Result<ComOptions, syn::Error> {
+    let mut options = ComOptions::default();
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            proc_macro2::TokenTree::Ident(ident) if ident == "win64" => {
+                options.x64_convention = X64CallingConvention::Win64;
+            }
+            proc_macro2::TokenTree::Ident(ident) if ident == "sysv64" => {
+                options.x64_convention = X64CallingConvention::Sysv64;
+            }
+            proc_macro2::TokenTree::Ident(ident) if for_impl && ident == "heap" => {
+                options.heap = true;
+            }
+            proc_macro2::TokenTree::Ident(ident) if for_impl && ident == "aggregatable" => {
+                options.aggregatable = true;
+            }
+            proc_macro2::TokenTree::Ident(ident) if for_impl && ident == "aggregate" => {
+                options
+                    .aggregates
+                    .push(parse_aggregate_option(ident, tokens.get(i + 1))?);
+                i += 1;
+            }
+            proc_macro2::TokenTree::Ident(ident) if for_impl && ident == "weak" => {
+                options.weak = true;
+            }
+            proc_macro2::TokenTree::Ident(ident) if !for_impl && ident == "internal" => {
+                options.internal = true;
+            }
+            proc_macro2::TokenTree::Ident(ident) if !for_impl && ident == "panic" => {
+                options.panic_policy = parse_panic_option(ident, tokens, &mut i)?;
+                continue;
+            }
+            proc_macro2::TokenTree::Ident(ident) if for_impl && ident == "tear_off" => {
+                options
+                    .tear_offs
+                    .extend(parse_interface_list_option(ident, tokens.get(i + 1))?);
+                i += 1;
+            }
+            proc_macro2::TokenTree::Ident(ident) if !for_impl && ident == "extends" => {
+                let (base, is_virtual) = parse_extends_option(ident, tokens, &mut i)?;
+                if is_virtual {
+                    return Err(syn::Error::new(
+                        base.span(),
+                        "COM interfaces can't have virtual bases",
+                    ));
+                }
+                options.base_interface = Some(base);
+                continue;
+            }
+            proc_macro2::TokenTree::Punct(punct) if punct.as_char() == ',' => {}
+            other => {
+                let expected = if for_impl {
+                    "'win64', 'sysv64', 'heap', 'weak', 'aggregatable', 'aggregate(...)' or 'tear_off(...)'"
+                } else {
+                    "'win64', 'sysv64', 'extends(...)' or 'panic = \"...\"'"
+                };
+                return Err(syn::Error::new(
+                    other.span(),
+                    format!("unknown option '{}', expected {}", other, expected),
+                ));
+            }
+        }
+        i += 1;
+    }
+    Ok(options)
+}
+
+/// Parse the `(field: IInner, ...)` group after `aggregate`
+fn parse_aggregate_option(
+    ident: &Ident,
+    group: Option<&proc_macro2::TokenTree>,
+) -> Result<(Ident, Vec<Ident>), syn::Error> {
+    let usage = "expected aggregate(field: IInterface, ...)";
+    let Some(proc_macro2::TokenTree::Group(group)) = group else {
+        return Err(syn::Error::new(ident.span(), usage));
+    };
+    let tokens: Vec<_> = group.stream().into_iter().collect();
+    let field = match (tokens.first(), tokens.get(1)) {
+        (
+            Some(proc_macro2::TokenTree::Ident(field)),
+            Some(proc_macro2::TokenTree::Punct(colon)),
+        ) if colon.as_char() == ':' => field.clone(),
+        _ => return Err(syn::Error::new(group.span(), usage)),
+    };
+    let mut interfaces = Vec::new();
+    for token in &tokens[2..] {
+        match token {
+            proc_macro2::TokenTree::Ident(interface) => interfaces.push(interface.clone()),
+            proc_macro2::TokenTree::Punct(punct) if punct.as_char() == ',' => {}
+            other => return Err(syn::Error::new(other.span(), usage)),
+        }
+    }
+    if interfaces.is_empty() {
+        return Err(syn::Error::new(group.span(), usage));
+    }
+    Ok((field, interfaces))
+}
+
+/// Parse the `(IFoo, ...)` group after an interface list option such as `tear_off`,
+/// `vtable_group` or `rtti`; `option` is the option's name, used in errors
+fn parse_interface_list_option(
+    option: &Ident,
+    group: Option<&proc_macro2::TokenTree>,
+) -> Result<Vec<Ident>, syn::Error> {
+    let usage = format!("expected {option}(IInterface, ...)");
+    let Some(proc_macro2::TokenTree::Group(group)) = group else {
+        return Err(syn::Error::new(option.span(), usage));
+    };
+    let mut interfaces = Vec::new();
+    for token in group.stream() {
+        match token {
+            proc_macro2::TokenTree::Ident(interface) => interfaces.push(interface),
+            proc_macro2::TokenTree::Punct(punct) if punct.as_char() == ',' => {}
+            other => return Err(syn::Error::new(other.span(), usage)),
+        }
+    }
+    if interfaces.is_empty() {
+        return Err(syn::Error::new(group.span(), usage));
+    }
+    Ok(interfaces)
+}
+
+/// Name of the struct generated for a tear-off interface of a `#[com_implement]` object
+fn tear_off_struct_name(struct_name: &Ident, interface: &Ident) -> Ident {
+    format_ident!("__{}{}TearOff", struct_name, interface)
+}
+
+/// C++ ABI used for vtable layout
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum CppAbi {
+    /// Pick per target: MSVC on `target_env = "msvc"`, Itanium everywhere else
+    #[default]
+    Auto,
+    /// MSVC: the vtable pointer points at slot 0, nothing is emitted before it
+    Msvc,
+    /// Itanium (g++/clang): offset-to-top and a typeinfo pointer precede slot 0
+    Itanium,
+}
+
+impl CppAbi {
+    /// Expand to the concrete ABIs to generate code for, each with the `#[cfg]`
+    /// attribute that selects it. `Auto` yields both layouts, gated on the target.
+    fn variants(self) -> Vec<(CppAbi, TokenStream2)> {
+        match self {
+            CppAbi::Auto => vec![
+                (CppAbi::Msvc, quote! { #[cfg(target_env = "msvc")] }),
+                (CppAbi::Itanium, quote! { #[cfg(not(target_env = "msvc"))] }),
+            ],
+            abi => vec![(abi, quote! {})],
+        }
+    }
+
+    /// Expression for the runtime `cppvtable::CppAbi` value
+    fn runtime_value(self, krate: &TokenStream2) -> TokenStream2 {
+        match self {
+            CppAbi::Auto => quote! { #krate::CppAbi::NATIVE },
+            CppAbi::Msvc => quote! { #krate::CppAbi::Msvc },
+            CppAbi::Itanium => quote! { #krate::CppAbi::Itanium },
+        }
+    }
+}
+
+/// What generated entry points do when the Rust method they call panics
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum PanicPolicy {
+    /// Report the panic and abort the process
+    #[default]
+    Abort,
+    /// Return `Default::default()`
+    Default,
+    /// Return `E_UNEXPECTED` from methods returning `HRESULT`, abort in the others
+    HResult,
+}
+
+impl PanicPolicy {
+    /// Expression for the runtime `cppvtable::PanicPolicy` value
+    fn runtime_value(self, krate: &TokenStream2) -> TokenStream2 {
+        match self {
+            PanicPolicy::Abort => quote! { #krate::PanicPolicy::Abort },
+            PanicPolicy::Default => quote! { #krate::PanicPolicy::Default },
+            PanicPolicy::HResult => quote! { #krate::PanicPolicy::HResult },
+        }
+    }
+
+    /// Expression an entry point returns after the method at `location` panicked
+    fn fallback(
+        self,
+        output: &syn::ReturnType,
+        krate: &TokenStream2,
+        location: &str,
+    ) -> TokenStream2 {
+        let returns_hresult = matches!(
+            output,
+            syn::ReturnType::Type(_, ty) if matches!(&**ty, Type::Path(path)
+                if path.path.segments.last().is_some_and(|segment| segment.ident == "HRESULT"))
+        );
+        match self {
+            PanicPolicy::Default => quote! { ::std::default::Default::default() },
+            PanicPolicy::HResult if returns_hresult => quote! { #krate::E_UNEXPECTED },
+            _ => quote! { #krate::panic::abort(#location) },
+        }
+    }
+}
+
+/// Evaluate `body` under `cppvtable::panic::catch`, or `fallback` if it panics.
+///
+/// `body` runs in a `move` closure, so it can't return from the enclosing function.
+fn contain_panic(
+    krate: &TokenStream2,
+    location: &str,
+    body: TokenStream2,
+    fallback: TokenStream2,
+) -> TokenStream2 {
+    quote! {
+        match #krate::panic::catch(#location, move || { #body }) {
+            ::std::option::Option::Some(value) => value,
+            ::std::option::Option::None => #fallback,
+        }
+    }
+}
+
 /// Interface ID type
 #[derive(Clone, Default)]
 enum InterfaceId {
     /// Pointer-based ID (address of a static)
     #[default]
     Pointer,
+    /// Stable ID, the same in every library: a hash of the interface's path and signature
+    /// (`stable_id`), or of an explicit `id = "..."`
+    Stable(Option<syn::LitStr>),
     /// GUID-based ID (COM style)
     Guid {
         data1: u32,
@@ -138,11 +542,21 @@ enum InterfaceId {
 /// Configuration for vtable generation
 #[derive(Clone, Default)]
 struct VTableConfig {
-    /// Calling convention (thiscall vs stdcall)
+    /// Calling convention on x86 (auto, thiscall, cdecl or stdcall)
     calling_convention: CallingConvention,
+    /// Calling convention on x86_64 (system, win64 or sysv64)
+    x64_convention: X64CallingConvention,
+    /// C++ ABI the vtable layout follows
+    abi: CppAbi,
+    /// What the interface's entry points do when a method panics
+    panic_policy: PanicPolicy,
     /// Base interface to inherit from (e.g., IUnknown)
     /// When set, the generated vtable embeds the base vtable as the first field
     base_interface: Option<syn::Ident>,
+    /// `extends(virtual Base)`: the base is a C++ virtual base. Itanium shares the vtable
+    /// pointer with it (a nearly empty primary base), MSVC leaves it out of the vtable and
+    /// reaches it through the vbptr that follows the vtable pointer.
+    virtual_base: bool,
     /// Interface ID type
     iid: InterfaceId,
     /// Slot overrides from attribute
@@ -150,50 +564,89 @@ struct VTableConfig {
     /// Internal mode: use `crate::` instead of `cppvtable::` for paths
     /// This is used when defining interfaces inside the cppvtable crate itself
     internal: bool,
-    /// Skip generating forwarder macros ({interface}_forwarders! and {interface}_base_vtable!)
-    /// Use this when the forwarders need to be manually defined (e.g., IUnknown with COM types)
+    /// Skip generating the vtable macro (`{Interface}!(forwarders ...)`/`(base_vtable ...)`)
+    /// Use this when the forwarders need to be manually defined (e.g., IUnknown with COM types).
+    /// `#[cppvtable_impl]` also asks the macro (`hidden_return`) for MSVC return values.
     no_forwarders: bool,
 }
 
 impl VTableConfig {
-    /// Generate the x86 calling convention token
-    fn x86_calling_conv(&self) -> TokenStream2 {
-        match self.calling_convention {
-            CallingConvention::Thiscall => quote! { "thiscall" },
-            CallingConvention::Stdcall => quote! { "stdcall" },
-        }
+    /// Calling conventions of the cfg-gated copies of each function pointer/forwarder
+    fn arch_conventions(&self) -> Vec<(TokenStream2, TokenStream2)> {
+        arch_conventions(self.calling_convention, self.x64_convention, self.abi)
     }
 }
 
 /// Configuration for vtable implementation generation
 #[derive(Clone, Default)]
 struct ImplConfig {
-    /// Calling convention (thiscall vs stdcall)
+    /// Calling convention on x86 (auto, thiscall, cdecl or stdcall)
     calling_convention: CallingConvention,
-    /// Base interface to inherit from (e.g., IUnknown)
-    /// When set, generates forwarders and base vtable entry via the base's macros:
-    /// - `{base}_forwarders!` - generates wrapper functions
-    /// - `{base}_base_vtable!` - generates base vtable initializer
-    /// - `{base}_methods!` - generates method implementations on the struct
+    /// Calling convention on x86_64 (system, win64 or sysv64)
+    x64_convention: X64CallingConvention,
+    /// Whether the conventions above were given; otherwise the wrappers take the
+    /// interface's (`IFoo!(with_conventions ...)`)
+    explicit_conventions: bool,
+    /// C++ ABI the static vtable follows (Itanium adds the offset-to-top/typeinfo prefix).
+    /// `Auto` follows the interface (`IFoo!(if_abi ...)`).
+    abi: CppAbi,
+    /// Base interface from `extends(IBase)`, checked against the interface's vtable
     base_interface: Option<syn::Ident>,
-    /// First slot index for user methods (e.g., 3 for IUnknown base)
-    /// Caller must set this based on the base interface's slot count
-    first_slot: usize,
+    /// `extends(virtual Base)`: emit the vbase offset (Itanium) and vbtable (MSVC) that
+    /// lead from the interface's vtable pointer to the struct's `Base` vtable pointer
+    virtual_base: bool,
+    /// `checked`: guard every call with the struct's `borrow_flag`, so a reentrant call
+    /// that would alias a `&mut self` call in progress is rejected
+    checked: bool,
     /// Whether to generate RTTI info
     generate_rtti: bool,
+    /// `rtti`: the static vtable carries the struct's `TypeInfo` at slot -1
+    rtti: bool,
+    /// `rtti(IFoo, IBar, ...)`: this block also defines the struct's `TypeInfo`, listing
+    /// these interfaces, and implements `HasTypeInfo`
+    rtti_interfaces: Vec<Ident>,
     /// IID constant name for COM (e.g., IID_ICALCULATOR)
     iid_const: Option<syn::Ident>,
     /// Internal mode: use `crate::` instead of `cppvtable::` for paths
     internal: bool,
+    /// Build the whole vtable with the interface's own vtable macro (`{Interface}!`),
+    /// which recurses through its `extends` chain.
+    /// The impl block then only supplies methods, so inherited ones can live in it or
+    /// in any other impl block of the struct.
+    interface_forwarders: bool,
+    /// `#[com_implement(I, heap)]`: the last `Release` drops and frees the object as a
+    /// `Box`, and the struct implements `ComHeap` so `ComPtr::new` can allocate it
+    com_heap: bool,
+    /// Every COM interface of the object, identity (IUnknown) first. When set, the block
+    /// gets the object's IUnknown methods, whose QueryInterface answers for all of them.
+    com_interfaces: Vec<Ident>,
+    /// `#[com_implement(I, aggregatable)]`: the object's own IUnknown is a separate
+    /// non-delegating vtable (`vtable_non_delegating`), and the IUnknown methods of its
+    /// interfaces delegate to the outer object when it is aggregated
+    com_aggregatable: bool,
+    /// `aggregate(field: IInner, ...)`: QueryInterface forwards these interfaces to the
+    /// inner object held in `field`
+    com_aggregates: Vec<(Ident, Vec<Ident>)>,
+    /// `tear_off(IRare, ...)`: QueryInterface creates a tear-off object for these
+    /// interfaces instead of returning a vtable field of the struct
+    com_tear_offs: Vec<Ident>,
+    /// `#[com_implement(I, weak)]`: QueryInterface answers IWeakReferenceSource
+    com_weak: bool,
+    /// `no_cpp_class`: leave `CppClass` to another impl block of the struct with a
+    /// `#[destructor]` instead of implementing it from this block's
+    no_cpp_class: bool,
+    /// `vtable_group(IFoo, ...)`: this block's Itanium vtable is the struct's primary
+    /// vtable, emitted in one static with the `grouped` vtables of these interfaces
+    vtable_group: Vec<Ident>,
+    /// `grouped`: this block's Itanium vtable goes in the `vtable_group(...)` of the
+    /// struct's primary impl block instead of a static of its own
+    grouped: bool,
 }
 
 impl ImplConfig {
-    /// Generate the x86 calling convention token
-    fn x86_calling_conv(&self) -> TokenStream2 {
-        match self.calling_convention {
-            CallingConvention::Thiscall => quote! { "thiscall" },
-            CallingConvention::Stdcall => quote! { "stdcall" },
-        }
+    /// Calling conventions of the cfg-gated copies of each wrapper function
+    fn arch_conventions(&self) -> Vec<(TokenStream2, TokenStream2)> {
+        arch_conventions(self.calling_convention, self.x64_convention, self.abi)
     }
 }
 
@@ -527,6 +980,264 @@ fn parse_slot_attr(attrs: &[Attribute]) -> Option<usize> {
     None
 }
 
+/// Parse `#[overload_of = "name"]` from a list of attributes.
+fn parse_overload_of(attrs: &[Attribute]) -> Result<Option<String>, syn::Error> {
+    for attr in attrs {
+        if attr.path().is_ident("overload_of") {
+            if let Meta::NameValue(name_value) = &attr.meta
+                && let Expr::Lit(expr_lit) = &name_value.value
+                && let Lit::Str(lit_str) = &expr_lit.lit
+            {
+                return Ok(Some(lit_str.value()));
+            }
+            return Err(syn::Error::new(
+                attr.span(),
+                "expected #[overload_of = \"name\"] with the name of the C++ overload set",
+            ));
+        }
+    }
+    Ok(None)
+}
+
+/// A method's vtable placement request, in declaration order
+struct SlotRequest {
+    name: Ident,
+    /// Name of the C++ overload set from `#[overload_of = "..."]`
+    overload_of: Option<String>,
+    /// Slot from `#[slot(N)]` or the interface attribute
+    explicit_slot: Option<usize>,
+}
+
+impl SlotRequest {
+    /// The C++ name this method is declared with (overloads share it)
+    fn cpp_name(&self) -> String {
+        self.overload_of
+            .clone()
+            .unwrap_or_else(|| self.name.to_string())
+    }
+}
+
+/// Check whether any two methods share a C++ name (i.e. the layout has overloads).
+fn has_overloads(requests: &[SlotRequest]) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    requests.iter().any(|r| !seen.insert(r.cpp_name()))
+}
+
+/// Order methods the way the C++ ABI lays them out and assign slot indices.
+///
+/// Itanium keeps declaration order. MSVC gathers overloads (methods sharing a C++ name)
+/// at the position of the first one and emits them in reverse declaration order.
+/// Explicit slots can't be combined with overloads, as their position is ABI-dependent.
+///
+/// Returns `(declaration index, slot)` pairs in vtable order.
+fn layout_slots(
+    requests: &[SlotRequest],
+    first_slot: usize,
+    abi: CppAbi,
+) -> Result<Vec<(usize, usize)>, syn::Error> {
+    let names: Vec<String> = requests.iter().map(SlotRequest::cpp_name).collect();
+    let overload_count = |name: &String| names.iter().filter(|n| *n == name).count();
+
+    for (request, name) in requests.iter().zip(&names) {
+        if request.explicit_slot.is_some() && overload_count(name) > 1 {
+            return Err(syn::Error::new(
+                request.name.span(),
+                format!(
+                    "method '{}': #[slot(N)] can't be used on overloads of '{}', their order depends on the ABI",
+                    request.name, name
+                ),
+            ));
+        }
+    }
+
+    let order: Vec<usize> = if abi == CppAbi::Msvc {
+        let mut order = Vec::with_capacity(requests.len());
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                continue;
+            }
+            // First of its overload set: place the whole set here, last declared first
+            order.extend((i..names.len()).rev().filter(|&j| names[j] == *name));
+        }
+        order
+    } else {
+        (0..requests.len()).collect()
+    };
+
+    let mut slots = Vec::with_capacity(order.len());
+    let mut next_slot = first_slot;
+    for index in order {
+        let request = &requests[index];
+        let slot = if let Some(explicit_slot) = request.explicit_slot {
+            if explicit_slot < first_slot {
+                return Err(syn::Error::new(
+                    request.name.span(),
+                    format!(
+                        "slot({}) for method '{}' conflicts with base interface methods (first available: {})",
+                        explicit_slot, request.name, first_slot
+                    ),
+                ));
+            }
+            if explicit_slot < next_slot {
+                return Err(syn::Error::new(
+                    request.name.span(),
+                    format!(
+                        "slot({}) for method '{}' would overlap with previous slots (next available: {})",
+                        explicit_slot, request.name, next_slot
+                    ),
+                ));
+            }
+            explicit_slot
+        } else {
+            next_slot
+        };
+        next_slot = slot + 1;
+        slots.push((index, slot));
+    }
+
+    Ok(slots)
+}
+
+/// Check for the #[destructor] marker on a method.
+fn is_destructor(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("destructor"))
+}
+
+/// Validate a #[destructor] signature: `fn name(&mut self)` with no parameters or return type.
+///
+/// The actual vtable entry (MSVC scalar deleting destructor, Itanium D1/D0 pair) is
+/// generated by the macros; the Rust side of it is the struct's `Drop` impl.
+fn validate_destructor(sig: &syn::Signature) -> Result<(), syn::Error> {
+    let is_mut_receiver = matches!(
+        sig.inputs.first(),
+        Some(FnArg::Receiver(r)) if r.reference.is_some() && r.mutability.is_some()
+    );
+    if !is_mut_receiver || sig.inputs.len() != 1 || !matches!(sig.output, syn::ReturnType::Default)
+    {
+        return Err(syn::Error::new(
+            sig.ident.span(),
+            format!(
+                "method '{}': #[destructor] must be declared as `fn {}(&mut self);` (the vtable entry is generated, cleanup goes in Drop)",
+                sig.ident, sig.ident
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Type names MSVC returns in registers from member functions: primitives, the C type
+/// aliases from `std::ffi`/`core::ffi` and common Windows scalar typedefs.
+const SCALAR_TYPE_NAMES: &[&str] = &[
+    "bool",
+    "char",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "isize",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "usize",
+    "f32",
+    "f64",
+    "c_char",
+    "c_schar",
+    "c_uchar",
+    "c_short",
+    "c_ushort",
+    "c_int",
+    "c_uint",
+    "c_long",
+    "c_ulong",
+    "c_longlong",
+    "c_ulonglong",
+    "c_float",
+    "c_double",
+    "HRESULT",
+    "BOOL",
+    "BYTE",
+    "WORD",
+    "DWORD",
+    "UINT",
+    "ULONG",
+    "LONG",
+];
+
+/// Check if a type is a scalar (primitive, pointer, or known scalar alias).
+///
+/// Paths that are not in [`SCALAR_TYPE_NAMES`], `NonNull<T>` or an `Option` of a
+/// non-null pointer are assumed to be user-defined types.
+fn is_scalar_type(ty: &Type) -> bool {
+    match ty {
+        Type::Ptr(_) | Type::Reference(_) | Type::BareFn(_) | Type::Never(_) => true,
+        Type::Tuple(tuple) => tuple.elems.is_empty(),
+        Type::Paren(paren) => is_scalar_type(&paren.elem),
+        Type::Group(group) => is_scalar_type(&group.elem),
+        Type::Path(type_path) if type_path.qself.is_none() => {
+            match type_path.path.segments.last() {
+                Some(segment) if segment.ident == "Option" => {
+                    single_type_argument(segment).is_some_and(is_non_null_pointer)
+                }
+                Some(segment) if segment.ident == "NonNull" => {
+                    single_type_argument(segment).is_some()
+                }
+                Some(segment) => SCALAR_TYPE_NAMES.contains(&&*segment.ident.to_string()),
+                None => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Whether `Option<ty>` is a nullable pointer: `ty` is a reference, `NonNull<T>` or a
+/// function pointer. Any other `Option` is an enum, returned like a user-defined type.
+fn is_non_null_pointer(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(_) | Type::BareFn(_) => true,
+        Type::Paren(paren) => is_non_null_pointer(&paren.elem),
+        Type::Group(group) => is_non_null_pointer(&group.elem),
+        Type::Path(type_path) => {
+            type_path.qself.is_none()
+                && type_path.path.segments.last().is_some_and(|segment| {
+                    segment.ident == "NonNull" && single_type_argument(segment).is_some()
+                })
+        }
+        _ => false,
+    }
+}
+
+/// The `T` of a path segment `Name<T>`
+fn single_type_argument(segment: &syn::PathSegment) -> Option<&Type> {
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+            match args.args.first() {
+                Some(syn::GenericArgument::Type(ty)) => Some(ty),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether a method returns through a hidden pointer on MSVC.
+///
+/// MSVC member functions return every user-defined type, even a small POD struct that a
+/// free function would return in registers, through a pointer passed after `this`.
+/// `#[scalar_return]` opts out for aliases of scalar types the macro can't recognize.
+fn has_hidden_return(output: &syn::ReturnType, attrs: &[Attribute]) -> bool {
+    match output {
+        syn::ReturnType::Default => false,
+        syn::ReturnType::Type(_, ty) => {
+            !is_scalar_type(ty)
+                && !attrs
+                    .iter()
+                    .any(|attr| attr.path().is_ident("scalar_return"))
+        }
+    }
+}
+
 /// Convert interface name to vtable field name (snake_case with vtable_ prefix)
 /// IFoo -> vtable_i_foo
 /// IAnimal -> vtable_i_animal
@@ -566,7 +1277,7 @@ fn cppvtable_internal(config: VTableConfig, input: ItemTrait) -> Result<TokenStr
     let trait_name = &input.ident;
     let vtable_name = format_ident!("{}VTable", trait_name);
     let vis = &input.vis;
-    let x86_cc = config.x86_calling_conv();
+    let arch_conventions = config.arch_conventions();
 
     // Extract generics from the trait for generic interface support
     // When a trait has generic type parameters (e.g., `trait IInArchive<T>`),
@@ -592,6 +1303,8 @@ fn cppvtable_internal(config: VTableConfig, input: ItemTrait) -> Result<TokenStr
     // - Own method slots start at 0 (relative to derived portion)
     // - Total slot count = base slot count + own method count
     let krate = crate_path(config.internal);
+    // Inside the vtable macro, the cppvtable path passed by the implementation
+    let macro_krate = quote! { $($krate)* };
 
     // For generic interfaces, we need to propagate the type parameter to the base vtable.
     // When a generic interface extends IUnknown, we use IUnknownVTable<T> so the
@@ -620,68 +1333,47 @@ fn cppvtable_internal(config: VTableConfig, input: ItemTrait) -> Result<TokenStr
 
     // Collect methods with their slot indices
     struct MethodInfo {
-        slot: usize,
+        explicit_slot: Option<usize>,
+        overload_of: Option<String>,
         name: Ident,
         param_names: Vec<Ident>,
         param_types: Vec<Type>,
         output: syn::ReturnType,
+        destructor: bool,
+        hidden_return: bool,
+        raw_receiver: bool,
     }
 
     let mut methods: Vec<MethodInfo> = Vec::new();
-    let mut next_slot = first_slot;
+    let mut seen_destructor = false;
 
     for item in &input.items {
         if let TraitItem::Fn(method) = item {
             let method_name = method.sig.ident.clone();
             let output = method.sig.output.clone();
 
-            // Check for slot override from attribute, then #[slot(N)] on method
-            let slot = if let Some(&explicit_slot) =
-                config.slot_overrides.get(&method_name.to_string())
-            {
-                if explicit_slot < first_slot {
-                    return Err(syn::Error::new(
-                        method_name.span(),
-                        format!(
-                            "slot({}) for method '{}' conflicts with base interface methods (first available: {})",
-                            explicit_slot, method_name, first_slot
-                        ),
-                    ));
-                }
-                if explicit_slot < next_slot {
-                    return Err(syn::Error::new(
-                        method_name.span(),
-                        format!(
-                            "slot({}) for method '{}' would overlap with previous slots (next available: {})",
-                            explicit_slot, method_name, next_slot
-                        ),
-                    ));
-                }
-                explicit_slot
-            } else if let Some(explicit_slot) = parse_slot_attr(&method.attrs) {
-                if explicit_slot < first_slot {
-                    return Err(syn::Error::new(
-                        method_name.span(),
-                        format!(
-                            "slot({}) for method '{}' conflicts with base interface methods (first available: {})",
-                            explicit_slot, method_name, first_slot
-                        ),
-                    ));
-                }
-                if explicit_slot < next_slot {
+            let destructor = is_destructor(&method.attrs);
+            if destructor {
+                validate_destructor(&method.sig)?;
+                if seen_destructor {
                     return Err(syn::Error::new(
                         method_name.span(),
                         format!(
-                            "slot({}) for method '{}' would overlap with previous slots (next available: {})",
-                            explicit_slot, method_name, next_slot
+                            "method '{}': an interface can only have one #[destructor]",
+                            method_name
                         ),
                     ));
                 }
-                explicit_slot
-            } else {
-                next_slot
-            };
-            next_slot = slot + 1;
+                seen_destructor = true;
+            }
+
+            // Check for slot override from attribute, then #[slot(N)] on method
+            let explicit_slot = config
+                .slot_overrides
+                .get(&method_name.to_string())
+                .copied()
+                .or_else(|| parse_slot_attr(&method.attrs));
+            let overload_of = parse_overload_of(&method.attrs)?;
 
             // Collect parameter names and types (skip self)
             let params: Vec<_> = method
@@ -701,101 +1393,144 @@ fn cppvtable_internal(config: VTableConfig, input: ItemTrait) -> Result<TokenStr
                 .collect();
 
             methods.push(MethodInfo {
-                slot,
+                explicit_slot,
+                overload_of,
                 name: method_name,
                 param_names: params.iter().map(|(n, _)| n.clone()).collect(),
                 param_types: params.iter().map(|(_, t)| t.clone()).collect(),
+                hidden_return: has_hidden_return(&method.sig.output, &method.attrs),
                 output,
+                destructor,
+                raw_receiver: method
+                    .attrs
+                    .iter()
+                    .any(|attr| attr.path().is_ident("raw_receiver")),
             });
         }
     }
 
-    // Sort by slot index
-    methods.sort_by_key(|m| m.slot);
-
-    // Generate vtable fields, filling gaps with dummy entries
-    let mut vtable_fields = Vec::new();
-    let mut wrapper_methods = Vec::new();
-    let mut current_slot = 0usize;
-
-    for method in &methods {
-        // Fill gaps with dummy entries
-        while current_slot < method.slot {
-            let dummy_name = format_ident!("__reserved_slot_{}", current_slot);
-            vtable_fields.push(quote! {
-                #[cfg(target_arch = "x86")]
-                pub #dummy_name: unsafe extern #x86_cc fn(this: #self_ptr_type),
-                #[cfg(not(target_arch = "x86"))]
-                pub #dummy_name: unsafe extern "system" fn(this: #self_ptr_type)
-            });
-            current_slot += 1;
-        }
-
-        let method_name = &method.name;
-        let param_names = &method.param_names;
-        let param_types = &method.param_types;
-        let output = &method.output;
-
-        // Generate vtable field (function pointer) using configured calling convention
-        // Uses self_ptr_type: *mut T for generic interfaces, *mut c_void for non-generic
-        vtable_fields.push(quote! {
-            #[cfg(target_arch = "x86")]
-            pub #method_name: unsafe extern #x86_cc fn(
-                this: #self_ptr_type
-                #(, #param_names: #param_types)*
-            ) #output,
-            #[cfg(not(target_arch = "x86"))]
-            pub #method_name: unsafe extern "system" fn(
-                this: #self_ptr_type
-                #(, #param_names: #param_types)*
-            ) #output
-        });
-
-        // Generate wrapper method on the base struct
-        // Cast self to the appropriate pointer type (c_void or T)
-        wrapper_methods.push(quote! {
-            #[inline]
-            pub unsafe fn #method_name(&mut self #(, #param_names: #param_types)*) #output {
-                ((*self.vtable).#method_name)(
-                    self as *mut Self as #self_ptr_type
-                    #(, #param_names)*
-                )
-            }
-        });
-
-        current_slot += 1;
-    }
-
-    // Total slot count for VTableLayout
-    let total_slot_count = current_slot;
+    let slot_requests: Vec<_> = methods
+        .iter()
+        .map(|m| SlotRequest {
+            name: m.name.clone(),
+            overload_of: m.overload_of.clone(),
+            explicit_slot: m.explicit_slot,
+        })
+        .collect();
 
     // Generate interface ID based on config
     let iid_static_name = format_ident!("IID_{}", trait_name.to_string().to_uppercase());
 
     // Generate IID definition and methods based on config
-    let (iid_definition, iid_methods) = match &config.iid {
-        InterfaceId::Pointer => {
-            let def = quote! {
-                /// Unique interface ID for RTTI (address of this static serves as ID)
-                #[doc(hidden)]
-                #vis static #iid_static_name: u8 = 0;
+    let (iid_definition, iid_methods, rtti_interface_id) = match &config.iid {
+        InterfaceId::Pointer | InterfaceId::Stable(_) => {
+            let (def, id_methods, interface_id) = if let InterfaceId::Stable(id) = &config.iid {
+                // Hash the path and signature so a changed interface gets a new ID
+                let key = match id {
+                    Some(id) => quote! { #id },
+                    None => {
+                        let mut signature = String::new();
+                        if let Some(base) = &config.base_interface {
+                            signature += &format!(": {}", base);
+                        }
+                        for m in &methods {
+                            let param_types = &m.param_types;
+                            let output = &m.output;
+                            if let Some(slot) = m.explicit_slot {
+                                signature += &format!(" #[slot({})]", slot);
+                            }
+                            signature += &format!(
+                                " fn {}({}) {};",
+                                m.name,
+                                quote! { #(#param_types),* },
+                                quote! { #output }
+                            );
+                        }
+                        let name = trait_name.to_string();
+                        quote! { concat!(module_path!(), "::", #name, #signature) }
+                    }
+                };
+                let def = quote! {
+                    /// Stable interface ID for RTTI, the same in every library using this interface
+                    #[doc(hidden)]
+                    #vis const #iid_static_name: #krate::rtti::InterfaceId = #krate::rtti::stable_interface_id(#key);
+                };
+                // A hash, not an address: no `interface_id_ptr()`
+                (def, quote! {}, quote! { #iid_static_name })
+            } else {
+                let def = quote! {
+                    /// Unique interface ID for RTTI (address of this static serves as ID)
+                    #[doc(hidden)]
+                    #vis static #iid_static_name: u8 = 0;
+                };
+                let id_methods = quote! {
+                    /// Get the interface ID pointer for this interface type (const-compatible)
+                    #[inline]
+                    #[must_use]
+                    pub const fn interface_id_ptr() -> *const u8 {
+                        &#iid_static_name as *const u8
+                    }
+
+                    /// Get the interface ID for this interface type as usize
+                    #[inline]
+                    #[must_use]
+                    pub fn interface_id() -> usize {
+                        Self::interface_id_ptr() as usize
+                    }
+                };
+                (
+                    def,
+                    id_methods,
+                    quote! { #krate::rtti::InterfaceId::Address(&#iid_static_name as *const u8) },
+                )
             };
             let methods = quote! {
-                /// Get the interface ID pointer for this interface type (const-compatible)
+                #id_methods
+
+                /// Cast to another interface of the same object through the RTTI at slot -1
+                /// of its vtables, `None` if the object doesn't implement it.
+                ///
+                /// # Safety
+                ///
+                /// - The object's vtables must carry RTTI (`#[cppvtable_impl(..., rtti)]`)
                 #[inline]
                 #[must_use]
-                pub const fn interface_id_ptr() -> *const u8 {
-                    &#iid_static_name as *const u8
+                pub unsafe fn query<Target: #krate::rtti::RttiInterface>(&self) -> Option<&Target> {
+                    unsafe {
+                        let this = self as *const Self as *const ::std::ffi::c_void;
+                        let target = #krate::rtti::query(this, Target::INTERFACE_ID);
+                        (target as *const Target).as_ref()
+                    }
                 }
 
-                /// Get the interface ID for this interface type as usize
+                /// Mutable version of [`query`](Self::query).
+                ///
+                /// # Safety
+                ///
+                /// - The object's vtables must carry RTTI (`#[cppvtable_impl(..., rtti)]`)
                 #[inline]
                 #[must_use]
-                pub fn interface_id() -> usize {
-                    Self::interface_id_ptr() as usize
+                pub unsafe fn query_mut<Target: #krate::rtti::RttiInterface>(&mut self) -> Option<&mut Target> {
+                    unsafe {
+                        let this = self as *mut Self as *const ::std::ffi::c_void;
+                        let target = #krate::rtti::query(this, Target::INTERFACE_ID);
+                        (target as *mut Target).as_mut()
+                    }
+                }
+
+                /// Whether the object implements another interface, according to its RTTI.
+                ///
+                /// # Safety
+                ///
+                /// - The object's vtables must carry RTTI (`#[cppvtable_impl(..., rtti)]`)
+                #[inline]
+                #[must_use]
+                pub unsafe fn implements<Target: #krate::rtti::RttiInterface>(&self) -> bool {
+                    unsafe { #krate::rtti::type_info_of(self as *const Self as *const ::std::ffi::c_void) }
+                        .implements(Target::INTERFACE_ID)
                 }
             };
-            (def, methods)
+            (def, methods, Some(interface_id))
         }
         InterfaceId::Guid {
             data1,
@@ -829,55 +1564,49 @@ fn cppvtable_internal(config: VTableConfig, input: ItemTrait) -> Result<TokenStr
                     &#iid_static_name
                 }
             };
-            (def, methods)
+            // The RTTI ID of a COM interface is a stable ID from its GUID
+            let guid = format!(
+                "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+                data1, data2, data3, d4_0, d4_1, d4_2, d4_3, d4_4, d4_5, d4_6, d4_7
+            );
+            let interface_id = quote! { #krate::rtti::stable_interface_id(#guid) };
+            (def, methods, Some(interface_id))
         }
         InterfaceId::None => {
             // No IID generation - user defines their own IID externally
-            (quote! {}, quote! {})
-        }
-    };
-
-    // Generate the slot count expression
-    // If we have a base, total = base slot count + own slot count
-    let own_slot_count = total_slot_count;
-    let slot_count_expr = if let Some(ref base_ident) = config.base_interface {
-        if has_type_params {
-            // Generic case: base interface is also generic
-            quote! { <#base_ident #type_generics as #krate::VTableLayout>::SLOT_COUNT + #own_slot_count }
-        } else {
-            quote! { <#base_ident as #krate::VTableLayout>::SLOT_COUNT + #own_slot_count }
+            (quote! {}, quote! {}, None)
         }
-    } else {
-        quote! { #own_slot_count }
     };
 
-    // Generate vtable struct with optional base field and generic parameters
-    let vtable_struct = if let Some(ref base_field) = base_vtable_field {
-        quote! {
-            /// VTable struct for #trait_name
-            #[repr(C)]
-            #vis struct #vtable_name #impl_generics #where_clause {
-                #base_field,
-                #(#vtable_fields),*
+    // The RTTI identity, with the chain of non-virtual bases that share the vtable
+    let rtti_interface_impl = rtti_interface_id.map(|interface_id| {
+        let chain = match &config.base_interface {
+            Some(base_ident) if !config.virtual_base => {
+                let base_type = if has_type_params {
+                    quote! { #base_ident #type_generics }
+                } else {
+                    quote! { #base_ident }
+                };
+                quote! {
+                    const CHAIN: &'static #krate::rtti::InterfaceChain = &#krate::rtti::InterfaceChain::new(
+                        #interface_id,
+                        Some(<#base_type as #krate::rtti::RttiInterface>::CHAIN),
+                    );
+                }
             }
-        }
-    } else {
+            _ => quote! {},
+        };
         quote! {
-            /// VTable struct for #trait_name
-            #[repr(C)]
-            #vis struct #vtable_name #impl_generics #where_clause {
-                #(#vtable_fields),*
+            impl #impl_generics #krate::rtti::RttiInterface for #trait_name #type_generics #where_clause {
+                const INTERFACE_ID: #krate::rtti::InterfaceId = #interface_id;
+                #chain
             }
         }
-    };
+    });
 
     // Generate IUnknown forwarding methods if extending IUnknown
     // Both generic and non-generic use .base, but with different pointer types
-    let iunknown_wrappers = if config
-        .base_interface
-        .as_ref()
-        .is_some_and(|name| name == "IUnknown")
-    {
+    let iunknown_wrappers = if config.base_interface.as_ref().is_some_and(is_iunknown) {
         // Both generic and non-generic interfaces access IUnknown through .base
         // Generic uses typed pointer (*mut T), non-generic uses *mut c_void
         let this_cast = if has_type_params {
@@ -927,192 +1656,1012 @@ fn cppvtable_internal(config: VTableConfig, input: ItemTrait) -> Result<TokenStr
         quote! {}
     };
 
-    // Generate {interface}_forwarders! and {interface}_base_vtable! macros
-    // These allow this interface to be used as a base for other interfaces
-    let interface_lower = trait_name.to_string().to_lowercase();
-    let forwarders_macro_name = format_ident!("{}_forwarders", interface_lower);
-    let base_vtable_macro_name = format_ident!("{}_base_vtable", interface_lower);
-
-    // Generate wrapper function code for each method in the forwarders macro
-    let mut forwarder_wrappers = Vec::new();
-    let mut vtable_entries = Vec::new();
-
-    for method in &methods {
-        let method_name = &method.name;
-        let method_name_str = method_name.to_string();
-        let param_names = &method.param_names;
-        let param_types = &method.param_types;
-        let output = &method.output;
-
-        // Qualify types for use in declarative macro context
-        // This transforms GUID -> $crate::GUID, HRESULT -> $crate::HRESULT, etc.
-        let qualified_param_types: Vec<_> =
-            param_types.iter().map(qualify_type_for_macro).collect();
-
-        // Build parameter list for function signature with qualified types
-        let params_with_types: Vec<_> = param_names
-            .iter()
-            .zip(qualified_param_types.iter())
-            .map(|(name, ty)| quote! { #name: #ty })
-            .collect();
+    // Signature checks for implementations: `#[cppvtable_impl]` passes each method to the
+    // matching `__check_{name}`, so a parameter or return type that differs from the
+    // interface is a type error on that method. The receiver is left to the implementation.
+    let signature_checks: Vec<_> = methods
+        .iter()
+        .filter(|m| !m.destructor)
+        .map(|m| {
+            let check_fn = format_ident!("__check_{}", m.name);
+            let param_types = &m.param_types;
+            let output = &m.output;
+            if m.raw_receiver {
+                return quote! {
+                    #[doc(hidden)]
+                    pub const fn #check_fn<__Receiver>(_method: unsafe fn(*mut __Receiver #(, #param_types)*) #output) {}
+                };
+            }
+            quote! {
+                #[doc(hidden)]
+                pub const fn #check_fn<__Receiver>(_method: fn(__Receiver #(, #param_types)*) #output) {}
+            }
+        })
+        .collect();
+    // The methods this interface declares itself, which every `#[cppvtable_impl]` of it
+    // must define (inherited ones belong to the base interface)
+    let method_names = methods.iter().map(|m| m.name.to_string());
+    let declared_methods = quote! {
+        /// Methods declared by this interface, checked against `#[cppvtable_impl]` blocks
+        #[doc(hidden)]
+        pub const __METHODS: &'static [&'static str] = &[#(#method_names),*];
+    };
 
-        // Qualify return type too
-        let qualified_output = match output {
-            syn::ReturnType::Default => quote! {},
-            syn::ReturnType::Type(arrow, ty) => {
-                let qualified_ty = qualify_type_for_macro(ty);
-                quote! { #arrow #qualified_ty }
+    // Upcast to the base wrapper: the base vtable is embedded first in ours, so an object
+    // pointer with our vtable is also a valid base interface pointer. Chains deref through
+    // every level. A virtual base is wherever the object says it is.
+    let base_deref = if let Some(base_ident) = &config.base_interface
+        && config.virtual_base
+    {
+        quote! {
+            impl ::std::ops::Deref for #trait_name {
+                type Target = #base_ident;
+
+                #[inline]
+                fn deref(&self) -> &Self::Target {
+                    unsafe {
+                        let base = (self as *const Self as *const u8).offset(self.virtual_base_offset());
+                        &*(base as *const Self::Target)
+                    }
+                }
             }
-        };
 
-        // Build the method call arguments (just parameter names)
-        let call_args: Vec<_> = param_names.iter().map(|name| quote! { #name }).collect();
-
-        // Generate wrapper functions for x86 and x64
-        // Uses paste! for identifier concatenation with $struct_name and $interface_name
-        forwarder_wrappers.push(quote! {
-            #[allow(non_snake_case)]
-            #[cfg(target_arch = "x86")]
-            unsafe extern #x86_cc fn [<__ $struct_name __ $interface_name __ #method_name_str>](
-                this: *mut ::std::ffi::c_void
-                #(, #params_with_types)*
-            ) #qualified_output {
-                unsafe {
-                    let offset = ::std::mem::offset_of!($struct_type, $vtable_field);
-                    let adjusted = (this as *mut u8).sub(offset) as *mut $struct_type;
-                    (*adjusted).#method_name(#(#call_args),*)
+            impl ::std::ops::DerefMut for #trait_name {
+                #[inline]
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    unsafe {
+                        let base = (self as *mut Self as *mut u8).offset(self.virtual_base_offset());
+                        &mut *(base as *mut Self::Target)
+                    }
                 }
             }
+        }
+    } else if let Some(base_ident) = &config.base_interface {
+        let base_type = if has_type_params {
+            quote! { #base_ident #type_generics }
+        } else {
+            quote! { #base_ident }
+        };
+        quote! {
+            impl #impl_generics ::std::ops::Deref for #trait_name #type_generics #where_clause {
+                type Target = #base_type;
 
-            #[allow(non_snake_case)]
-            #[cfg(not(target_arch = "x86"))]
-            unsafe extern "system" fn [<__ $struct_name __ $interface_name __ #method_name_str>](
-                this: *mut ::std::ffi::c_void
-                #(, #params_with_types)*
-            ) #qualified_output {
-                unsafe {
-                    let offset = ::std::mem::offset_of!($struct_type, $vtable_field);
-                    let adjusted = (this as *mut u8).sub(offset) as *mut $struct_type;
-                    (*adjusted).#method_name(#(#call_args),*)
+                #[inline]
+                fn deref(&self) -> &Self::Target {
+                    unsafe { &*(self as *const Self as *const Self::Target) }
                 }
             }
-        });
 
-        // Generate vtable entry for base_vtable macro
-        vtable_entries.push(quote! {
-            #method_name: [<__ $struct_name __ $interface_name __ #method_name_str>]
-        });
-    }
+            impl #impl_generics ::std::ops::DerefMut for #trait_name #type_generics #where_clause {
+                #[inline]
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    unsafe { &mut *(self as *mut Self as *mut Self::Target) }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
 
-    // Generate the forwarders macro and base_vtable macro
-    // Skip if no_forwarders is set (e.g., for IUnknown where manual forwarders are needed)
-    let (forwarders_macro, base_vtable_macro) = if config.no_forwarders {
-        (quote! {}, quote! {})
-    } else if let Some(ref base_ident) = config.base_interface {
-        let base_lower = base_ident.to_string().to_lowercase();
-        let base_forwarders_macro = format_ident!("{}_forwarders", base_lower);
-        let parent_base_vtable_macro = format_ident!("{}_base_vtable", base_lower);
-        (
+    // The view `CppOwned` derefs to: the interface's methods without `unsafe`, since the
+    // handle owns a live object. Methods taking raw pointers keep it for their arguments.
+    // Inherited methods come from the base's view, the root view derefs to the wrapper.
+    let owned_view = format_ident!("{}Owned", trait_name);
+    let owned_methods = methods.iter().filter(|m| !m.destructor).map(|m| {
+        let method_name = &m.name;
+        let param_names = &m.param_names;
+        let param_types = &m.param_types;
+        let output = &m.output;
+        if param_types.iter().any(|ty| matches!(ty, Type::Ptr(_))) {
             quote! {
-                /// Auto-generated forwarders macro for #trait_name.
-                ///
-                /// This macro generates wrapper functions that adjust the `this` pointer
-                /// and forward calls to the implementing struct's methods.
-                /// Also invokes the base interface's forwarders macro.
+                /// Calls the method of the owned object.
                 ///
-                /// # Parameters
-                /// - `$struct_name`: The implementing struct name (e.g., `Calculator`)
-                /// - `$struct_type`: The implementing struct type (e.g., `Calculator` or `Calculator<T>`)
-                /// - `$interface_name`: The interface being implemented (e.g., `ICalculator`)
-                /// - `$vtable_field`: The vtable pointer field name (e.g., `vtable_i_calculator`)
-                /// - `$iid_const`: The IID constant for the interface (unused but kept for consistency)
-                #[macro_export]
-                macro_rules! #forwarders_macro_name {
-                    ($struct_name:ident, $struct_type:ty, $interface_name:ident, $vtable_field:ident, $iid_const:ident) => {
-                        // First invoke base interface's forwarders
-                        $crate::#base_forwarders_macro!($struct_name, $struct_type, $interface_name, $vtable_field, $iid_const);
-
-                        // Then generate our own forwarders
-                        $crate::paste! {
-                            #(#forwarder_wrappers)*
-                        }
-                    };
+                /// # Safety
+                /// Pointer arguments must be valid for the C++ method.
+                #[inline]
+                pub unsafe fn #method_name(&self #(, #param_names: #param_types)*) #output {
+                    unsafe { self.interface.#method_name(#(#param_names),*) }
                 }
-            },
+            }
+        } else {
             quote! {
-                /// Auto-generated base vtable initializer macro for #trait_name.
-                ///
-                /// Returns an expression that creates `#vtable_name { base: ..., ... }` with the wrapper function pointers.
-                /// Recursively invokes the parent interface's base_vtable macro for the base field.
-                #[macro_export]
-                macro_rules! #base_vtable_macro_name {
-                    ($struct_name:ident, $interface_name:ident) => {
-                        $crate::paste! {
-                            #vtable_name {
-                                base: $crate::#parent_base_vtable_macro!($struct_name, $interface_name),
-                                #(#vtable_entries),*
+                /// Calls the method of the owned object.
+                #[inline]
+                pub fn #method_name(&self #(, #param_names: #param_types)*) #output {
+                    // SAFETY: the view only exists inside a `CppOwned` of a live object
+                    unsafe { self.interface.#method_name(#(#param_names),*) }
+                }
+            }
+        }
+    });
+    let owned_deref = if let Some(base_ident) = &config.base_interface {
+        let base_type = if has_type_params {
+            quote! { #base_ident #type_generics }
+        } else {
+            quote! { #base_ident }
+        };
+        let base_view = quote! { <#base_type as #krate::owned::OwnedView>::View };
+        quote! {
+            impl #impl_generics ::std::ops::Deref for #owned_view #type_generics #where_clause {
+                type Target = #base_view;
+
+                #[inline]
+                fn deref(&self) -> &Self::Target {
+                    let base: &#base_type = &self.interface;
+                    unsafe { &*(base as *const #base_type as *const Self::Target) }
+                }
+            }
+        }
+    } else {
+        quote! {
+            impl #impl_generics ::std::ops::Deref for #owned_view #type_generics #where_clause {
+                type Target = #trait_name #type_generics;
+
+                #[inline]
+                fn deref(&self) -> &Self::Target {
+                    &self.interface
+                }
+            }
+        }
+    };
+    let owned_view_items = quote! {
+        /// Methods of an object owned by a `CppOwned`, callable without `unsafe`
+        #[repr(transparent)]
+        #vis struct #owned_view #generics #where_clause {
+            interface: #trait_name #type_generics,
+        }
+
+        impl #impl_generics #owned_view #type_generics #where_clause {
+            #(#owned_methods)*
+        }
+
+        #owned_deref
+
+        unsafe impl #impl_generics #krate::owned::OwnedView for #trait_name #type_generics #where_clause {
+            type View = #owned_view #type_generics;
+        }
+    };
+
+    // C++ `delete` through this interface: its own destructor, or the one of the interface
+    // it derefs to, when that has one
+    let virtual_destructor = if let Some(method) = methods.iter().find(|m| m.destructor) {
+        let deleting_name = format_ident!("{}_deleting", method.name);
+        quote! {
+            unsafe impl #impl_generics #krate::VirtualDestructor for #trait_name #type_generics #where_clause {
+                #[inline]
+                unsafe fn delete(this: *mut Self) {
+                    unsafe { (*this).#deleting_name() }
+                }
+            }
+        }
+    } else if config.base_interface.is_some() {
+        let mut base_generics = generics.clone();
+        base_generics
+            .params
+            .push(syn::parse_quote!(__Base: #krate::VirtualDestructor));
+        let (base_impl_generics, _, _) = base_generics.split_for_impl();
+        let predicates = where_clause.map(|w| &w.predicates);
+        quote! {
+            unsafe impl #base_impl_generics #krate::VirtualDestructor for #trait_name #type_generics
+            where
+                Self: ::std::ops::DerefMut<Target = __Base>,
+                #predicates
+            {
+                #[inline]
+                unsafe fn delete(this: *mut Self) {
+                    unsafe {
+                        let base: *mut __Base = &mut **this;
+                        <__Base as #krate::VirtualDestructor>::delete(base)
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // COM interfaces get `ComInterface`, so `ComPtr` can hold them: the IIDs along the
+    // extends chain plus raw IUnknown calls (through the wrappers above, or the base's).
+    let com_interface_impl = if let (InterfaceId::Guid { .. }, Some(base_ident)) =
+        (&config.iid, &config.base_interface)
+    {
+        let base_type = if has_type_params {
+            quote! { #base_ident #type_generics }
+        } else {
+            quote! { #base_ident }
+        };
+        quote! {
+            impl #impl_generics #krate::ComInterface for #trait_name #type_generics #where_clause {
+                const IID: #krate::GUID = #iid_static_name;
+
+                #[inline]
+                fn has_iid(iid: &#krate::GUID) -> bool {
+                    *iid == #iid_static_name || <#base_type as #krate::ComInterface>::has_iid(iid)
+                }
+
+                #[inline]
+                unsafe fn raw_query_interface(
+                    this: *mut Self,
+                    riid: *const #krate::GUID,
+                    ppv: *mut *mut std::ffi::c_void,
+                ) -> #krate::HRESULT {
+                    unsafe { (*this).query_interface(riid, ppv) }
+                }
+
+                #[inline]
+                unsafe fn raw_add_ref(this: *mut Self) -> u32 {
+                    unsafe { (*this).add_ref() }
+                }
+
+                #[inline]
+                unsafe fn raw_release(this: *mut Self) -> u32 {
+                    // The base's vtable comes first in ours; no reference to an object
+                    // the call may free
+                    unsafe { <#base_type as #krate::ComInterface>::raw_release(this as *mut #base_type) }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // The vtable macro, used by implementations and by interfaces extending this one
+    let vtable_macro_name = vtable_macro_name(trait_name);
+
+    // Generate PhantomData field for generic interfaces to avoid unused type parameter errors
+    let phantom_field = if has_type_params {
+        quote! { _phantom: std::marker::PhantomData #type_generics, }
+    } else {
+        quote! {}
+    };
+
+    // One layout per ABI when a method's vtable entry depends on it: MSVC uses a single
+    // scalar deleting destructor slot, returns aggregates through a hidden pointer and
+    // reverses overloads; Itanium has a complete (D1) and deleting (D0) destructor pair,
+    // returns aggregates like C and keeps declaration order. Everything else is
+    // identical, so other interfaces get a single ABI-neutral layout.
+    if config.virtual_base && has_type_params {
+        return Err(syn::Error::new(
+            generics.span(),
+            "extends(virtual ...) isn't supported on generic interfaces",
+        ));
+    }
+    if config.virtual_base && methods.is_empty() {
+        return Err(syn::Error::new(
+            trait_name.span(),
+            "an interface with a virtual base must declare at least one method (MSVC gives it no vtable pointer otherwise)",
+        ));
+    }
+
+    // A virtual base is embedded on Itanium only, so those interfaces need both layouts too.
+    let abi_dependent = has_overloads(&slot_requests)
+        || config.virtual_base
+        || methods.iter().any(|m| m.destructor || m.hidden_return);
+    let layout_variants = if abi_dependent {
+        config.abi.variants()
+    } else {
+        vec![(config.abi, quote! {})]
+    };
+
+    // Stubs in `UNIMPLEMENTED` are generic like the interface (fn generics can't have defaults)
+    let mut stub_generics = generics.clone();
+    for param in stub_generics.type_params_mut() {
+        param.eq_token = None;
+        param.default = None;
+    }
+    let (stub_generics, _, _) = stub_generics.split_for_impl();
+    let turbofish = type_generics.as_turbofish();
+
+    // The base part of `UNIMPLEMENTED` is the base interface's own
+    let base_stub = config.base_interface.as_ref().map(|base_ident| {
+        if has_type_params {
+            let base_vtable_name = format_ident!("{}VTable", base_ident);
+            quote! { base: <#krate::#base_vtable_name #type_generics>::UNIMPLEMENTED, }
+        } else {
+            quote! { base: <<#base_ident as #krate::VTableLayout>::VTable>::UNIMPLEMENTED, }
+        }
+    });
+
+    let mut layouts = Vec::new();
+    for (abi, cfg) in layout_variants {
+        let slots = layout_slots(&slot_requests, first_slot, abi)?;
+
+        // The base embedded in this layout's vtable: MSVC keeps a virtual base out of it
+        let layout_base = config
+            .base_interface
+            .as_ref()
+            .filter(|_| !(config.virtual_base && abi == CppAbi::Msvc));
+        let base_field = layout_base.and(base_vtable_field.as_ref());
+        let base_stub = layout_base.and(base_stub.as_ref());
+
+        // Generate vtable fields, filling gaps with dummy entries. Each field also gets a
+        // panicking stub in the `UNIMPLEMENTED` vtable, which contains its panic like any
+        // entry point and then evaluates `fallback` (it may use `this` and the parameters).
+        let mut vtable_fields = Vec::new();
+        let mut stub_entries = Vec::new();
+        let mut push_field = |name: &Ident,
+                              params: &[(TokenStream2, TokenStream2)],
+                              output: TokenStream2,
+                              message: &str,
+                              fallback: TokenStream2| {
+            let param_names: Vec<_> = params.iter().map(|(name, _)| name).collect();
+            let param_types: Vec<_> = params.iter().map(|(_, ty)| ty).collect();
+            let fields = arch_conventions.iter().map(|(cfg_arch, cc)| {
+                quote! {
+                    #cfg_arch
+                    pub #name: unsafe extern #cc fn(this: #self_ptr_type #(, #param_names: #param_types)*) #output,
+                }
+            });
+            vtable_fields.push(quote! { #(#fields)* });
+            let location = format!("{}::{}", trait_name, name);
+            let body = contain_panic(&krate, &location, quote! { panic!(#message) }, fallback);
+            let stubs = arch_conventions.iter().map(|(cfg_arch, cc)| {
+                quote! {
+                    #cfg_arch
+                    #[allow(unused_variables, unreachable_code, clippy::diverging_sub_expression)]
+                    unsafe extern #cc fn unimplemented #stub_generics(
+                        this: #self_ptr_type #(, #param_names: #param_types)*
+                    ) #output #where_clause {
+                        #body
+                    }
+                }
+            });
+            stub_entries.push(quote! {
+                #name: {
+                    #(#stubs)*
+                    unimplemented #turbofish
+                }
+            });
+        };
+        let mut wrapper_methods = Vec::new();
+        // Wrapper functions and vtable entries for the forwarders macros
+        let mut forwarder_wrappers = Vec::new();
+        let mut vtable_entries = Vec::new();
+        let mut current_slot = 0usize;
+        // Vtable entries beyond one per slot (the Itanium D0 destructor)
+        let mut extra_entries = 0usize;
+
+        for &(index, slot) in &slots {
+            let method = &methods[index];
+
+            let slot_fn = format_ident!("__slot_{}", method.name);
+            wrapper_methods.push(quote! {
+                /// Slot of this method, checked against `#[slot(N)]` on implementations
+                #[doc(hidden)]
+                pub const fn #slot_fn() -> usize {
+                    #slot
+                }
+            });
+
+            // Fill gaps with dummy entries
+            while current_slot < slot {
+                let dummy_name = format_ident!("__reserved_slot_{}", current_slot);
+                let message = format!("Called reserved vtable slot {}", current_slot);
+                let location = format!("{}::{}", trait_name, dummy_name);
+                let no_output = syn::ReturnType::Default;
+                push_field(
+                    &dummy_name,
+                    &[],
+                    quote! {},
+                    &message,
+                    config.panic_policy.fallback(&no_output, &krate, &location),
+                );
+                let dummy_name_str = dummy_name.to_string();
+                let body = contain_panic(
+                    &macro_krate,
+                    &location,
+                    quote! { panic!(#message) },
+                    config
+                        .panic_policy
+                        .fallback(&no_output, &macro_krate, &location),
+                );
+                for (cfg_arch, cc) in &arch_conventions {
+                    forwarder_wrappers.push(quote! {
+                        #[allow(non_snake_case)]
+                        #cfg_arch
+                        unsafe extern #cc fn [<__ $struct_name __ $interface_name __ #dummy_name_str>](
+                            _this: *mut ::std::ffi::c_void
+                        ) {
+                            #body
+                        }
+                    });
+                }
+                vtable_entries.push(quote! {
+                    #dummy_name: [<__ $struct_name __ $interface_name __ #dummy_name_str>]
+                });
+                current_slot += 1;
+            }
+            current_slot += 1;
+
+            let method_name = &method.name;
+            let method_name_str = method_name.to_string();
+            let unimplemented_message =
+                format!("Called unimplemented vtable entry '{}'", method_name_str);
+
+            // What the entry points return when the method panics; implementations call
+            // `__on_panic_*` so the interface's policy applies to them too
+            let location = format!("{}::{}", trait_name, method_name_str);
+            let output_or_unit = if method.destructor {
+                syn::ReturnType::Default
+            } else {
+                method.output.clone()
+            };
+            let fallback = config
+                .panic_policy
+                .fallback(&output_or_unit, &krate, &location);
+            let macro_fallback =
+                config
+                    .panic_policy
+                    .fallback(&output_or_unit, &macro_krate, &location);
+            let on_panic_fn = format_ident!("__on_panic_{}", method_name);
+            wrapper_methods.push(quote! {
+                /// Value returned by an implementation of this method that panicked
+                #[doc(hidden)]
+                pub fn #on_panic_fn() #output_or_unit {
+                    #fallback
+                }
+            });
+
+            // This-adjustment for the forwarders
+            let this_adjust = quote! {
+                let offset = ::std::mem::offset_of!($struct_type, $vtable_field);
+                let adjusted = (this as *mut u8).sub(offset) as *mut $struct_type;
+            };
+
+            if method.destructor {
+                let deleting_name = format_ident!("{}_deleting", method_name);
+                let deleting_name_str = deleting_name.to_string();
+
+                if abi == CppAbi::Itanium {
+                    push_field(
+                        method_name,
+                        &[],
+                        quote! {},
+                        &unimplemented_message,
+                        fallback.clone(),
+                    );
+                    push_field(
+                        &deleting_name,
+                        &[],
+                        quote! {},
+                        &unimplemented_message,
+                        fallback.clone(),
+                    );
+                    let destroy = contain_panic(
+                        &macro_krate,
+                        &location,
+                        quote! { ::std::ptr::drop_in_place(adjusted) },
+                        macro_fallback.clone(),
+                    );
+                    let delete = contain_panic(
+                        &macro_krate,
+                        &location,
+                        quote! { ::std::mem::drop(::std::boxed::Box::from_raw(adjusted)) },
+                        macro_fallback,
+                    );
+                    extra_entries += 1;
+
+                    wrapper_methods.push(quote! {
+                        /// Destroy the object without freeing its memory (Itanium D1 destructor).
+                        ///
+                        /// # Safety
+                        /// The object must not be used afterwards.
+                        #[inline]
+                        pub unsafe fn #method_name(&mut self) {
+                            unsafe { ((*self.vtable).#method_name)(self as *mut Self as #self_ptr_type) }
+                        }
+
+                        /// Destroy and free the object, like C++ `delete` (Itanium D0 destructor).
+                        ///
+                        /// # Safety
+                        /// The object must be heap-allocated as its implementation expects and must
+                        /// not be used afterwards.
+                        #[inline]
+                        pub unsafe fn #deleting_name(&mut self) {
+                            unsafe { ((*self.vtable).#deleting_name)(self as *mut Self as #self_ptr_type) }
+                        }
+                    });
+
+                    for (cfg_arch, cc) in &arch_conventions {
+                        forwarder_wrappers.push(quote! {
+                            #[allow(non_snake_case)]
+                            #cfg_arch
+                            unsafe extern #cc fn [<__ $struct_name __ $interface_name __ #method_name_str>](
+                                this: *mut ::std::ffi::c_void
+                            ) {
+                                unsafe {
+                                    #this_adjust
+                                    #destroy
+                                }
+                            }
+
+                            #[allow(non_snake_case)]
+                            #cfg_arch
+                            unsafe extern #cc fn [<__ $struct_name __ $interface_name __ #deleting_name_str>](
+                                this: *mut ::std::ffi::c_void
+                            ) {
+                                unsafe {
+                                    #this_adjust
+                                    #delete
+                                }
                             }
+                        });
+                    }
+                    vtable_entries.push(quote! {
+                        #method_name: [<__ $struct_name __ $interface_name __ #method_name_str>]
+                    });
+                    vtable_entries.push(quote! {
+                        #deleting_name: [<__ $struct_name __ $interface_name __ #deleting_name_str>]
+                    });
+                } else {
+                    push_field(
+                        method_name,
+                        &[(quote! { flags }, quote! { u32 })],
+                        quote! { -> *mut std::ffi::c_void },
+                        &unimplemented_message,
+                        quote! {{
+                            #fallback;
+                            this as *mut ::std::ffi::c_void
+                        }},
+                    );
+                    let destroy = contain_panic(
+                        &macro_krate,
+                        &location,
+                        quote! {
+                            // Bit 0: free the memory after destroying the object
+                            if flags & 1 != 0 {
+                                ::std::mem::drop(::std::boxed::Box::from_raw(adjusted));
+                            } else {
+                                ::std::ptr::drop_in_place(adjusted);
+                            }
+                        },
+                        macro_fallback,
+                    );
+
+                    wrapper_methods.push(quote! {
+                        /// Destroy the object without freeing its memory
+                        /// (MSVC scalar deleting destructor with flags 0).
+                        ///
+                        /// # Safety
+                        /// The object must not be used afterwards.
+                        #[inline]
+                        pub unsafe fn #method_name(&mut self) {
+                            unsafe { ((*self.vtable).#method_name)(self as *mut Self as #self_ptr_type, 0); }
                         }
-                    };
+
+                        /// Destroy and free the object, like C++ `delete`
+                        /// (MSVC scalar deleting destructor with flags 1).
+                        ///
+                        /// # Safety
+                        /// The object must be heap-allocated as its implementation expects and must
+                        /// not be used afterwards.
+                        #[inline]
+                        pub unsafe fn #deleting_name(&mut self) {
+                            unsafe { ((*self.vtable).#method_name)(self as *mut Self as #self_ptr_type, 1); }
+                        }
+                    });
+
+                    for (cfg_arch, cc) in &arch_conventions {
+                        forwarder_wrappers.push(quote! {
+                            #[allow(non_snake_case)]
+                            #cfg_arch
+                            unsafe extern #cc fn [<__ $struct_name __ $interface_name __ #method_name_str>](
+                                this: *mut ::std::ffi::c_void,
+                                flags: u32
+                            ) -> *mut ::std::ffi::c_void {
+                                unsafe {
+                                    #this_adjust
+                                    #destroy;
+                                    adjusted as *mut ::std::ffi::c_void
+                                }
+                            }
+                        });
+                    }
+                    vtable_entries.push(quote! {
+                        #method_name: [<__ $struct_name __ $interface_name __ #method_name_str>]
+                    });
                 }
-            },
-        )
-    } else {
-        (
-            quote! {
-                /// Auto-generated forwarders macro for #trait_name.
-                ///
-                /// This macro generates wrapper functions that adjust the `this` pointer
-                /// and forward calls to the implementing struct's methods.
-                ///
-                /// # Parameters
-                /// - `$struct_name`: The implementing struct name (e.g., `Calculator`)
-                /// - `$struct_type`: The implementing struct type (e.g., `Calculator` or `Calculator<T>`)
-                /// - `$interface_name`: The interface being implemented (e.g., `ICalculator`)
-                /// - `$vtable_field`: The vtable pointer field name (e.g., `vtable_i_calculator`)
-                /// - `$iid_const`: The IID constant for the interface (unused but kept for consistency)
-                #[macro_export]
-                macro_rules! #forwarders_macro_name {
-                    ($struct_name:ident, $struct_type:ty, $interface_name:ident, $vtable_field:ident, $iid_const:ident) => {
-                        $crate::paste! {
-                            #(#forwarder_wrappers)*
+                continue;
+            }
+
+            let param_names = &method.param_names;
+            let param_types = &method.param_types;
+            let output = &method.output;
+
+            // Qualify types for use in declarative macro context
+            // This transforms GUID -> cppvtable::GUID, HRESULT -> cppvtable::HRESULT, etc.
+            let qualified_param_types: Vec<_> = param_types
+                .iter()
+                .map(|ty| qualify_type_for_macro(ty, &macro_krate))
+                .collect();
+
+            // Build parameter list for function signature with qualified types
+            let params_with_types: Vec<_> = param_names
+                .iter()
+                .zip(qualified_param_types.iter())
+                .map(|(name, ty)| quote! { #name: #ty })
+                .collect();
+
+            // Build the method call arguments (just parameter names)
+            let call_args: Vec<_> = param_names.iter().map(|name| quote! { #name }).collect();
+            // `#[raw_receiver]`: the implementation takes `this: *mut Self`, so a call that
+            // frees the object never holds a reference to it
+            let method_call = if method.raw_receiver {
+                quote! { <$struct_type>::#method_name(adjusted #(, #call_args)*) }
+            } else {
+                quote! { (*adjusted).#method_name(#(#call_args),*) }
+            };
+
+            // MSVC member functions return user-defined types through a hidden pointer
+            // passed right after `this`, and hand the same pointer back
+            if let syn::ReturnType::Type(_, ret_ty) = output
+                && method.hidden_return
+                && abi == CppAbi::Msvc
+            {
+                let qualified_ret_ty = qualify_type_for_macro(ret_ty, &macro_krate);
+
+                let mut params = vec![(quote! { __ret }, quote! { *mut #ret_ty })];
+                params.extend(
+                    param_names
+                        .iter()
+                        .zip(param_types)
+                        .map(|(name, ty)| (quote! { #name }, quote! { #ty })),
+                );
+                push_field(
+                    method_name,
+                    &params,
+                    quote! { -> *mut #ret_ty },
+                    &unimplemented_message,
+                    quote! {{
+                        __ret.write(#fallback);
+                        __ret
+                    }},
+                );
+                let call = contain_panic(&macro_krate, &location, method_call, macro_fallback);
+
+                wrapper_methods.push(quote! {
+                    #[inline]
+                    pub unsafe fn #method_name(&self #(, #param_names: #param_types)*) -> #ret_ty {
+                        let mut __ret = ::std::mem::MaybeUninit::<#ret_ty>::uninit();
+                        unsafe {
+                            ((*self.vtable).#method_name)(
+                                self as *const Self as #self_ptr_type,
+                                __ret.as_mut_ptr()
+                                #(, #param_names)*
+                            );
+                            __ret.assume_init()
                         }
-                    };
+                    }
+                });
+
+                for (cfg_arch, cc) in &arch_conventions {
+                    forwarder_wrappers.push(quote! {
+                        #[allow(non_snake_case)]
+                        #cfg_arch
+                        unsafe extern #cc fn [<__ $struct_name __ $interface_name __ #method_name_str>](
+                            this: *mut ::std::ffi::c_void,
+                            __ret: *mut #qualified_ret_ty
+                            #(, #params_with_types)*
+                        ) -> *mut #qualified_ret_ty {
+                            unsafe {
+                                #this_adjust
+                                __ret.write(#call);
+                                __ret
+                            }
+                        }
+                    });
+                }
+            } else {
+                // Generate vtable field (function pointer) using configured calling convention
+                // Uses self_ptr_type: *mut T for generic interfaces, *mut c_void for non-generic
+                let params: Vec<_> = param_names
+                    .iter()
+                    .zip(param_types)
+                    .map(|(name, ty)| (quote! { #name }, quote! { #ty }))
+                    .collect();
+                push_field(
+                    method_name,
+                    &params,
+                    quote! { #output },
+                    &unimplemented_message,
+                    fallback,
+                );
+                let call = contain_panic(&macro_krate, &location, method_call, macro_fallback);
+
+                // Generate wrapper method on the base struct
+                // Cast self to the appropriate pointer type (c_void or T)
+                wrapper_methods.push(quote! {
+                    #[inline]
+                    pub unsafe fn #method_name(&self #(, #param_names: #param_types)*) #output {
+                        ((*self.vtable).#method_name)(
+                            self as *const Self as #self_ptr_type
+                            #(, #param_names)*
+                        )
+                    }
+                });
+
+                // Qualify return type too
+                let qualified_output = match output {
+                    syn::ReturnType::Default => quote! {},
+                    syn::ReturnType::Type(arrow, ty) => {
+                        let qualified_ty = qualify_type_for_macro(ty, &macro_krate);
+                        quote! { #arrow #qualified_ty }
+                    }
+                };
+
+                // Generate wrapper functions for x86 and x64
+                // Uses paste! for identifier concatenation with $struct_name and $interface_name
+                for (cfg_arch, cc) in &arch_conventions {
+                    forwarder_wrappers.push(quote! {
+                        #[allow(non_snake_case)]
+                        #cfg_arch
+                        unsafe extern #cc fn [<__ $struct_name __ $interface_name __ #method_name_str>](
+                            this: *mut ::std::ffi::c_void
+                            #(, #params_with_types)*
+                        ) #qualified_output {
+                            unsafe {
+                                #this_adjust
+                                #call
+                            }
+                        }
+                    });
+                }
+            }
+
+            // Generate vtable entry for base_vtable macro
+            vtable_entries.push(quote! {
+                #method_name: [<__ $struct_name __ $interface_name __ #method_name_str>]
+            });
+        }
+
+        // Cast to a virtual base: the offset is only known at runtime
+        if let Some(base_ident) = &config.base_interface
+            && config.virtual_base
+        {
+            let read_offset = if abi == CppAbi::Msvc {
+                // The vbptr follows our vtable pointer, the base is vbtable entry 1
+                quote! {
+                    #krate::msvc::virtual_base_offset(
+                        self as *const Self as *const ::std::ffi::c_void,
+                        ::std::mem::size_of::<*const ::std::ffi::c_void>() as isize,
+                        1,
+                    )
+                }
+            } else {
+                quote! {
+                    #krate::itanium::virtual_base_offset(
+                        self as *const Self as *const ::std::ffi::c_void,
+                        <#base_ident as #krate::VTableLayout>::FUNCTION_COUNT,
+                        0,
+                    )
+                }
+            };
+            wrapper_methods.push(quote! {
+                /// Byte offset from this interface pointer to its virtual base, read from
+                /// the object (vbase offset on Itanium, vbtable on MSVC)
+                #[inline]
+                #[must_use]
+                pub fn virtual_base_offset(&self) -> isize {
+                    unsafe { #read_offset }
                 }
+            });
+        }
+
+        // Total slot count for VTableLayout
+        let total_slot_count = current_slot + extra_entries;
+
+        // Generate the slot count expression
+        // If we have a base, total = base slot count + own slot count
+        let own_slot_count = total_slot_count;
+        let slot_count_expr = if let Some(base_ident) = layout_base {
+            if has_type_params {
+                // Generic case: base interface is also generic
+                quote! { <#base_ident #type_generics as #krate::VTableLayout>::SLOT_COUNT + #own_slot_count }
+            } else {
+                quote! { <#base_ident as #krate::VTableLayout>::SLOT_COUNT + #own_slot_count }
+            }
+        } else {
+            quote! { #own_slot_count }
+        };
+        // Same for virtual functions, where the Itanium destructor pair is one function
+        let own_function_count = current_slot;
+        let function_count_expr = match layout_base {
+            Some(base_ident) if has_type_params => quote! {
+                <#base_ident #type_generics as #krate::VTableLayout>::FUNCTION_COUNT + #own_function_count
             },
+            Some(base_ident) => quote! {
+                <#base_ident as #krate::VTableLayout>::FUNCTION_COUNT + #own_function_count
+            },
+            None => quote! { #own_function_count },
+        };
+
+        // Generate vtable struct with optional base field and generic parameters
+        let vtable_struct = if let Some(base_field) = base_field {
             quote! {
-                /// Auto-generated base vtable initializer macro for #trait_name.
-                ///
-                /// Returns an expression that creates `#vtable_name { ... }` with the wrapper function pointers.
-                #[macro_export]
-                macro_rules! #base_vtable_macro_name {
-                    ($struct_name:ident, $interface_name:ident) => {
-                        $crate::paste! {
-                            #vtable_name {
-                                #(#vtable_entries),*
+                /// VTable struct for #trait_name
+                #cfg
+                #[repr(C)]
+                #vis struct #vtable_name #generics #where_clause {
+                    #base_field,
+                    #(#vtable_fields)*
+                }
+            }
+        } else {
+            quote! {
+                /// VTable struct for #trait_name
+                #cfg
+                #[repr(C)]
+                #vis struct #vtable_name #generics #where_clause {
+                    #(#vtable_fields)*
+                }
+            }
+        };
+
+        // Generate the vtable macro, whose `forwarders` arm generates the wrapper functions
+        // and `base_vtable` arm the vtable value. Skip if no_forwarders is set (e.g., for
+        // IUnknown where manual forwarders are needed)
+        let vtable_macro = if config.no_forwarders {
+            quote! {}
+        } else {
+            // A derived interface's macro invokes its base's first, down the whole chain
+            let (base_forwarders, base_entry) = match layout_base {
+                Some(base_ident) => {
+                    let base_macro_path = base_macro_path(base_ident, &macro_krate);
+                    (
+                        quote! {
+                            #base_macro_path #base_ident!(forwarders [$($krate)*] $struct_name, $struct_type, $interface_name, $vtable_field, $iid_const);
+                        },
+                        quote! {
+                            base: #base_macro_path #base_ident!(base_vtable [$($krate)*] $struct_name, $interface_name),
+                        },
+                    )
+                }
+                None => (quote! {}, quote! {}),
+            };
+            // Methods returning through the hidden pointer on MSVC, so implementations
+            // needn't repeat `#[scalar_return]`
+            let hidden_return_arms: Vec<_> = methods
+                .iter()
+                .filter(|m| m.hidden_return)
+                .map(|m| {
+                    let name = &m.name;
+                    quote! {
+                        (hidden_return #name { $($hidden:tt)* } { $($direct:tt)* }) => { $($hidden)* };
+                    }
+                })
+                .collect();
+            // Implementations without their own convention options take the interface's
+            let conventions: Vec<_> = arch_conventions
+                .iter()
+                .map(|(cfg_arch, cc)| {
+                    quote! {
+                        #[allow(non_snake_case)]
+                        #cfg_arch
+                        unsafe extern #cc fn $($function)*
+                    }
+                })
+                .collect();
+            // Implementations without `abi = "..."` generate both layouts and keep the
+            // interface's; a layout shared by both ABIs still gets a macro per target
+            let macro_variants = if cfg.is_empty() && abi == CppAbi::Auto {
+                CppAbi::Auto.variants()
+            } else {
+                vec![(abi, cfg.clone())]
+            };
+            let macros = macro_variants.into_iter().map(|(macro_abi, macro_cfg)| {
+                let (msvc, itanium) = if macro_abi == CppAbi::Msvc {
+                    (quote! { $($items)* }, quote! {})
+                } else {
+                    (quote! {}, quote! { $($items)* })
+                };
+                quote! {
+                    /// Auto-generated vtable macro for #trait_name, invoked as `#trait_name!(...)`.
+                    ///
+                    /// `forwarders` generates wrapper functions that adjust the `this` pointer and
+                    /// forward calls to the implementing struct's methods, after the base
+                    /// interface's. `base_vtable` returns an expression that creates
+                    /// `#vtable_name { base: ..., ... }` with the wrapper function pointers.
+                    ///
+                    /// For `#[cppvtable_impl]` wrappers: `hidden_return method { ... } { ... }`
+                    /// expands to its first group if the method returns through the MSVC hidden
+                    /// pointer, else to its second; `with_conventions { unsafe extern fn ... }`
+                    /// to one copy of the function per calling convention of the interface;
+                    /// `if_abi msvc { ... }` / `if_abi itanium { ... }` to its items if the
+                    /// interface has that layout.
+                    ///
+                    /// # Parameters
+                    /// - `[$krate]`: Path to the cppvtable crate at the call site
+                    /// - `$struct_name`: The implementing struct name (e.g., `Calculator`)
+                    /// - `$struct_type`: The implementing struct type (e.g., `Calculator` or `Calculator<T>`)
+                    /// - `$interface_name`: The interface being implemented (e.g., `ICalculator`)
+                    /// - `$vtable_field`: The vtable pointer field name (e.g., `vtable_i_calculator`)
+                    /// - `$iid_const`: The IID constant for the interface (unused but kept for consistency)
+                    #macro_cfg
+                    #[doc(hidden)]
+                    #[macro_export]
+                    macro_rules! #vtable_macro_name {
+                        (forwarders [$($krate:tt)*] $struct_name:ident, $struct_type:ty, $interface_name:ident, $vtable_field:ident, $iid_const:ident) => {
+                            #base_forwarders
+
+                            #macro_krate::paste! {
+                                #(#forwarder_wrappers)*
                             }
-                        }
-                    };
+                        };
+                        (base_vtable [$($krate:tt)*] $struct_name:ident, $interface_name:ident) => {
+                            #macro_krate::paste! {
+                                #vtable_name {
+                                    #base_entry
+                                    #(#vtable_entries),*
+                                }
+                            }
+                        };
+                        #(#hidden_return_arms)*
+                        (hidden_return $method:ident { $($hidden:tt)* } { $($direct:tt)* }) => { $($direct)* };
+                        (with_conventions { unsafe extern fn $($function:tt)* }) => {
+                            #(#conventions)*
+                        };
+                        (if_abi msvc { $($items:tt)* }) => { #msvc };
+                        (if_abi itanium { $($items:tt)* }) => { #itanium };
+                    }
                 }
-            },
-        )
-    };
+            });
+            quote! { #(#macros)* }
+        };
 
-    // Generate PhantomData field for generic interfaces to avoid unused type parameter errors
-    let phantom_field = if has_type_params {
-        quote! { _phantom: std::marker::PhantomData #type_generics, }
-    } else {
-        quote! {}
-    };
+        let abi_value = abi.runtime_value(&krate);
+        let panic_policy_value = config.panic_policy.runtime_value(&krate);
+
+        layouts.push(quote! {
+            #vtable_struct
+
+            #cfg
+            impl #impl_generics #vtable_name #type_generics #where_clause {
+                /// Vtable whose entries all panic when called.
+                ///
+                /// `#[cppvtable_impl]` fills an implementation's methods in over this, so
+                /// reserved slots keep these stubs.
+                pub const UNIMPLEMENTED: Self = Self {
+                    #base_stub
+                    #(#stub_entries),*
+                };
+            }
+
+            #cfg
+            impl #impl_generics #trait_name #type_generics #where_clause {
+                #(#wrapper_methods)*
+            }
+
+            #cfg
+            impl #impl_generics #krate::VTableLayout for #trait_name #type_generics #where_clause {
+                const SLOT_COUNT: usize = #slot_count_expr;
+                const FUNCTION_COUNT: usize = #function_count_expr;
+                const ABI: #krate::CppAbi = #abi_value;
+                const PANIC_POLICY: #krate::PanicPolicy = #panic_policy_value;
+                type VTable = #vtable_name #type_generics;
+            }
+
+            #vtable_macro
+        });
+    }
+
+    // `IFoo!(...)` in the macro namespace, wherever the interface can be named
+    let vtable_macro_export = (!config.no_forwarders).then(|| {
+        quote! {
+            #[doc(hidden)]
+            #vis use #vtable_macro_name as #trait_name;
+        }
+    });
 
     let expanded = quote! {
         #iid_definition
+        #rtti_interface_impl
 
-        #vtable_struct
+        #(#layouts)*
+        #vtable_macro_export
 
         /// Base struct representing the interface pointer
         #[repr(C)]
-        #vis struct #trait_name #impl_generics #where_clause {
+        #vis struct #trait_name #generics #where_clause {
             vtable: *const #vtable_name #type_generics,
             #phantom_field
         }
@@ -1159,16 +2708,14 @@ fn cppvtable_internal(config: VTableConfig, input: ItemTrait) -> Result<TokenStr
 
             #iunknown_wrappers
 
-            #(#wrapper_methods)*
-        }
-
-        impl #impl_generics #krate::VTableLayout for #trait_name #type_generics #where_clause {
-            const SLOT_COUNT: usize = #slot_count_expr;
-            type VTable = #vtable_name #type_generics;
+            #declared_methods
+            #(#signature_checks)*
         }
 
-        #forwarders_macro
-        #base_vtable_macro
+        #base_deref
+        #owned_view_items
+        #virtual_destructor
+        #com_interface_impl
     };
 
     Ok(expanded)
@@ -1181,11 +2728,50 @@ fn cppvtable_internal(config: VTableConfig, input: ItemTrait) -> Result<TokenStr
 /// - A base struct `{Name}` with just the vtable pointer
 ///
 /// Supports `#[slot(N)]` attribute to specify explicit vtable slot indices.
-/// Gaps are filled with dummy entries that panic if called.
+/// Gaps are filled with dummy entries that panic if called; `{Name}VTable::UNIMPLEMENTED`
+/// is a vtable made only of such stubs. The generated `__METHODS`/`__check_*`/`__slot_*`
+/// items let `#[cppvtable_impl]` verify implementations against the interface.
+///
+/// `#[destructor] fn name(&mut self);` declares the virtual destructor. The wrapper gets
+/// `name()` (destroy only) and `name_deleting()` (destroy and free, like `delete`).
+/// The interface, and every interface extending it, implements
+/// `cppvtable::VirtualDestructor`, so `CppOwned` can own objects through it. Every
+/// interface also gets a `{Name}Owned` view (`cppvtable::OwnedView`), which `CppOwned`
+/// derefs to, with its methods callable without `unsafe`.
+/// On Itanium the destructor takes two vtable entries, so slot numbers after it refer
+/// to declaration order rather than the raw vtable index.
+///
+/// Under the MSVC ABI, methods returning a user-defined type use a hidden return pointer
+/// after `this`. Mark a method `#[scalar_return]` when its return type is an alias of a
+/// scalar (e.g. `type Handle = u64`).
+///
+/// Overloads of one C++ name are declared in C++ order with `#[overload_of = "name"]`;
+/// their slots follow the ABI (reversed and grouped on MSVC).
+///
+/// A method that may free the object, like COM's `Release`, is marked `#[raw_receiver]`:
+/// implementations reached through the interface's forwarders (`extends(...)`,
+/// `#[com_implement]`) define it as `unsafe fn name(this: *mut Self, ...)`, so no
+/// reference to the object is alive when it goes away.
 ///
 /// # Options
-/// - `stdcall` - Use stdcall calling convention on x86 (default: thiscall)
-/// - `extends(IUnknown)` - Inherit IUnknown methods at slots 0-2
+/// - `stdcall` / `thiscall` / `cdecl` - Force the x86 calling convention (default: thiscall
+///   for the MSVC ABI and on Windows, cdecl on other targets, matching the C++ compiler)
+/// - `win64` / `sysv64` - Use the Microsoft x64 or System V convention on x86_64, whatever
+///   the target OS (default: `extern "system"`). A base interface must use the same one.
+/// - `abi = "msvc"` / `abi = "itanium"` - Force a C++ ABI (default: per target)
+/// - `panic = "abort"` / `"default"` / `"hresult"` - What every entry point of the interface
+///   does when the Rust method panics: abort (default), return `Default::default()`, or
+///   return `E_UNEXPECTED` from `HRESULT` methods. See `cppvtable::panic`.
+/// - `extends(IUnknown)` - Inherit IUnknown methods at slots 0-2. The wrapper derefs
+///   (`Deref`/`DerefMut`) to the base wrapper, so base methods can be called on it.
+/// - `extends(virtual IBase)` - C++ `public virtual IBase`. The MSVC vtable leaves the base
+///   out, the Itanium vtable embeds it; both find the base through the object at runtime
+///   (`virtual_base_offset()`, used by `Deref`). Not available on generic interfaces.
+/// - `stable_id` - Make the RTTI interface ID a 128-bit hash of the interface's path and
+///   signature instead of the address of a static, so that every library built against
+///   the interface agrees on it. `id = "..."` hashes the given string instead, keeping
+///   the ID when the interface moves. There is no `interface_id_ptr()` then; the ID is
+///   `<I as RttiInterface>::INTERFACE_ID`.
 ///
 /// # Example
 /// ```ignore
@@ -1245,32 +2831,22 @@ fn parse_cppvtable_config(attr: TokenStream) -> Result<VTableConfig, syn::Error>
                         config.calling_convention = CallingConvention::Thiscall;
                         i += 1;
                     }
-                    "extends" => {
-                        // Expect: extends(BaseInterface)
+                    "cdecl" => {
+                        config.calling_convention = CallingConvention::Cdecl;
+                        i += 1;
+                    }
+                    "win64" => {
+                        config.x64_convention = X64CallingConvention::Win64;
                         i += 1;
-                        if i >= tokens.len() {
-                            return Err(syn::Error::new(
-                                ident.span(),
-                                "expected '(' after 'extends'",
-                            ));
-                        }
-                        if let proc_macro2::TokenTree::Group(group) = &tokens[i] {
-                            // Parse the base interface identifier
-                            let base_ident: syn::Ident =
-                                syn::parse2(group.stream()).map_err(|_| {
-                                    syn::Error::new(
-                                        group.span(),
-                                        "expected an identifier inside 'extends(...)'",
-                                    )
-                                })?;
-                            config.base_interface = Some(base_ident);
-                            i += 1;
-                        } else {
-                            return Err(syn::Error::new(
-                                ident.span(),
-                                "expected '(...)' after 'extends'",
-                            ));
-                        }
+                    }
+                    "sysv64" => {
+                        config.x64_convention = X64CallingConvention::Sysv64;
+                        i += 1;
+                    }
+                    "extends" => {
+                        let (base, is_virtual) = parse_extends_option(ident, &tokens, &mut i)?;
+                        config.base_interface = Some(base);
+                        config.virtual_base = is_virtual;
                     }
                     "slots" => {
                         // Expect: slots(method = N, ...)
@@ -1292,11 +2868,28 @@ fn parse_cppvtable_config(attr: TokenStream) -> Result<VTableConfig, syn::Error>
                             ));
                         }
                     }
+                    "abi" => {
+                        // Expect: abi = "msvc" | "itanium"
+                        config.abi = parse_abi_option(ident, &tokens, &mut i)?;
+                    }
+                    "panic" => {
+                        // Expect: panic = "abort" | "default" | "hresult"
+                        config.panic_policy = parse_panic_option(ident, &tokens, &mut i)?;
+                    }
                     "no_iid" => {
                         // Skip IID generation - user defines their own IID
                         config.iid = InterfaceId::None;
                         i += 1;
                     }
+                    "stable_id" => {
+                        config.iid = InterfaceId::Stable(None);
+                        i += 1;
+                    }
+                    "id" => {
+                        // Expect: id = "..."
+                        let id = parse_string_option(ident, &tokens, &mut i, "'= \"...\"'")?;
+                        config.iid = InterfaceId::Stable(Some(id));
+                    }
                     "internal" => {
                         // Use crate:: instead of cppvtable:: for paths
                         // This is used when defining interfaces inside the cppvtable crate itself
@@ -1313,7 +2906,7 @@ fn parse_cppvtable_config(attr: TokenStream) -> Result<VTableConfig, syn::Error>
                         return Err(syn::Error::new(
                             ident.span(),
                             format!(
-                                "unknown option '{}', expected 'stdcall', 'thiscall', 'extends(...)', 'slots(...)', 'no_iid', 'internal', or 'no_forwarders'",
+                                "unknown option '{}', expected 'stdcall', 'thiscall', 'cdecl', 'win64', 'sysv64', 'abi = \"...\"', 'panic = \"...\"', 'extends(...)', 'slots(...)', 'stable_id', 'id = \"...\"', 'no_iid', 'internal', or 'no_forwarders'",
                                 name
                             ),
                         ));
@@ -1335,6 +2928,116 @@ fn parse_cppvtable_config(attr: TokenStream) -> Result<VTableConfig, syn::Error>
     Ok(config)
 }
 
+/// Parse the value of an `abi = "..."` option.
+///
+/// `i` points at the `abi` identifier on entry and is left after the string literal.
+fn parse_abi_option(
+    ident: &Ident,
+    tokens: &[proc_macro2::TokenTree],
+    i: &mut usize,
+) -> Result<CppAbi, syn::Error> {
+    let lit = parse_string_option(ident, tokens, i, "'= \"msvc\"' or '= \"itanium\"'")?;
+    match lit.value().as_str() {
+        "msvc" => Ok(CppAbi::Msvc),
+        "itanium" => Ok(CppAbi::Itanium),
+        other => Err(syn::Error::new(
+            lit.span(),
+            format!("unknown abi '{}', expected 'msvc' or 'itanium'", other),
+        )),
+    }
+}
+
+/// Parse the value of a `panic = "..."` option, like [`parse_abi_option`].
+fn parse_panic_option(
+    ident: &Ident,
+    tokens: &[proc_macro2::TokenTree],
+    i: &mut usize,
+) -> Result<PanicPolicy, syn::Error> {
+    let lit = parse_string_option(
+        ident,
+        tokens,
+        i,
+        "'= \"abort\"', '= \"default\"' or '= \"hresult\"'",
+    )?;
+    match lit.value().as_str() {
+        "abort" => Ok(PanicPolicy::Abort),
+        "default" => Ok(PanicPolicy::Default),
+        "hresult" => Ok(PanicPolicy::HResult),
+        other => Err(syn::Error::new(
+            lit.span(),
+            format!(
+                "unknown panic policy '{}', expected 'abort', 'default' or 'hresult'",
+                other
+            ),
+        )),
+    }
+}
+
+/// Parse the `= "..."` after an option name, advancing `i` past the string literal.
+///
+/// `expected` describes the accepted values for the error message.
+fn parse_string_option(
+    ident: &Ident,
+    tokens: &[proc_macro2::TokenTree],
+    i: &mut usize,
+    expected: &str,
+) -> Result<syn::LitStr, syn::Error> {
+    *i += 1;
+    match tokens.get(*i) {
+        Some(proc_macro2::TokenTree::Punct(eq)) if eq.as_char() == '=' => *i += 1,
+        _ => {
+            return Err(syn::Error::new(
+                ident.span(),
+                format!("expected {} after '{}'", expected, ident),
+            ));
+        }
+    }
+    let message = format!("expected a string literal after '{} ='", ident);
+    let Some(value) = tokens.get(*i) else {
+        return Err(syn::Error::new(ident.span(), message));
+    };
+    *i += 1;
+    syn::parse2(value.clone().into()).map_err(|_| syn::Error::new(value.span(), message))
+}
+
+/// Parse `extends(BaseInterface)` starting at the `extends` identifier, advancing `i` past it.
+fn parse_extends_option(
+    ident: &Ident,
+    tokens: &[proc_macro2::TokenTree],
+    i: &mut usize,
+) -> Result<(Ident, bool), syn::Error> {
+    *i += 1;
+    match tokens.get(*i) {
+        Some(proc_macro2::TokenTree::Group(group)) => {
+            *i += 1;
+            // `virtual` is a reserved word, so it can't go through syn's Ident parser
+            let mut inner: Vec<_> = group.stream().into_iter().collect();
+            let is_virtual = matches!(
+                inner.first(),
+                Some(proc_macro2::TokenTree::Ident(word)) if word == "virtual"
+            );
+            if is_virtual {
+                inner.remove(0);
+            }
+            let base = syn::parse2(inner.into_iter().collect()).map_err(|_| {
+                syn::Error::new(
+                    group.span(),
+                    "expected an identifier inside 'extends(...)', optionally after 'virtual'",
+                )
+            })?;
+            Ok((base, is_virtual))
+        }
+        Some(_) => Err(syn::Error::new(
+            ident.span(),
+            "expected '(...)' after 'extends'",
+        )),
+        None => Err(syn::Error::new(
+            ident.span(),
+            "expected '(' after 'extends'",
+        )),
+    }
+}
+
 /// Parse slot overrides from a token stream: method = N, ...
 fn parse_slot_overrides_from_stream(
     stream: TokenStream2,
@@ -1389,18 +3092,149 @@ fn parse_slot_overrides_from_stream(
     result
 }
 
-/// Internal implementation of cppvtable_impl
-fn cppvtable_impl_impl(interface_name: Ident, input: ItemImpl) -> Result<TokenStream2, syn::Error> {
+/// Parse `#[cppvtable_impl(Interface, options...)]` into the interface name and an ImplConfig
+fn parse_cppvtable_impl_config(attr: TokenStream) -> Result<(Ident, ImplConfig), syn::Error> {
     // Use default config for regular C++ vtables
-    let config = ImplConfig {
-        calling_convention: CallingConvention::Thiscall,
+    let mut config = ImplConfig {
+        calling_convention: CallingConvention::Auto,
+        x64_convention: X64CallingConvention::System,
+        explicit_conventions: false,
+        abi: CppAbi::Auto,
         base_interface: None,
-        first_slot: 0,
+        virtual_base: false,
+        checked: false,
         generate_rtti: true,
+        rtti: false,
+        rtti_interfaces: Vec::new(),
         iid_const: None,
         internal: false,
+        interface_forwarders: false,
+        com_heap: false,
+        com_interfaces: Vec::new(),
+        com_aggregatable: false,
+        com_aggregates: Vec::new(),
+        com_tear_offs: Vec::new(),
+        com_weak: false,
+        no_cpp_class: false,
+        vtable_group: Vec::new(),
+        grouped: false,
+    };
+
+    let attr2: TokenStream2 = attr.into();
+    let tokens: Vec<_> = attr2.into_iter().collect();
+
+    let interface_name = match tokens.first() {
+        Some(proc_macro2::TokenTree::Ident(ident)) => ident.clone(),
+        _ => {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "expected the interface name, e.g. #[cppvtable_impl(IAnimal)]",
+            ));
+        }
     };
-    cppvtable_impl_internal(interface_name, input, config)
+
+    let mut i = 1;
+    while i < tokens.len() {
+        match &tokens[i] {
+            proc_macro2::TokenTree::Ident(ident) => match ident.to_string().as_str() {
+                "abi" => {
+                    config.abi = parse_abi_option(ident, &tokens, &mut i)?;
+                }
+                "extends" => {
+                    let (base, is_virtual) = parse_extends_option(ident, &tokens, &mut i)?;
+                    config.base_interface = Some(base);
+                    config.virtual_base = is_virtual;
+                    config.interface_forwarders = true;
+                }
+                "checked" => {
+                    config.checked = true;
+                    i += 1;
+                }
+                "no_cpp_class" => {
+                    config.no_cpp_class = true;
+                    i += 1;
+                }
+                "vtable_group" => {
+                    config.vtable_group = parse_interface_list_option(ident, tokens.get(i + 1))?;
+                    i += 2;
+                }
+                "grouped" => {
+                    config.grouped = true;
+                    i += 1;
+                }
+                "rtti" => {
+                    config.rtti = true;
+                    i += 1;
+                    // `rtti(IFoo, ...)`: the interfaces of the struct's TypeInfo
+                    if let Some(proc_macro2::TokenTree::Group(_)) = tokens.get(i) {
+                        config.rtti_interfaces = parse_interface_list_option(ident, tokens.get(i))?;
+                        i += 1;
+                    }
+                }
+                "thiscall" => {
+                    config.calling_convention = CallingConvention::Thiscall;
+                    config.explicit_conventions = true;
+                    i += 1;
+                }
+                "cdecl" => {
+                    config.calling_convention = CallingConvention::Cdecl;
+                    config.explicit_conventions = true;
+                    i += 1;
+                }
+                "stdcall" => {
+                    config.calling_convention = CallingConvention::Stdcall;
+                    config.explicit_conventions = true;
+                    i += 1;
+                }
+                "win64" => {
+                    config.x64_convention = X64CallingConvention::Win64;
+                    config.explicit_conventions = true;
+                    i += 1;
+                }
+                "sysv64" => {
+                    config.x64_convention = X64CallingConvention::Sysv64;
+                    config.explicit_conventions = true;
+                    i += 1;
+                }
+                name => {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        format!(
+                            "unknown option '{}', expected 'abi = \"...\"', 'extends(...)', 'checked', 'no_cpp_class', 'vtable_group(...)', 'grouped', 'rtti', 'rtti(...)', 'thiscall', 'cdecl', 'stdcall', 'win64' or 'sysv64'",
+                            name
+                        ),
+                    ));
+                }
+            },
+            proc_macro2::TokenTree::Punct(punct) if punct.as_char() == ',' => {
+                i += 1; // Skip commas
+            }
+            other => {
+                return Err(syn::Error::new(
+                    other.span(),
+                    "unexpected token in cppvtable_impl options",
+                ));
+            }
+        }
+    }
+
+    if config.grouped && !config.vtable_group.is_empty() {
+        return Err(syn::Error::new(
+            interface_name.span(),
+            "'grouped' can't be combined with 'vtable_group(...)': the primary vtable leads the group",
+        ));
+    }
+
+    // Inherited methods are dispatched by the interface's forwarders, which may live in
+    // other impl blocks and can't see the flag or `#[reentrant]`
+    if config.checked && config.base_interface.is_some() {
+        return Err(syn::Error::new(
+            interface_name.span(),
+            "'checked' can't be combined with 'extends(...)'",
+        ));
+    }
+
+    Ok((interface_name, config))
 }
 
 /// Core implementation shared by cppvtable_impl and com_implement
@@ -1424,213 +3258,417 @@ fn cppvtable_impl_internal(
     // Derive vtable field name from interface name
     let vtable_field = interface_to_field_name(&interface_name);
 
-    // x86 calling convention
-    let x86_cc = config.x86_calling_conv();
-
     // Collect methods with their slot indices
     struct ImplMethodInfo {
-        slot: usize,
+        explicit_slot: Option<usize>,
         name: Ident,
         param_names: Vec<Ident>,
         param_types: Vec<Type>,
         output: syn::ReturnType,
         is_mut: bool,
         original: syn::ImplItemFn,
+        destructor: bool,
+        reentrant: bool,
     }
 
     let mut methods: Vec<ImplMethodInfo> = Vec::new();
-    let mut next_slot = config.first_slot;
+    let mut seen_destructor = false;
 
     for item in &input.items {
-        if let ImplItem::Fn(method) = item {
-            let method_name = method.sig.ident.clone();
-            let output = method.sig.output.clone();
-
-            // Check for #[slot(N)] attribute
-            let slot = if let Some(explicit_slot) = parse_slot_attr(&method.attrs) {
-                if explicit_slot < next_slot {
+        let (method, destructor) = match item {
+            ImplItem::Fn(method) => {
+                if is_destructor(&method.attrs) {
                     return Err(syn::Error::new(
-                        method_name.span(),
+                        method.sig.ident.span(),
                         format!(
-                            "slot({}) for method '{}' would overlap with previous slots (next available: {})",
-                            explicit_slot, method_name, next_slot
+                            "method '{}': #[destructor] must be declared without a body, cleanup goes in Drop",
+                            method.sig.ident
                         ),
                     ));
                 }
-                explicit_slot
-            } else {
-                next_slot
-            };
-            next_slot = slot + 1;
-
-            // Collect parameters (skip self)
-            let params: Vec<_> = method
-                .sig
-                .inputs
-                .iter()
-                .filter_map(|arg| {
-                    if let FnArg::Typed(pat_type) = arg
-                        && let Pat::Ident(pat_ident) = pat_type.pat.as_ref()
-                    {
-                        let name = &pat_ident.ident;
-                        let ty = pat_type.ty.as_ref();
-                        return Some((name.clone(), ty.clone()));
+                (method.clone(), false)
+            }
+            // A bodiless `#[destructor] fn destructor(&mut self);` is not valid impl syntax,
+            // so it reaches us as verbatim tokens
+            ImplItem::Verbatim(tokens) => match syn::parse2::<syn::TraitItemFn>(tokens.clone()) {
+                Ok(decl) if decl.default.is_none() && is_destructor(&decl.attrs) => {
+                    validate_destructor(&decl.sig)?;
+                    if seen_destructor {
+                        return Err(syn::Error::new(
+                            decl.sig.ident.span(),
+                            format!(
+                                "method '{}': an interface can only have one #[destructor]",
+                                decl.sig.ident
+                            ),
+                        ));
                     }
-                    None
-                })
-                .collect();
+                    seen_destructor = true;
+                    let method = syn::ImplItemFn {
+                        attrs: decl.attrs,
+                        vis: syn::Visibility::Inherited,
+                        defaultness: None,
+                        sig: decl.sig,
+                        block: syn::parse_quote!({}),
+                    };
+                    (method, true)
+                }
+                _ => continue,
+            },
+            _ => continue,
+        };
 
-            // Check if method takes &self or &mut self
-            let is_mut = method
-                .sig
-                .inputs
-                .first()
-                .is_some_and(|arg| matches!(arg, FnArg::Receiver(r) if r.mutability.is_some()));
+        let method_name = method.sig.ident.clone();
+        let output = method.sig.output.clone();
 
-            methods.push(ImplMethodInfo {
-                slot,
-                name: method_name,
-                param_names: params.iter().map(|(n, _)| n.clone()).collect(),
-                param_types: params.iter().map(|(_, t)| t.clone()).collect(),
-                output,
-                is_mut,
-                original: method.clone(),
-            });
+        let explicit_slot = parse_slot_attr(&method.attrs);
+
+        // Collect parameters (skip self)
+        let params: Vec<_> = method
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|arg| {
+                if let FnArg::Typed(pat_type) = arg
+                    && let Pat::Ident(pat_ident) = pat_type.pat.as_ref()
+                {
+                    let name = &pat_ident.ident;
+                    let ty = pat_type.ty.as_ref();
+                    return Some((name.clone(), ty.clone()));
+                }
+                None
+            })
+            .collect();
+
+        // Check if method takes &self or &mut self
+        let is_mut = method
+            .sig
+            .inputs
+            .first()
+            .is_some_and(|arg| matches!(arg, FnArg::Receiver(r) if r.mutability.is_some()));
+
+        // `#[reentrant]`: dispatched without the borrow flag, so it must not take &mut self
+        let reentrant = method
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("reentrant"));
+        if reentrant && !config.checked {
+            return Err(syn::Error::new(
+                method_name.span(),
+                format!(
+                    "method '{}': #[reentrant] needs #[cppvtable_impl({}, checked)]",
+                    method_name, interface_name
+                ),
+            ));
+        }
+        if reentrant && is_mut {
+            return Err(syn::Error::new(
+                method_name.span(),
+                format!(
+                    "method '{}': #[reentrant] methods must take &self",
+                    method_name
+                ),
+            ));
         }
-    }
 
-    // Sort by slot index
-    methods.sort_by_key(|m| m.slot);
+        methods.push(ImplMethodInfo {
+            explicit_slot,
+            name: method_name,
+            param_names: params.iter().map(|(n, _)| n.clone()).collect(),
+            param_types: params.iter().map(|(_, t)| t.clone()).collect(),
+            output,
+            is_mut,
+            original: method,
+            destructor,
+            reentrant,
+        });
+    }
 
-    // Generate wrapper functions and vtable entries, filling gaps
-    let mut wrapper_fns = Vec::new();
-    let mut vtable_entries = Vec::new();
-    let mut original_methods = Vec::new();
-    let mut current_slot = config.first_slot;
+    // Keep original methods (strip our marker attributes)
+    let original_methods: Vec<_> = methods
+        .iter()
+        .filter(|m| !m.destructor)
+        .map(|m| {
+            let mut cleaned_method = m.original.clone();
+            cleaned_method.attrs.retain(|a| {
+                !a.path().is_ident("slot")
+                    && !a.path().is_ident("scalar_return")
+                    && !a.path().is_ident("overload_of")
+                    && !a.path().is_ident("reentrant")
+            });
+            cleaned_method
+        })
+        .collect();
 
-    // Generate base interface forwarders, vtable entry, and methods if configured
-    // Uses convention: base interface `IFoo` provides macros `ifoo_forwarders!`, `ifoo_base_vtable!`, `ifoo_methods!`
+    // Generate the interface's forwarders if configured, with the vtable macro the
+    // interface exports under its own name (`IFoo!(forwarders ...)`)
     let krate = crate_path(config.internal);
-    let (base_forwarders, base_vtable_entry, base_methods) = if let Some(base) =
-        &config.base_interface
-    {
-        let base_lower = base.to_string().to_lowercase();
-        let forwarders_macro = format_ident!("{}_forwarders", base_lower);
-        let base_vtable_macro = format_ident!("{}_base_vtable", base_lower);
-        let methods_macro = format_ident!("{}_methods", base_lower);
-
-        let iid_const =
-            config.iid_const.as_ref().cloned().unwrap_or_else(|| {
-                format_ident!("IID_{}", interface_name.to_string().to_uppercase())
-            });
+    let interface_macro_path = base_macro_path(&interface_name, &krate);
+    let base_forwarders = config.interface_forwarders.then(|| {
+        // The interface's forwarders macro invokes its base's, down the whole chain
+        let iid_const = format_ident!("IID_{}", interface_name.to_string().to_uppercase());
 
-        let forwarders = quote! {
-            #krate::#forwarders_macro!(#struct_name, #struct_type, #interface_name, #vtable_field, #iid_const);
-        };
+        quote! {
+            #interface_macro_path #interface_name!(forwarders [#krate] #struct_name, #struct_type, #interface_name, #vtable_field, #iid_const);
+        }
+    });
 
-        let vtable_entry = quote! {
-            base: #krate::#base_vtable_macro!(#struct_name, #interface_name)
-        };
+    // The object's IUnknown methods (`{iunknown}_methods!`), called by the IUnknown
+    // forwarders of every interface: QueryInterface knows each interface's vtable field
+    let com_methods = (!config.com_interfaces.is_empty()).then(|| {
+        let iunknown = config.x64_convention.iunknown().to_string().to_lowercase();
+        let methods_macro = format_ident!("{}_methods", iunknown);
+        let heap = config.com_heap.then(|| quote! { heap });
+        let weak = config.com_weak.then(|| quote! { weak });
+        let non_delegating = config.com_aggregatable.then(|| quote! { non_delegating });
+        let entries = config.com_interfaces.iter().map(|interface| {
+            let field = interface_to_field_name(interface);
+            quote! { #interface => #field }
+        });
+        let tear_offs = (!config.com_tear_offs.is_empty()).then(|| {
+            let entries = config.com_tear_offs.iter().map(|interface| {
+                let tear_off = tear_off_struct_name(&struct_name, interface);
+                quote! { #interface => #tear_off }
+            });
+            quote! { tear_off [#(#entries),*] }
+        });
+        let aggregates = config.com_aggregates.iter().map(|(field, interfaces)| {
+            quote! { , #field => [#(#interfaces),*] }
+        });
+        quote! {
+            #krate::#methods_macro!(
+                #heap #weak #non_delegating #struct_type,
+                [#(#entries),*] #tear_offs #(#aggregates)*
+            );
+        }
+    });
 
-        let methods = quote! {
-            #krate::#methods_macro!(#struct_type, #vtable_field, #iid_const);
-        };
+    // Calling conventions of the cfg-gated copies of each wrapper function
+    let arch_conventions = config.arch_conventions();
 
-        (Some(forwarders), Some(vtable_entry), Some(methods))
-    } else {
-        (None, None, None)
+    // This-adjustment: subtract the offset to get from interface pointer to struct start
+    // Uses offset_of! to calculate the offset at compile time
+    let this_adjust = quote! {
+        let offset = ::std::mem::offset_of!(#struct_type, #vtable_field);
+        let adjusted = (this as *mut u8).sub(offset) as *mut #struct_type;
     };
 
-    for method in &methods {
-        // Fill gaps with dummy panic stubs (only for slots after first_slot)
-        while current_slot < method.slot {
-            let dummy_name = format_ident!("__reserved_slot_{}", current_slot);
-            let dummy_wrapper =
-                format_ident!("__{}__{}__{}", struct_name, interface_name, dummy_name);
-
-            wrapper_fns.push(quote! {
-                #[allow(non_snake_case)]
-                #[cfg(target_arch = "x86")]
-                unsafe extern #x86_cc fn #dummy_wrapper(_this: *mut std::ffi::c_void) {
-                    panic!("Called reserved vtable slot {}", #current_slot);
-                }
-
-                #[allow(non_snake_case)]
-                #[cfg(not(target_arch = "x86"))]
-                unsafe extern "system" fn #dummy_wrapper(_this: *mut std::ffi::c_void) {
-                    panic!("Called reserved vtable slot {}", #current_slot);
+    // One copy of a wrapper function per calling convention: the impl's own when it repeats
+    // the convention options, else the interface's, which its vtable macro puts on
+    // `unsafe extern fn` (`IFoo!(with_conventions ...)`)
+    let explicit_conventions = config
+        .explicit_conventions
+        .then(|| config.arch_conventions());
+    let with_conventions = |function: TokenStream2| match &explicit_conventions {
+        Some(arch_conventions) => arch_conventions
+            .iter()
+            .map(|(cfg_arch, cc)| {
+                quote! {
+                    #[allow(non_snake_case)]
+                    #cfg_arch
+                    unsafe extern #cc fn #function
                 }
+            })
+            .collect(),
+        None => quote! {
+            #interface_macro_path #interface_name!(with_conventions {
+                unsafe extern fn #function
             });
+        },
+    };
 
-            vtable_entries.push(quote! {
-                #dummy_name: #dummy_wrapper
+    // Generate wrapper functions and vtable entries for one ABI. Reserved slots come
+    // from the interface's `UNIMPLEMENTED` vtable, so the impl needs no slot numbers.
+    let generate_wrappers = |abi: CppAbi| {
+        let mut wrapper_fns = Vec::new();
+        let mut vtable_entries = Vec::new();
+
+        for method in &methods {
+            let method_name = &method.name;
+            // Include interface name in wrapper to avoid conflicts with multiple inheritance
+            let wrapper_name =
+                format_ident!("__{}__{}__{}", struct_name, interface_name, method_name);
+            // A panic is contained and answered with the interface's `__on_panic_*` value
+            let location = format!("{}::{}", interface_name, method_name);
+            let on_panic_fn = format_ident!("__on_panic_{}", method_name);
+            let fallback = quote! { #interface_name::#on_panic_fn() };
+            // `checked`: the object must not be destroyed during another call
+            let check_unborrowed = config.checked.then(|| {
+                quote! { (*adjusted).borrow_flag.check_unborrowed(#location); }
             });
 
-            current_slot += 1;
-        }
-
-        let method_name = &method.name;
-        // Include interface name in wrapper to avoid conflicts with multiple inheritance
-        let wrapper_name = format_ident!("__{}__{}__{}", struct_name, interface_name, method_name);
-        let param_names = &method.param_names;
-        let param_types = &method.param_types;
-        let output = &method.output;
-
-        // This-adjustment: subtract the offset to get from interface pointer to struct start
-        // Uses offset_of! to calculate the offset at compile time
-        let this_adjust = quote! {
-            let offset = ::std::mem::offset_of!(#struct_type, #vtable_field);
-            let adjusted = (this as *mut u8).sub(offset) as *mut #struct_type;
-        };
-
-        let this_cast = if method.is_mut {
-            quote! { &mut *adjusted }
-        } else {
-            quote! { &*adjusted }
-        };
-
-        // Generate wrapper function
-        // x86: thiscall/stdcall depending on config, x64: C calling convention
-        wrapper_fns.push(quote! {
-            #[allow(non_snake_case)]
-            #[cfg(target_arch = "x86")]
-            unsafe extern #x86_cc fn #wrapper_name(
-                this: *mut std::ffi::c_void
-                #(, #param_names: #param_types)*
-            ) #output {
-                unsafe {
-                    #this_adjust
-                    let obj = #this_cast;
-                    obj.#method_name(#(#param_names),*)
+            // Destructor entries: MSVC has one scalar deleting destructor taking a flags
+            // argument (bit 0 = free the memory), Itanium a D1 (complete object) destructor
+            // followed by a D0 (deleting) destructor. Both run the struct's Drop, and free
+            // through Box, matching how Rust-side objects are expected to be allocated.
+            if method.destructor {
+                if abi == CppAbi::Itanium {
+                    let deleting_name = format_ident!("{}_deleting", method_name);
+                    let deleting_wrapper =
+                        format_ident!("__{}__{}__{}", struct_name, interface_name, deleting_name);
+                    let destroy = contain_panic(
+                        &krate,
+                        &location,
+                        quote! {
+                            #check_unborrowed
+                            ::std::ptr::drop_in_place(adjusted)
+                        },
+                        fallback.clone(),
+                    );
+                    let delete = contain_panic(
+                        &krate,
+                        &location,
+                        quote! {
+                            #check_unborrowed
+                            drop(::std::boxed::Box::from_raw(adjusted))
+                        },
+                        fallback.clone(),
+                    );
+                    wrapper_fns.push(with_conventions(quote! {
+                        #wrapper_name(this: *mut std::ffi::c_void) {
+                            unsafe {
+                                #this_adjust
+                                #destroy
+                            }
+                        }
+                    }));
+                    wrapper_fns.push(with_conventions(quote! {
+                        #deleting_wrapper(this: *mut std::ffi::c_void) {
+                            unsafe {
+                                #this_adjust
+                                #delete
+                            }
+                        }
+                    }));
+                    vtable_entries.push(quote! { #method_name: #wrapper_name });
+                    vtable_entries.push(quote! { #deleting_name: #deleting_wrapper });
+                } else {
+                    let destroy = contain_panic(
+                        &krate,
+                        &location,
+                        quote! {
+                            #check_unborrowed
+                            if flags & 1 != 0 {
+                                drop(::std::boxed::Box::from_raw(adjusted));
+                            } else {
+                                ::std::ptr::drop_in_place(adjusted);
+                            }
+                        },
+                        fallback.clone(),
+                    );
+                    wrapper_fns.push(with_conventions(quote! {
+                        #wrapper_name(
+                            this: *mut std::ffi::c_void,
+                            flags: u32,
+                        ) -> *mut std::ffi::c_void {
+                            unsafe {
+                                #this_adjust
+                                #destroy;
+                                adjusted as *mut std::ffi::c_void
+                            }
+                        }
+                    }));
+                    vtable_entries.push(quote! { #method_name: #wrapper_name });
                 }
+                continue;
             }
 
-            #[allow(non_snake_case)]
-            #[cfg(not(target_arch = "x86"))]
-            unsafe extern "system" fn #wrapper_name(
-                this: *mut std::ffi::c_void
-                #(, #param_names: #param_types)*
-            ) #output {
-                unsafe {
-                    #this_adjust
-                    let obj = #this_cast;
-                    obj.#method_name(#(#param_names),*)
+            let param_names = &method.param_names;
+            let param_types = &method.param_types;
+            let output = &method.output;
+
+            let this_cast = if method.is_mut {
+                quote! { &mut *adjusted }
+            } else {
+                quote! { &*adjusted }
+            };
+            let body = quote! {
+                let obj = #this_cast;
+                obj.#method_name(#(#param_names),*)
+            };
+            // `checked`: take the flag through the raw pointer before the reference to the
+            // object exists, and release it after the reference is dead
+            let body = if config.checked && !method.reentrant {
+                let exclusive = method.is_mut;
+                quote! {
+                    let borrow = #krate::BorrowFlag::enter(
+                        &raw const (*adjusted).borrow_flag,
+                        #exclusive,
+                        #location,
+                    );
+                    let result = { #body };
+                    drop(borrow);
+                    result
+                }
+            } else {
+                body
+            };
+            let call = contain_panic(&krate, &location, body, fallback);
+
+            // Generate wrapper function
+            let direct = with_conventions(quote! {
+                #wrapper_name(
+                    this: *mut std::ffi::c_void
+                    #(, #param_names: #param_types)*
+                ) #output {
+                    unsafe {
+                        #this_adjust
+                        #call
+                    }
                 }
+            });
+            // MSVC: aggregates are written through the hidden pointer after `this`, which
+            // is also returned. The interface knows which methods do (`#[scalar_return]`).
+            if let syn::ReturnType::Type(_, ret_ty) = output
+                && abi == CppAbi::Msvc
+            {
+                let hidden = with_conventions(quote! {
+                    #wrapper_name(
+                        this: *mut std::ffi::c_void,
+                        __ret: *mut #ret_ty
+                        #(, #param_names: #param_types)*
+                    ) -> *mut #ret_ty {
+                        unsafe {
+                            #this_adjust
+                            __ret.write(#call);
+                            __ret
+                        }
+                    }
+                });
+                wrapper_fns.push(quote! {
+                    #interface_macro_path #interface_name!(hidden_return #method_name {
+                        #hidden
+                    } {
+                        #direct
+                    });
+                });
+            } else {
+                wrapper_fns.push(direct);
             }
-        });
 
-        // Entry in vtable
-        vtable_entries.push(quote! {
-            #method_name: #wrapper_name
-        });
+            // Entry in vtable
+            vtable_entries.push(quote! {
+                #method_name: #wrapper_name
+            });
+        }
 
-        // Keep original method (strip #[slot] attribute)
-        let mut cleaned_method = method.original.clone();
-        cleaned_method.attrs.retain(|a| !a.path().is_ident("slot"));
-        original_methods.push(cleaned_method);
+        Ok::<_, syn::Error>((wrapper_fns, vtable_entries))
+    };
 
-        current_slot += 1;
-    }
+    // Wrappers only need one copy per ABI when some entry depends on it: a destructor, or
+    // a return value the interface may pass through the MSVC hidden pointer
+    let abi_dependent = methods
+        .iter()
+        .any(|m| m.destructor || matches!(m.output, syn::ReturnType::Type(..)));
+    let mut wrapper_fns = Vec::new();
+    let shared_entries = if config.interface_forwarders {
+        Some(Vec::new())
+    } else if abi_dependent {
+        None
+    } else {
+        let (fns, entries) = generate_wrappers(config.abi)?;
+        wrapper_fns = fns;
+        Some(entries)
+    };
 
     // Include interface name in vtable static name to support multiple interfaces
     let vtable_static_name = format_ident!(
@@ -1642,18 +3680,6 @@ fn cppvtable_impl_internal(
     // Generate const name matching field naming convention: vtable_i_foo -> VTABLE_I_FOO
     let vtable_const_name = format_ident!("{}", vtable_field.to_string().to_uppercase());
 
-    // Build vtable entries with optional base vtable entry (e.g., base: IUnknownVTable { ... })
-    let vtable_body = if let Some(base_entry) = &base_vtable_entry {
-        quote! {
-            #base_entry,
-            #(#vtable_entries),*
-        }
-    } else {
-        quote! {
-            #(#vtable_entries),*
-        }
-    };
-
     // Generate RTTI if configured
     let rtti_const = if config.generate_rtti {
         let interface_info_const_name = format_ident!(
@@ -1667,7 +3693,7 @@ fn cppvtable_impl_internal(
             /// RTTI: Interface info for this interface implementation.
             /// Contains interface ID and byte offset from struct start.
             pub const #interface_info_const_name: #krate::InterfaceInfo = #krate::InterfaceInfo {
-                interface_id: #interface_name::interface_id_ptr(),
+                interface_id: <#interface_name as #krate::rtti::RttiInterface>::INTERFACE_ID,
                 offset: ::std::mem::offset_of!(Self, #vtable_field) as isize,
             };
         }
@@ -1685,10 +3711,546 @@ fn cppvtable_impl_internal(
         quote! {}
     };
 
-    // Extra methods from base interface (e.g., query_interface/add_ref/release for IUnknown)
-    let extra_methods = base_methods.unwrap_or_default();
+    // Static vtable instance, one per ABI variant (Auto expands to both, cfg-gated).
+    // Itanium places offset-to-top and a typeinfo pointer before slot 0; the vtable
+    // pointer stored in the object points past them at the methods.
+    let mut vtable_statics = Vec::new();
+    let mut vtable_consts = Vec::new();
+
+    // A virtual base is implemented by its own cppvtable_impl on the same struct. The
+    // `vbptr_*` field right after our vtable pointer holds the vbtable MSVC reads, for
+    // Itanium the offset goes in front of the vtable.
+    let virtual_base = config
+        .base_interface
+        .as_ref()
+        .filter(|_| config.virtual_base)
+        .map(|base| (base, interface_to_field_name(base)));
+    if let Some((_, base_field)) = &virtual_base {
+        let suffix = vtable_field.to_string();
+        let suffix = suffix.trim_start_matches("vtable_");
+        let vbptr_field = format_ident!("vbptr_{}", suffix);
+        let vbtable_const_name = format_ident!("VBTABLE_{}", suffix.to_uppercase());
+        let vbtable_static_name = format_ident!(
+            "__{}_{}_VBTABLE",
+            struct_name.to_string().to_uppercase(),
+            interface_name.to_string().to_uppercase()
+        );
+        let message = format!(
+            "cppvtable_impl: `{}` must directly follow `{}`",
+            vbptr_field, vtable_field
+        );
+        vtable_statics.push(quote! {
+            const _: () = assert!(
+                ::std::mem::offset_of!(#struct_type, #vbptr_field)
+                    == ::std::mem::offset_of!(#struct_type, #vtable_field)
+                        + ::std::mem::size_of::<*const ::std::ffi::c_void>(),
+                #message,
+            );
+
+            // vbtable: offset from the vbptr back to our vtable pointer, then to the virtual base
+            static #vbtable_static_name: [i32; 2] = [
+                -(::std::mem::size_of::<*const ::std::ffi::c_void>() as i32),
+                (::std::mem::offset_of!(#struct_type, #base_field) as isize
+                    - ::std::mem::offset_of!(#struct_type, #vbptr_field) as isize) as i32,
+            ];
+        });
+        vtable_consts.push(quote! {
+            /// Pointer to the vbtable for this interface implementation.
+            /// Use this for the `vbptr_*` field when constructing the struct.
+            pub const #vbtable_const_name: *const i32 = #vbtable_static_name.as_ptr();
+        });
+    }
+
+    // `rtti`: the struct's TypeInfo goes in slot -1, the typeinfo slot of the Itanium prefix
+    let type_info = if config.rtti {
+        quote! {
+            <#struct_type as #krate::rtti::HasTypeInfo>::TYPE_INFO as *const #krate::TypeInfo
+                as *const ::std::ffi::c_void
+        }
+    } else {
+        quote! { ::std::ptr::null() }
+    };
+
+    // `rtti(IFoo, ...)`: the struct's TypeInfo, from the INTERFACE_INFO_* consts of the
+    // listed interfaces' impl blocks, each followed by the bases sharing its vtable
+    if !config.rtti_interfaces.is_empty() {
+        let struct_upper = struct_name.to_string().to_uppercase();
+        let type_info_static = format_ident!("__{}_TYPE_INFO", struct_upper);
+        let interfaces_static = format_ident!("__{}_TYPE_INFO_INTERFACES", struct_upper);
+        let chains: Vec<_> = config
+            .rtti_interfaces
+            .iter()
+            .map(|interface| quote! { <#interface as #krate::rtti::RttiInterface>::CHAIN })
+            .collect();
+        let vtables = config
+            .rtti_interfaces
+            .iter()
+            .zip(&chains)
+            .map(|(interface, chain)| {
+                let field = interface_to_field_name(interface).to_string();
+                let info = format_ident!(
+                    "INTERFACE_INFO_{}",
+                    field.trim_start_matches("vtable_").to_uppercase()
+                );
+                quote! { (<#struct_type>::#info.offset, #chain) }
+            });
+        vtable_statics.push(quote! {
+            static #interfaces_static: [#krate::InterfaceInfo; 0 #(+ #chains.depth())*] =
+                #krate::rtti::interface_table(&[#(#vtables),*]);
+
+            static #type_info_static: #krate::TypeInfo = #krate::TypeInfo::new(
+                #krate::rtti::type_id_from_path(concat!(module_path!(), "::", stringify!(#struct_name))),
+                stringify!(#struct_name),
+                &#interfaces_static,
+            );
+
+            impl #krate::rtti::HasTypeInfo for #struct_type {
+                const TYPE_INFO: &'static #krate::TypeInfo = &#type_info_static;
+            }
+        });
+    }
+
+    // Without `abi = "..."` both layouts are generated, and the interface's vtable macro
+    // keeps the one it was declared with (`IFoo!(if_abi msvc { ... })`)
+    let abis = match config.abi {
+        CppAbi::Auto => vec![CppAbi::Msvc, CppAbi::Itanium],
+        abi => vec![abi],
+    };
+    for abi in abis {
+        let abi_value = abi.runtime_value(&krate);
+        let mut abi_fns = Vec::new();
+        let mut abi_statics = Vec::new();
+        let mut abi_consts = Vec::new();
+
+        let vtable_entries = if let Some(entries) = &shared_entries {
+            entries.clone()
+        } else {
+            let (fns, entries) = generate_wrappers(abi)?;
+            abi_fns = fns;
+            entries
+        };
+
+        // Build the vtable value with optional base vtable entry (e.g., base: IUnknownVTable { ... })
+        let vtable_value = if config.interface_forwarders {
+            quote! { #interface_macro_path #interface_name!(base_vtable [#krate] #struct_name, #interface_name) }
+        } else {
+            quote! {
+                #vtable_name {
+                    #(#vtable_entries,)*
+                    ..#vtable_name::UNIMPLEMENTED
+                }
+            }
+        };
+
+        abi_statics.push(quote! {
+            const _: () = assert!(
+                <#interface_name as #krate::VTableLayout>::ABI.same_as(#abi_value),
+                concat!("cppvtable_impl: ABI does not match the one ", stringify!(#interface_name), " was declared with"),
+            );
+        });
+        match abi {
+            CppAbi::Itanium => {
+                let offset_to_top =
+                    quote! { -(::std::mem::offset_of!(#struct_type, #vtable_field) as isize) };
+                let (table_type, table_value) = if let Some((base, base_field)) = &virtual_base {
+                    // Our forwarders adjust `this` themselves, so the vcall offsets only need
+                    // to lead C++ virtual thunks to the struct
+                    let function_count = quote! { <#base as #krate::VTableLayout>::FUNCTION_COUNT };
+                    (
+                        quote! { #krate::itanium::ItaniumVirtualVTable<#vtable_name, 1, { #function_count }> },
+                        quote! {
+                            #krate::itanium::ItaniumVirtualVTable::new(
+                                [::std::mem::offset_of!(#struct_type, #base_field) as isize
+                                    - ::std::mem::offset_of!(#struct_type, #vtable_field) as isize],
+                                [#offset_to_top; #function_count],
+                                #offset_to_top,
+                                #type_info,
+                                #vtable_value,
+                            )
+                        },
+                    )
+                } else {
+                    (
+                        quote! { #krate::itanium::ItaniumVTable<#vtable_name> },
+                        quote! {
+                            #krate::itanium::ItaniumVTable::new(
+                                #offset_to_top,
+                                #type_info,
+                                #vtable_value,
+                            )
+                        },
+                    )
+                };
+
+                if config.grouped {
+                    // The primary impl block's `vtable_group(...)` emits it and the const
+                    abi_statics.push(quote! {
+                        impl #krate::itanium::GroupedVTable<#interface_name> for #struct_type {
+                            type VTable = #table_type;
+                            #[allow(clippy::needless_update)]
+                            const VTABLE: #table_type = #table_value;
+                        }
+                    });
+                } else if !config.vtable_group.is_empty() {
+                    // One static for the struct, like g++'s vtable group: the primary
+                    // vtable, then each secondary one with its prefix
+                    let group_type = format_ident!("__{}VTableGroup", struct_name);
+                    let group_static =
+                        format_ident!("__{}_VTABLE_GROUP", struct_name.to_string().to_uppercase());
+                    let secondaries = &config.vtable_group;
+                    let secondary_types = secondaries.iter().map(|interface| {
+                        quote! { <#struct_type as #krate::itanium::GroupedVTable<#interface>>::VTable }
+                    });
+                    let secondary_values = secondaries.iter().map(|interface| {
+                        quote! { <#struct_type as #krate::itanium::GroupedVTable<#interface>>::VTABLE }
+                    });
+                    let message = format!(
+                        "cppvtable_impl: vtable_group(...) belongs on the impl block of the vtable field at offset 0, not `{}`",
+                        vtable_field
+                    );
+                    abi_statics.push(quote! {
+                        const _: () = assert!(
+                            ::std::mem::offset_of!(#struct_type, #vtable_field) == 0,
+                            #message,
+                        );
+
+                        #[repr(C)]
+                        struct #group_type(#table_type, #(#secondary_types),*);
+
+                        #[allow(clippy::needless_update)]
+                        static #group_static: #group_type = #group_type(#table_value, #(#secondary_values),*);
+                    });
+                    abi_consts.push(quote! {
+                        /// Pointer to the vtable for this interface implementation.
+                        /// Use this when constructing the struct.
+                        pub const #vtable_const_name: *const #vtable_name = #group_static.0.vtable_ptr();
+                    });
+                    for (index, interface) in secondaries.iter().enumerate() {
+                        let member = syn::Index::from(index + 1);
+                        let secondary_vtable = format_ident!("{}VTable", interface);
+                        let secondary_const = format_ident!(
+                            "{}",
+                            interface_to_field_name(interface)
+                                .to_string()
+                                .to_uppercase()
+                        );
+                        abi_consts.push(quote! {
+                            /// Pointer to the vtable for this interface implementation, in the
+                            /// struct's vtable group. Use this when constructing the struct.
+                            pub const #secondary_const: *const #secondary_vtable = #group_static.#member.vtable_ptr();
+                        });
+                    }
+                } else {
+                    abi_statics.push(quote! {
+                        #[allow(clippy::needless_update)]
+                        static #vtable_static_name: #table_type = #table_value;
+                    });
+                    abi_consts.push(quote! {
+                        /// Pointer to the vtable for this interface implementation.
+                        /// Use this when constructing the struct.
+                        pub const #vtable_const_name: *const #vtable_name = #vtable_static_name.vtable_ptr();
+                    });
+                }
+            }
+            _ if config.rtti => {
+                abi_statics.push(quote! {
+                    #[allow(clippy::needless_update)]
+                    static #vtable_static_name: #krate::rtti::VTableWithRtti<#vtable_name> =
+                        #krate::rtti::VTableWithRtti::with_offset_to_top(
+                            -(::std::mem::offset_of!(#struct_type, #vtable_field) as isize),
+                            <#struct_type as #krate::rtti::HasTypeInfo>::TYPE_INFO,
+                            #vtable_value,
+                        );
+                });
+                abi_consts.push(quote! {
+                    /// Pointer to the vtable for this interface implementation.
+                    /// Use this when constructing the struct.
+                    pub const #vtable_const_name: *const #vtable_name = #vtable_static_name.vtable_ptr();
+                });
+            }
+            _ => {
+                abi_statics.push(quote! {
+                    #[allow(clippy::needless_update)]
+                    static #vtable_static_name: #vtable_name = #vtable_value;
+                });
+                abi_consts.push(quote! {
+                    /// Pointer to the vtable for this interface implementation.
+                    /// Use this when constructing the struct.
+                    pub const #vtable_const_name: *const #vtable_name = &#vtable_static_name;
+                });
+            }
+        }
+
+        if config.abi == CppAbi::Auto {
+            let abi_name = match abi {
+                CppAbi::Msvc => quote! { msvc },
+                _ => quote! { itanium },
+            };
+            wrapper_fns.push(quote! {
+                #interface_macro_path #interface_name!(if_abi #abi_name {
+                    #(#abi_fns)*
+                    #(#abi_statics)*
+                });
+            });
+            vtable_consts.push(quote! {
+                #interface_macro_path #interface_name!(if_abi #abi_name {
+                    #(#abi_consts)*
+                });
+            });
+        } else {
+            wrapper_fns.extend(abi_fns);
+            vtable_statics.extend(abi_statics);
+            vtable_consts.extend(abi_consts);
+        }
+    }
+
+    // Check each method against the interface's signature and, when repeated here, its slot.
+    // Inherited methods of an `extends` impl may live in other impl blocks, and their
+    // forwarders are type-checked against the interfaces that declare them.
+    // Every method of the interface must be defined here; reserved slots are the only
+    // entries left to `UNIMPLEMENTED`.
+    let mut interface_checks = Vec::new();
+    if !config.interface_forwarders {
+        let implemented = methods.iter().map(|m| m.name.to_string());
+        interface_checks.push(quote! {
+            const _: () = #krate::check_impl_methods(
+                stringify!(#struct_name),
+                stringify!(#interface_name),
+                #interface_name::__METHODS,
+                &[#(#implemented),*],
+            );
+        });
+        for method in &methods {
+            let method_name = &method.name;
+            let span = method_name.span();
+            let name = method_name.to_string();
+            let message = format!(
+                "cppvtable_impl: '{}' is not a method of {}",
+                method_name, interface_name
+            );
+            interface_checks.push(quote_spanned! {span=>
+                const _: () = assert!(
+                    #krate::declares_method(#interface_name::__METHODS, #name),
+                    #message,
+                );
+            });
+            if !method.destructor {
+                let check_fn = format_ident!("__check_{}", method_name);
+                interface_checks.push(quote_spanned! {span=>
+                    const _: () = #interface_name::#check_fn(<#struct_type>::#method_name);
+                });
+            }
+            if let Some(slot) = method.explicit_slot {
+                let slot_fn = format_ident!("__slot_{}", method_name);
+                let message = format!(
+                    "#[slot({})] on method '{}' doesn't match its slot in {}",
+                    slot, method_name, interface_name
+                );
+                interface_checks.push(quote_spanned! {span=>
+                    const _: () = assert!(#interface_name::#slot_fn() == #slot, #message);
+                });
+            }
+        }
+    }
+
+    // `extends(Base)` must name the interface's actual base: its vtable is the `base` field,
+    // or for a virtual base (not embedded on MSVC) what the interface derefs to
+    let base_check = match &config.base_interface {
+        Some(base) if config.virtual_base => quote! {
+            const _: fn(&#interface_name) -> &#base = |iface| iface;
+        },
+        Some(base) if config.interface_forwarders => quote! {
+            const _: fn(&#vtable_name) -> &<#base as #krate::VTableLayout>::VTable =
+                |vtable| &vtable.base;
+        },
+        _ => quote! {},
+    };
+
+    // Typed casts for CppBox, and freeing through the deleting destructor when there is one
+    let implements = quote! {
+        unsafe impl #krate::Implements<#interface_name> for #struct_type {
+            const OFFSET: usize = ::std::mem::offset_of!(#struct_type, #vtable_field);
+        }
+    };
+    let com_heap = config.com_heap.then(|| {
+        quote! {
+            unsafe impl #krate::ComHeap for #struct_type {}
+        }
+    });
+    // Aggregatable COM objects: a non-delegating IUnknown vtable of their own (the
+    // identity, which manages the object's lifetime), and IUnknown methods for every
+    // other interface that delegate to the outer object once aggregated
+    let (non_delegating_items, non_delegating_methods) = if config.com_aggregatable {
+        let iunknown = config.x64_convention.iunknown();
+        let iunknown_vtable = format_ident!("{}VTable", iunknown);
+        let query_interface = format_ident!("__{}__non_delegating__query_interface", struct_name);
+        let add_ref = format_ident!("__{}__non_delegating__add_ref", struct_name);
+        let release = format_ident!("__{}__non_delegating__release", struct_name);
+        let static_name = format_ident!(
+            "__{}_NON_DELEGATING_VTABLE",
+            struct_name.to_string().to_uppercase()
+        );
+        let adjust = quote! {
+            let offset = ::std::mem::offset_of!(#struct_type, vtable_non_delegating);
+            let adjusted = (this as *mut u8).sub(offset) as *mut #struct_type;
+        };
+        // Panics (e.g. in Drop on the last release) follow IUnknown's policy
+        let [call_query_interface, call_add_ref, call_release] =
+            ["query_interface", "add_ref", "release"].map(|method| {
+                let location = format!("{}::{}", iunknown, method);
+                let call = format_ident!("non_delegating_{}", method);
+                let on_panic_fn = format_ident!("__on_panic_{}", method);
+                let args = (method == "query_interface").then(|| quote! { riid, ppv });
+                // Release takes the raw pointer, the object may be freed during the call
+                let call = if method == "release" {
+                    quote! { <#struct_type>::#call(adjusted) }
+                } else {
+                    quote! { (*adjusted).#call(#args) }
+                };
+                contain_panic(
+                    &krate,
+                    &location,
+                    call,
+                    quote! { <#krate::#iunknown>::#on_panic_fn() },
+                )
+            });
+        let forwarders = arch_conventions.iter().map(|(cfg_arch, cc)| {
+            quote! {
+                #[allow(non_snake_case)]
+                #cfg_arch
+                unsafe extern #cc fn #query_interface(
+                    this: *mut ::std::ffi::c_void,
+                    riid: *const #krate::GUID,
+                    ppv: *mut *mut ::std::ffi::c_void,
+                ) -> #krate::HRESULT {
+                    unsafe {
+                        #adjust
+                        #call_query_interface
+                    }
+                }
+
+                #[allow(non_snake_case)]
+                #cfg_arch
+                unsafe extern #cc fn #add_ref(this: *mut ::std::ffi::c_void) -> u32 {
+                    unsafe {
+                        #adjust
+                        #call_add_ref
+                    }
+                }
+
+                #[allow(non_snake_case)]
+                #cfg_arch
+                unsafe extern #cc fn #release(this: *mut ::std::ffi::c_void) -> u32 {
+                    unsafe {
+                        #adjust
+                        #call_release
+                    }
+                }
+            }
+        });
+        let items = quote! {
+            unsafe impl #krate::Implements<#krate::#iunknown> for #struct_type {
+                const OFFSET: usize = ::std::mem::offset_of!(#struct_type, vtable_non_delegating);
+            }
+
+            #(#forwarders)*
+
+            #[allow(non_upper_case_globals)]
+            static #static_name: #krate::#iunknown_vtable = #krate::#iunknown_vtable {
+                query_interface: #query_interface,
+                add_ref: #add_ref,
+                release: #release,
+            };
+        };
+        let methods = quote! {
+            /// Non-delegating IUnknown vtable pointer, for the `vtable_non_delegating` field
+            pub const VTABLE_NON_DELEGATING: *const #krate::#iunknown_vtable = &#static_name;
+
+            /// Query for another interface by GUID, through the outer object when aggregated.
+            ///
+            /// # Safety
+            /// - `riid` must point to a valid GUID
+            /// - `ppv` must point to a valid, writable pointer location
+            pub unsafe fn query_interface(
+                &self,
+                riid: *const #krate::GUID,
+                ppv: *mut *mut ::std::ffi::c_void,
+            ) -> #krate::HRESULT {
+                match self.ref_count.outer() {
+                    Some(outer) => unsafe {
+                        <#krate::#iunknown as #krate::ComInterface>::raw_query_interface(
+                            outer.as_ptr() as *mut #krate::#iunknown,
+                            riid,
+                            ppv,
+                        )
+                    },
+                    None => unsafe { self.non_delegating_query_interface(riid, ppv) },
+                }
+            }
+
+            /// Increment the reference count of the outer object when aggregated, else
+            /// this object's.
+            pub fn add_ref(&self) -> u32 {
+                match self.ref_count.outer() {
+                    Some(outer) => unsafe {
+                        <#krate::#iunknown as #krate::ComInterface>::raw_add_ref(
+                            outer.as_ptr() as *mut #krate::#iunknown,
+                        )
+                    },
+                    None => self.non_delegating_add_ref(),
+                }
+            }
+
+            /// Decrement the reference count of the outer object when aggregated, else
+            /// this object's.
+            ///
+            /// # Safety
+            /// `this` must point to a live object, and the caller must own the reference
+            /// it gives up.
+            pub unsafe fn release(this: *mut Self) -> u32 {
+                match unsafe { (*this).ref_count.outer() } {
+                    Some(outer) => unsafe {
+                        <#krate::#iunknown as #krate::ComInterface>::raw_release(
+                            outer.as_ptr() as *mut #krate::#iunknown,
+                        )
+                    },
+                    None => unsafe { Self::non_delegating_release(this) },
+                }
+            }
+        };
+        (items, methods)
+    } else {
+        (quote! {}, quote! {})
+    };
+    // One impl block of the struct implements CppClass; the others say `no_cpp_class`
+    let cpp_class_destructor = methods
+        .iter()
+        .find(|m| m.destructor)
+        .filter(|_| !config.no_cpp_class);
+    let cpp_class = cpp_class_destructor.map(|method| {
+        let deleting_name = format_ident!("{}_deleting", method.name);
+        quote! {
+            impl #krate::CppClass for #struct_type {
+                unsafe fn delete(this: *mut Self) {
+                    unsafe {
+                        let iface = (this as *mut u8)
+                            .add(<Self as #krate::Implements<#interface_name>>::OFFSET)
+                            as *mut #interface_name;
+                        (*iface).#deleting_name();
+                    }
+                }
+            }
+        }
+    });
 
     let expanded = quote! {
+        #base_check
+        #(#interface_checks)*
+        #implements
+        #com_heap
+        #non_delegating_items
+        #cpp_class
+
         // Base interface forwarders (e.g., IUnknown wrapper functions)
         #base_forwarders
 
@@ -1696,22 +4258,19 @@ fn cppvtable_impl_internal(
         #(#wrapper_fns)*
 
         // Static vtable instance
-        static #vtable_static_name: #vtable_name = #vtable_name {
-            #vtable_body
-        };
+        #(#vtable_statics)*
 
         // Original impl with methods + vtable const accessor
         impl #struct_type {
-            /// Pointer to the vtable for this interface implementation.
-            /// Use this when constructing the struct.
-            pub const #vtable_const_name: *const #vtable_name = &#vtable_static_name;
+            #(#vtable_consts)*
 
             #iid_const
             #rtti_const
 
             #(#original_methods)*
 
-            #extra_methods
+            #com_methods
+            #non_delegating_methods
         }
     };
 
@@ -1725,24 +4284,81 @@ fn cppvtable_impl_internal(
 /// - Wrapper functions that cast `this` and call your methods
 /// - A `new()` helper or vtable accessor
 ///
-/// Supports `#[slot(N)]` attribute to specify explicit vtable slot indices.
-/// Must match the slot indices used in the corresponding `#[cppvtable]`.
+/// Slots come from the interface, so methods can be in any order and `#[slot(N)]` or
+/// `#[overload_of]` don't need repeating; a repeated `#[slot(N)]` must match. Each method's
+/// parameter and return types are checked against the interface at compile time. Every
+/// method the interface declares must be defined, and every method defined must be
+/// declared, or compilation fails naming them; only reserved slots keep the interface's
+/// panicking stubs.
+///
+/// A `#[destructor] fn name(&mut self);` declaration (no body) generates the destructor
+/// entry from the struct's `Drop` impl. Deleting calls free the object as a `Box<Self>`.
+///
+/// Whether a method returns through the MSVC hidden pointer comes from the interface, so
+/// `#[scalar_return]` doesn't need repeating.
+///
+/// # Options
+/// - `abi = "msvc"` / `abi = "itanium"` - Optional: the interface's ABI is used by default.
+///   When given it must match the interface; a mismatch is a compile-time error.
+/// - `stdcall` / `thiscall` / `cdecl`, `win64` / `sysv64` - Optional: the interface's
+///   calling conventions are used by default. When given they must repeat all of the
+///   interface's convention options, or the vtable entries don't type-check.
+/// - `extends(IBase)` - Implement an interface declared with `extends(IBase)`. The vtable
+///   is built by the vtable macro each interface exports under its own name (`IFoo!`)
+///   down the whole chain, so inherited methods can be in this impl block or any other
+///   impl block of the struct. The interfaces of the chain must be in scope, like their
+///   `{Name}VTable` structs; they can be declared anywhere, in this crate or another.
+/// - `extends(virtual IBase)` - Implement an interface with a virtual base. The struct
+///   needs a `vbptr_*: *const i32` field right after the interface's vtable field (set it
+///   to `VBTABLE_*`) and an `IBase` vtable field, implemented by its own
+///   `#[cppvtable_impl(IBase)]`. Interfaces sharing the virtual base share that field.
+/// - `checked` - Guard every call with the struct's `borrow_flag: cppvtable::BorrowFlag`
+///   field: `&self` methods share it, `&mut self` methods take it exclusively, and a
+///   reentrant call that conflicts panics (handled by the interface's panic policy).
+///   Methods marked `#[reentrant]` take `&self` and skip the flag. Not available with
+///   `extends(...)`: inherited methods go through the base interfaces' forwarders,
+///   which don't check the flag.
+/// - `no_cpp_class` - Don't implement `cppvtable::CppClass` from this block's
+///   `#[destructor]`, because another impl block of the struct does.
+/// - `vtable_group(IFoo, ...)` / `grouped` - Emit the struct's Itanium vtables as one
+///   group, like g++: the block of the vtable field at offset 0 lists the other
+///   interfaces in `vtable_group(...)` and defines their `VTABLE_*` consts, and their
+///   blocks say `grouped`. No effect with the MSVC ABI.
+/// - `rtti` - Put the struct's `cppvtable::rtti::TypeInfo` at slot -1 of the static
+///   vtable and the offset-to-top at slot -2 (a `VTableWithRtti` for MSVC, the Itanium
+///   prefix otherwise), for `get_type_info`/`type_info_of` and `query::<I>()`. Exactly one impl block of the struct uses
+///   `rtti(IFoo, IBar, ...)` instead, listing every interface: it defines the `TypeInfo`
+///   from their `INTERFACE_INFO_*` consts, each followed by the interfaces it extends
+///   non-virtually, and implements `HasTypeInfo`.
+///
+/// With the Itanium ABI the static vtable is a `cppvtable::itanium::ItaniumVTable` whose
+/// offset-to-top is minus the offset of the interface's vtable field, like g++'s
+/// secondary vtables. Its typeinfo slot holds no `std::type_info`, so C++ `typeid` and
+/// `dynamic_cast` other than to `void*` don't work on Rust objects.
+///
+/// Also implements `cppvtable::Implements<IAnimal>` for the struct (the vtable field's
+/// offset, used by `CppBox` casts) and, for a block with a `#[destructor]`,
+/// `cppvtable::CppClass` so that `CppBox` frees through the deleting destructor. A struct
+/// with destructors in several impl blocks gets it from one of them; the others add
+/// `no_cpp_class` and share it, since every deleting destructor frees the whole object.
 ///
 /// # Example
 /// ```ignore
 /// #[cppvtable_impl(IAnimal)]
 /// impl Dog {
 ///     fn speak(&self) { println!("Woof!"); }  // slot 0
-///     #[slot(5)]
-///     fn jump(&self) { }                       // slot 5
+///     fn jump(&self) { }                       // slot 5, from the interface
 ///     fn legs(&self) -> i32 { 4 }              // slot 6
 /// }
 /// ```
 #[proc_macro_attribute]
 pub fn cppvtable_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let interface_name = parse_macro_input!(attr as Ident);
+    let (interface_name, config) = match parse_cppvtable_impl_config(attr) {
+        Ok(parsed) => parsed,
+        Err(err) => return err.to_compile_error().into(),
+    };
     let input = parse_macro_input!(item as ItemImpl);
-    match cppvtable_impl_impl(interface_name, input) {
+    match cppvtable_impl_internal(interface_name, input, config) {
         Ok(tokens) => tokens.into(),
         Err(err) => err.to_compile_error().into(),
     }
@@ -1809,9 +4425,21 @@ fn parse_guid_string(s: &str) -> Result<(u32, u16, u16, [u8; 8]), String> {
 /// - A vtable struct `{Name}VTable` with IUnknown methods (slots 0-2) + your methods
 /// - An interface wrapper struct `{Name}` with method wrappers
 /// - An IID constant `IID_{NAME}` parsed from the GUID string
+/// - A `cppvtable::ComInterface` impl, so `ComPtr<{Name}>` can hold the interface
 ///
 /// Uses `stdcall` calling convention on x86 (not `thiscall` like C++ vtables).
 ///
+/// A `win64` or `sysv64` option after the GUID fixes the x86_64 convention, e.g. for
+/// `ms_abi` COM under Wine on Linux. The interface then extends `IUnknownWin64` or
+/// `IUnknownSysv64` instead of `IUnknown`, which must be in scope.
+///
+/// `extends(IBase)` derives from another COM interface instead of IUnknown (with the same
+/// `win64`/`sysv64` option). The vtable embeds the base's, and QueryInterface on an
+/// implementing object answers for both IIDs.
+///
+/// `panic = "hresult"` makes methods returning `HRESULT` return `E_UNEXPECTED` when their
+/// implementation panics (see `#[cppvtable]` for the other policies).
+///
 /// # Example
 /// ```ignore
 /// #[com_interface("12345678-1234-1234-1234-123456789abc")]
@@ -1823,11 +4451,18 @@ fn parse_guid_string(s: &str) -> Result<(u32, u16, u16, [u8; 8]), String> {
 /// ```
 #[proc_macro_attribute]
 pub fn com_interface(attr: TokenStream, item: TokenStream) -> TokenStream {
-    // Parse the GUID string from the attribute
-    let guid_str: syn::LitStr = match syn::parse(attr) {
+    // Parse the GUID string from the attribute, then the options after it
+    let attr2: TokenStream2 = attr.into();
+    let tokens: Vec<_> = attr2.into_iter().collect();
+    let guid_str: syn::LitStr = match syn::parse2(tokens.first().cloned().into_iter().collect()) {
         Ok(s) => s,
         Err(e) => return e.to_compile_error().into(),
     };
+    let options = match parse_com_options(&tokens[1..], false) {
+        Ok(options) => options,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let x64_convention = options.x64_convention;
 
     // Parse GUID
     let (data1, data2, data3, data4) = match parse_guid_string(&guid_str.value()) {
@@ -1839,10 +4474,19 @@ pub fn com_interface(attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     };
 
-    // Create COM config: stdcall + extends(IUnknown) + GUID IID
+    // Create COM config: stdcall + extends(IUnknown or another COM interface) + GUID IID
+    // COM interfaces follow the MSVC layout on every target (no Itanium prefix)
     let config = VTableConfig {
         calling_convention: CallingConvention::Stdcall,
-        base_interface: Some(syn::Ident::new("IUnknown", proc_macro2::Span::call_site())),
+        x64_convention,
+        abi: CppAbi::Msvc,
+        panic_policy: options.panic_policy,
+        base_interface: Some(
+            options
+                .base_interface
+                .unwrap_or_else(|| x64_convention.iunknown()),
+        ),
+        virtual_base: false,
         iid: InterfaceId::Guid {
             data1,
             data2,
@@ -1850,7 +4494,7 @@ pub fn com_interface(attr: TokenStream, item: TokenStream) -> TokenStream {
             data4,
         },
         slot_overrides: std::collections::HashMap::new(),
-        internal: false,
+        internal: options.internal,
         no_forwarders: false,
     };
 
@@ -1863,37 +4507,269 @@ pub fn com_interface(attr: TokenStream, item: TokenStream) -> TokenStream {
 
 /// Internal implementation of com_implement
 fn com_implement_internal(
-    interface_name: Ident,
+    interfaces: Vec<Ident>,
+    options: ComOptions,
     input: ItemImpl,
 ) -> Result<TokenStream2, syn::Error> {
-    // COM uses stdcall, inherits from IUnknown (3 slots), no RTTI
-    let iid_const = format_ident!("IID_{}", interface_name.to_string().to_uppercase());
+    // Weak references resolve through the object's own count and IUnknown, and the weak
+    // reference objects only have default-convention vtables
+    if options.weak && options.aggregatable {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "'weak' can't be combined with 'aggregatable'",
+        ));
+    }
+    if options.weak && options.x64_convention != X64CallingConvention::System {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "'weak' can't be combined with 'win64' or 'sysv64'",
+        ));
+    }
 
-    let config = ImplConfig {
-        calling_convention: CallingConvention::Stdcall,
-        base_interface: Some(format_ident!("IUnknown")),
-        first_slot: 3, // IUnknown has QueryInterface, AddRef, Release
-        generate_rtti: false,
-        iid_const: Some(iid_const),
-        internal: false,
+    // COM uses stdcall, MSVC layout and no RTTI. Each vtable comes from the interface's own
+    // forwarders, down its extends chain to IUnknown, so the block only supplies methods
+    // (of any of the interfaces). The first interface also gets the object's IUnknown
+    // methods, which answer QueryInterface for all of them.
+    let mut expanded = TokenStream2::new();
+    for (index, interface_name) in interfaces.iter().enumerate() {
+        let primary = index == 0;
+        let iid_const = format_ident!("IID_{}", interface_name.to_string().to_uppercase());
+        let config = ImplConfig {
+            calling_convention: CallingConvention::Stdcall,
+            x64_convention: options.x64_convention,
+            explicit_conventions: true,
+            abi: CppAbi::Msvc,
+            base_interface: None,
+            virtual_base: false,
+            checked: false,
+            generate_rtti: false,
+            rtti: false,
+            rtti_interfaces: Vec::new(),
+            iid_const: primary.then_some(iid_const),
+            internal: false,
+            interface_forwarders: true,
+            com_heap: primary && options.heap,
+            com_interfaces: if primary {
+                interfaces.clone()
+            } else {
+                Vec::new()
+            },
+            com_aggregatable: primary && options.aggregatable,
+            com_aggregates: if primary {
+                options.aggregates.clone()
+            } else {
+                Vec::new()
+            },
+            com_tear_offs: if primary {
+                options.tear_offs.clone()
+            } else {
+                Vec::new()
+            },
+            com_weak: primary && options.weak,
+            no_cpp_class: false,
+            vtable_group: Vec::new(),
+            grouped: false,
+        };
+        let block = if primary {
+            input.clone()
+        } else {
+            ItemImpl {
+                items: Vec::new(),
+                ..input.clone()
+            }
+        };
+        expanded.extend(cppvtable_impl_internal(
+            interface_name.clone(),
+            block,
+            config,
+        )?);
+    }
+
+    // Tear-offs: a small heap object per query, with the interface's vtable, its own count
+    // and a counted reference to the owner. Its forwarders reach the owner's methods
+    // through Deref, and every query but its own interface goes back to the owner, which
+    // keeps the object's identity. Only a shared borrow of the owner is handed out: other
+    // tear-offs and the owner's own interfaces may be in a call on it at the same time.
+    if !options.tear_offs.is_empty() && !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "tear-off interfaces need a struct without generic parameters",
+        ));
+    }
+    let struct_type = &input.self_ty;
+    let struct_name = match struct_type.as_ref() {
+        Type::Path(type_path) => type_path.path.segments.last().unwrap().ident.clone(),
+        _ => return Err(syn::Error::new(struct_type.span(), "Expected a type path")),
     };
+    let krate = crate_path(false);
+    for interface_name in &options.tear_offs {
+        let tear_off = tear_off_struct_name(&struct_name, interface_name);
+        let vtable_name = format_ident!("{}VTable", interface_name);
+        let vtable_field = interface_to_field_name(interface_name);
+        let vtable_const = format_ident!("{}", vtable_field.to_string().to_uppercase());
+        expanded.extend(quote! {
+            #[repr(C)]
+            struct #tear_off {
+                #vtable_field: *const #vtable_name,
+                ref_count: #krate::ComRefCount,
+                owner: *mut #struct_type,
+            }
+
+            impl #tear_off {
+                /// Create a tear-off holding a reference to `owner`, returning its interface
+                /// pointer with one reference
+                fn new_tear_off(owner: &#struct_type) -> *mut ::std::ffi::c_void {
+                    owner.add_ref();
+                    let tear_off = ::std::boxed::Box::into_raw(::std::boxed::Box::new(Self {
+                        #vtable_field: Self::#vtable_const,
+                        ref_count: #krate::ComRefCount::new(),
+                        owner: owner as *const #struct_type as *mut #struct_type,
+                    }));
+                    // The vtable pointer is the first field
+                    tear_off as *mut ::std::ffi::c_void
+                }
+
+                /// Query for another interface: the tear-off's own, else through the owner
+                ///
+                /// # Safety
+                /// - `riid` must point to a valid GUID
+                /// - `ppv` must point to a valid, writable pointer location
+                unsafe fn query_interface(
+                    &self,
+                    riid: *const #krate::GUID,
+                    ppv: *mut *mut ::std::ffi::c_void,
+                ) -> #krate::HRESULT {
+                    unsafe {
+                        if ppv.is_null() {
+                            return #krate::E_POINTER;
+                        }
+                        let riid_ref = &*riid;
+                        if *riid_ref != #krate::IID_IUNKNOWN
+                            && <#interface_name as #krate::ComInterface>::has_iid(riid_ref)
+                        {
+                            *ppv = &self.#vtable_field as *const _ as *mut ::std::ffi::c_void;
+                            self.add_ref();
+                            return #krate::S_OK;
+                        }
+                        (*self.owner).query_interface(riid, ppv)
+                    }
+                }
+
+                /// Increment the tear-off's reference count
+                fn add_ref(&self) -> u32 {
+                    self.ref_count.add_ref()
+                }
+
+                /// Decrement the tear-off's reference count, freeing it and releasing the
+                /// owner on the last release
+                ///
+                /// # Safety
+                /// `this` must point to a live tear-off, and the caller must own the
+                /// reference it gives up.
+                unsafe fn release(this: *mut Self) -> u32 {
+                    unsafe {
+                        let count = (*this).ref_count.release();
+                        if count == 0 {
+                            let owner = (*this).owner;
+                            drop(::std::boxed::Box::from_raw(this));
+                            <#struct_type>::release(owner);
+                        }
+                        count
+                    }
+                }
+            }
+
+            impl ::std::ops::Deref for #tear_off {
+                type Target = #struct_type;
 
-    cppvtable_impl_internal(interface_name, input, config)
+                fn deref(&self) -> &#struct_type {
+                    // SAFETY: the tear-off holds a reference to its owner
+                    unsafe { &*self.owner }
+                }
+            }
+        });
+        let config = ImplConfig {
+            calling_convention: CallingConvention::Stdcall,
+            x64_convention: options.x64_convention,
+            explicit_conventions: true,
+            abi: CppAbi::Msvc,
+            generate_rtti: false,
+            interface_forwarders: true,
+            ..ImplConfig::default()
+        };
+        let block = ItemImpl {
+            attrs: Vec::new(),
+            self_ty: Box::new(syn::parse_quote!(#tear_off)),
+            items: Vec::new(),
+            ..input.clone()
+        };
+        expanded.extend(cppvtable_impl_internal(
+            interface_name.clone(),
+            block,
+            config,
+        )?);
+    }
+    Ok(expanded)
 }
 
-/// Implement a COM interface for a struct.
+/// Implement one or more COM interfaces for a struct.
 ///
-/// This generates:
+/// This generates, for each interface:
 /// - Static vtable instance with IUnknown methods (QueryInterface, AddRef, Release)
 /// - Wrapper functions that cast `this` and call your methods
 /// - A vtable accessor constant (`VTABLE_I_INTERFACE_NAME`)
-/// - IUnknown methods on the struct (`query_interface`, `add_ref`, `release`)
+///
+/// and once for the object, IUnknown methods on the struct (`query_interface`, `add_ref`,
+/// `release`). QueryInterface answers for every listed interface and the interfaces they
+/// extend, and returns the first interface's pointer for IUnknown, so the object has
+/// one identity.
+///
+/// The vtables are built by the vtable macro each interface exports under its own name,
+/// so the interfaces (and their bases) must be in scope. Methods are found by name,
+/// so the block may hold the methods of every interface (and they may also live in other
+/// impl blocks of the struct), but two interfaces can't share a method name.
 ///
 /// # Requirements
 ///
 /// Your struct must have:
 /// - A `ref_count: ComRefCount` field for reference counting
-/// - A vtable pointer field named `vtable_i_{interface_name}` (auto-derived from interface name)
+/// - A vtable pointer field named `vtable_i_{interface_name}` (auto-derived from interface
+///   name) for each interface
+///
+/// Use one `#[com_implement]` per struct, listing every interface:
+/// `#[com_implement(ICalculator, IPrinter)]`.
+///
+/// Repeat the interface's `win64` or `sysv64` option: `#[com_implement(IMyInterface, win64)]`.
+///
+/// By default `release` only decrements the count, which suits stack and static objects.
+/// With `heap` (`#[com_implement(IMyInterface, heap)]`) the last `release` drops and
+/// frees the object as a `Box`; allocate it with `ComPtr::new`.
+///
+/// `weak` adds weak reference support: QueryInterface answers `IWeakReferenceSource`, whose
+/// weak reference resolves while the object's count is above zero, so
+/// `ComPtr::downgrade` and `ComWeak::upgrade` work on the object. The `ref_count` field
+/// is then a `ComWeakRefCount`, which holds the weak reference. It can't be combined
+/// with `aggregatable` or with `win64`/`sysv64` (see `ComWeakRefCount`).
+///
+/// `tear_off(IRare, ...)` implements interfaces without a vtable field in the struct. Each
+/// query for one allocates a small tear-off object with the interface's vtable, its own
+/// count and a reference to the object. Its methods run on the object, and queries for
+/// other interfaces (IUnknown included) go back to the object, so its identity holds.
+/// The tear-off only borrows the object shared, so the methods of tear-off interfaces
+/// take `&self` (use interior mutability for state they change).
+///
+/// # Aggregation
+///
+/// `aggregatable` makes an object that an outer object can aggregate. It needs a
+/// `vtable_non_delegating` field (set to `VTABLE_NON_DELEGATING`) for its non-delegating
+/// IUnknown, which is its identity and implements `Implements<IUnknown>`, so
+/// `ComPtr::<IUnknown>::new` returns it. Created with `ComRefCount::aggregated(outer)`, the
+/// IUnknown methods of its interfaces call the outer object's instead, and only the
+/// non-delegating IUnknown counts references to the object itself.
+///
+/// `aggregate(field: IInner, ...)` makes the outer side: QueryInterface for `IInner` (and
+/// the interfaces it extends) goes to the inner object held in `field`, an
+/// `Option<ComPtr<IUnknown>>` holding the inner object's non-delegating IUnknown.
 ///
 /// # Example
 /// ```ignore
@@ -1920,9 +4796,47 @@ fn com_implement_internal(
 /// ```
 #[proc_macro_attribute]
 pub fn com_implement(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let interface_name = parse_macro_input!(attr as Ident);
+    let attr2: TokenStream2 = attr.into();
+    let tokens: Vec<_> = attr2.into_iter().collect();
+
+    // Interface names come first, then the options
+    let mut interfaces = Vec::new();
+    let mut i = 0;
+    while let Some(proc_macro2::TokenTree::Ident(ident)) = tokens.get(i) {
+        if [
+            "win64",
+            "sysv64",
+            "heap",
+            "weak",
+            "aggregatable",
+            "aggregate",
+            "tear_off",
+        ]
+        .iter()
+        .any(|option| ident == option)
+        {
+            break;
+        }
+        interfaces.push(ident.clone());
+        i += 1;
+        if matches!(tokens.get(i), Some(proc_macro2::TokenTree::Punct(p)) if p.as_char() == ',') {
+            i += 1;
+        }
+    }
+    if interfaces.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "expected the interface name, e.g. #[com_implement(IMyInterface)]",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let options = match parse_com_options(&tokens[i..], true) {
+        Ok(options) => options,
+        Err(e) => return e.to_compile_error().into(),
+    };
     let input = parse_macro_input!(item as ItemImpl);
-    match com_implement_internal(interface_name, input) {
+    match com_implement_internal(interfaces, options, input) {
         Ok(tokens) => tokens.into(),
         Err(err) => err.to_compile_error().into(),
     }
@@ -0,0 +1,181 @@
+//! Borrow checking for calls from C++
+//!
+//! A generated wrapper turns the incoming `this` into `&self` or `&mut self`. If C++ calls
+//! back into the object while a `&mut self` method is still running, as listener patterns
+//! do, the two calls would alias. `#[cppvtable_impl(IFoo, checked)]` guards every call with
+//! the object's [`BorrowFlag`], which must be a field named `borrow_flag`:
+//!
+//! ```ignore
+//! #[repr(C)]
+//! pub struct Listener {
+//!     vtable_i_listener: *const IListenerVTable,
+//!     borrow_flag: BorrowFlag,
+//!     events: Cell<u32>,
+//! }
+//!
+//! #[cppvtable_impl(IListener, checked)]
+//! impl Listener {
+//!     fn on_event(&mut self, event: i32) { /* may call back into C++ */ }
+//!
+//!     #[reentrant]
+//!     fn event_count(&self) -> u32 { self.events.get() }
+//! }
+//! ```
+//!
+//! `&self` methods share the flag, `&mut self` methods take it exclusively. A call that
+//! conflicts with one in progress panics, and the interface's panic policy decides what
+//! the caller gets (see [`crate::panic`]). `#[reentrant]` methods take `&self` and skip the
+//! flag, so C++ can call them at any time; they should only touch state behind `Cell`,
+//! atomics or locks.
+//!
+//! `checked` isn't available on an `extends(...)` impl: its inherited methods are dispatched
+//! by the base interfaces' forwarders, which may live in other impl blocks and know nothing
+//! of the flag or of `#[reentrant]`.
+//!
+//! ```compile_fail
+//! use cppvtable::BorrowFlag;
+//! use cppvtable::proc::{cppvtable, cppvtable_impl};
+//!
+//! #[cppvtable]
+//! pub trait IBase {
+//!     fn base_value(&self) -> i32;
+//! }
+//!
+//! #[cppvtable(extends(IBase))]
+//! pub trait IDerived {
+//!     fn derived_value(&self) -> i32;
+//! }
+//!
+//! #[repr(C)]
+//! pub struct Checked {
+//!     vtable_i_derived: *const IDerivedVTable,
+//!     borrow_flag: BorrowFlag,
+//! }
+//!
+//! #[cppvtable_impl(IDerived, extends(IBase), checked)]
+//! impl Checked {
+//!     fn derived_value(&self) -> i32 { 2 }
+//! }
+//! ```
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+/// Borrow state of an object called from C++: the number of `&self` calls in progress,
+/// or -1 during a `&mut self` call.
+pub struct BorrowFlag(AtomicIsize);
+
+impl BorrowFlag {
+    /// A flag with no call in progress
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(AtomicIsize::new(0))
+    }
+
+    /// Whether a checked call is in progress
+    #[must_use]
+    pub fn is_borrowed(&self) -> bool {
+        self.0.load(Ordering::Acquire) != 0
+    }
+
+    /// Whether a checked `&mut self` call is in progress
+    #[must_use]
+    pub fn is_borrowed_mut(&self) -> bool {
+        self.0.load(Ordering::Acquire) < 0
+    }
+
+    /// Enter a `&self` call, panicking if a `&mut self` call is in progress
+    #[doc(hidden)]
+    pub fn borrow(&self, location: &'static str) -> BorrowGuard<'_> {
+        // SAFETY: the guard borrows `self`
+        unsafe { Self::enter(self, false, location) }
+    }
+
+    /// Enter a `&mut self` call, panicking if any other call is in progress
+    #[doc(hidden)]
+    pub fn borrow_mut(&self, location: &'static str) -> BorrowGuard<'_> {
+        // SAFETY: the guard borrows `self`
+        unsafe { Self::enter(self, true, location) }
+    }
+
+    /// Enter a call through a raw pointer to the flag: a `&mut self` call if `exclusive`.
+    ///
+    /// Wrappers take the pointer from the object pointer before creating the reference to
+    /// the object, so the guard doesn't hold a reference into it while that `&mut` lives.
+    ///
+    /// # Safety
+    /// `flag` must point to a live `BorrowFlag` until the guard is dropped.
+    #[doc(hidden)]
+    pub unsafe fn enter<'a>(
+        flag: *const Self,
+        exclusive: bool,
+        location: &'static str,
+    ) -> BorrowGuard<'a> {
+        let count = unsafe { &(*flag).0 };
+        if exclusive {
+            if count
+                .compare_exchange(0, -1, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                panic!("reentrant call to {location} while the object is borrowed");
+            }
+        } else {
+            let shared = count.fetch_update(Ordering::Acquire, Ordering::Relaxed, |count| {
+                (count >= 0).then(|| count + 1)
+            });
+            if shared.is_err() {
+                panic!("reentrant call to {location} while the object is mutably borrowed");
+            }
+        }
+        BorrowGuard {
+            flag,
+            exclusive,
+            _flag: PhantomData,
+        }
+    }
+
+    /// Check that no call is in progress before the object is destroyed
+    #[doc(hidden)]
+    pub fn check_unborrowed(&self, location: &'static str) {
+        if self.is_borrowed() {
+            panic!("{location} called while the object is borrowed");
+        }
+    }
+}
+
+impl Default for BorrowFlag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for BorrowFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BorrowFlag")
+            .field(&self.0.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// A call in progress, released on drop (also when the call unwinds)
+#[doc(hidden)]
+#[must_use]
+pub struct BorrowGuard<'a> {
+    // A raw pointer: a reference would be invalidated by the `&mut` to the object
+    flag: *const BorrowFlag,
+    exclusive: bool,
+    _flag: PhantomData<&'a BorrowFlag>,
+}
+
+impl Drop for BorrowGuard<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `enter` requires the flag to outlive the guard
+        let count = unsafe { &(*self.flag).0 };
+        if self.exclusive {
+            count.store(0, Ordering::Release);
+        } else {
+            count.fetch_sub(1, Ordering::Release);
+        }
+    }
+}
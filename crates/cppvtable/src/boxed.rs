@@ -0,0 +1,219 @@
+//! Heap-owned Rust objects for C++
+//!
+//! A C++ caller keeps interface pointers into an object, so the object must neither move
+//! nor be dropped while C++ may still use them. [`CppBox`] allocates the object once and
+//! keeps it at that address until it is dropped:
+//!
+//! ```ignore
+//! impl CppClass for Dog {}
+//!
+//! let dog = CppBox::new(Dog::new());
+//! let animal: *mut IAnimal = dog.as_interface_ptr::<IAnimal>();
+//! cpp_use_animal(animal);
+//!
+//! // Hand ownership to C++, and take it back later
+//! let raw = CppBox::into_raw_interface::<IAnimal>(dog);
+//! let dog = unsafe { CppBox::<Dog>::from_raw_interface(raw) };
+//! ```
+//!
+//! Casts to interfaces use [`Implements`], which `#[cppvtable_impl]` implements for each
+//! interface with the `offset_of!` of its vtable field. Dropping the box frees the object
+//! through [`CppClass::delete`]: the deleting destructor slot when the struct has a
+//! `#[destructor]`, else `Drop` and the `Box` allocation.
+//!
+//! Mutable interface references are `unsafe` to get: an interface wrapper is only the
+//! vtable pointer, and swapping two of them (`std::mem::swap`) would leave each object
+//! with the other one's vtable, which its destructor slot and `Drop` then run through.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::ptr::NonNull;
+
+/// A struct that implements interface `I`, with the interface's vtable pointer at `OFFSET`.
+///
+/// Implemented by `#[cppvtable_impl(I)]`.
+///
+/// # Safety
+/// `OFFSET` must be the byte offset of a field holding a vtable pointer for `I`.
+pub unsafe trait Implements<I> {
+    /// Byte offset from the start of the struct to the interface's vtable pointer
+    const OFFSET: usize;
+}
+
+/// A struct that can be owned by a [`CppBox`].
+///
+/// `#[cppvtable_impl]` implements it for structs with a `#[destructor]`, freeing them
+/// through the deleting destructor slot. Other structs opt in with an empty impl:
+///
+/// ```ignore
+/// impl CppClass for Dog {}
+/// ```
+pub trait CppClass: Sized {
+    /// Destroy and free an object allocated by [`CppBox`].
+    ///
+    /// # Safety
+    /// `this` must come from [`CppBox::into_raw`] (or equivalent) and must not be used
+    /// afterwards.
+    unsafe fn delete(this: *mut Self) {
+        // SAFETY: caller guarantees the pointer came from Box::into_raw
+        unsafe { drop(Box::from_raw(this)) }
+    }
+}
+
+/// An owned, heap-allocated Rust object whose address stays fixed until it is dropped.
+///
+/// Derefs to `&T`; mutable access goes through [`as_mut`](Self::as_mut).
+pub struct CppBox<T: CppClass> {
+    ptr: NonNull<T>,
+    _owned: PhantomData<T>,
+}
+
+// SAFETY: CppBox owns its T like Box does
+unsafe impl<T: CppClass + Send> Send for CppBox<T> {}
+unsafe impl<T: CppClass + Sync> Sync for CppBox<T> {}
+
+impl<T: CppClass> CppBox<T> {
+    /// Move `value` to the heap
+    pub fn new(value: T) -> Self {
+        // SAFETY: Box::into_raw never returns null
+        let ptr = unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(value))) };
+        Self {
+            ptr,
+            _owned: PhantomData,
+        }
+    }
+
+    /// Pointer to the object
+    #[must_use]
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    /// Mutable access to the object.
+    ///
+    /// The pin keeps the allocation in place, not the value: `T` is usually `Unpin`, so the
+    /// value can still be replaced or swapped with another `T`. That is sound since every
+    /// `T` carries the same vtable pointers; interface-level writes are what
+    /// [`as_interface_mut`](Self::as_interface_mut) rules out.
+    pub fn as_mut(&mut self) -> Pin<&mut T> {
+        // SAFETY: the allocation never moves while the box owns it
+        unsafe { Pin::new_unchecked(self.ptr.as_mut()) }
+    }
+
+    /// Cast to interface `I` (adds the interface's vtable field offset)
+    #[must_use]
+    pub fn as_interface<I>(&self) -> &I
+    where
+        T: Implements<I>,
+    {
+        // SAFETY: Implements guarantees a vtable pointer for I at OFFSET
+        unsafe { &*self.as_interface_ptr::<I>() }
+    }
+
+    /// Cast to interface `I` (mutable)
+    ///
+    /// # Safety
+    /// The caller must not overwrite the interface's vtable pointer through the reference,
+    /// by assignment or by swapping it with another interface reference: dropping the box
+    /// would then destroy the object through the wrong vtable.
+    ///
+    /// ```compile_fail,E0133
+    /// # use cppvtable::{CppBox, CppClass, Implements};
+    /// # fn swap<I, A: CppClass + Implements<I>, B: CppClass + Implements<I>>(
+    /// #     a: &mut CppBox<A>,
+    /// #     b: &mut CppBox<B>,
+    /// # ) {
+    /// // Would give `a` the vtable of `b`'s struct
+    /// std::mem::swap(a.as_interface_mut::<I>(), b.as_interface_mut::<I>());
+    /// # }
+    /// ```
+    pub unsafe fn as_interface_mut<I>(&mut self) -> &mut I
+    where
+        T: Implements<I>,
+    {
+        // SAFETY: Implements guarantees a vtable pointer for I at OFFSET, and the caller
+        // leaves it in place
+        unsafe { &mut *self.as_interface_ptr::<I>() }
+    }
+
+    /// Interface pointer for `I`, to pass to C++ while the box keeps ownership
+    #[must_use]
+    pub fn as_interface_ptr<I>(&self) -> *mut I
+    where
+        T: Implements<I>,
+    {
+        // SAFETY: OFFSET is within the object
+        unsafe { (self.ptr.as_ptr() as *mut u8).add(T::OFFSET) as *mut I }
+    }
+
+    /// Give up ownership, returning the object pointer.
+    ///
+    /// Free it with [`from_raw`](Self::from_raw), or from C++ through its destructor slot.
+    #[must_use = "the object leaks unless it is passed back to from_raw"]
+    pub fn into_raw(this: Self) -> *mut T {
+        let ptr = this.ptr.as_ptr();
+        std::mem::forget(this);
+        ptr
+    }
+
+    /// Give up ownership, returning an interface pointer for `I`.
+    #[must_use = "the object leaks unless it is passed back to from_raw_interface"]
+    pub fn into_raw_interface<I>(this: Self) -> *mut I
+    where
+        T: Implements<I>,
+    {
+        let ptr = this.as_interface_ptr::<I>();
+        std::mem::forget(this);
+        ptr
+    }
+
+    /// Take back ownership of an object from [`into_raw`](Self::into_raw).
+    ///
+    /// # Safety
+    /// `ptr` must come from `into_raw` for the same `T`, and ownership must not be
+    /// taken back twice.
+    pub unsafe fn from_raw(ptr: *mut T) -> Self {
+        Self {
+            // SAFETY: caller guarantees a pointer from into_raw
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            _owned: PhantomData,
+        }
+    }
+
+    /// Take back ownership from an interface pointer for `I` (subtracts the field offset).
+    ///
+    /// # Safety
+    /// `ptr` must point into an object from `into_raw`/`into_raw_interface` for the same
+    /// `T`, and ownership must not be taken back twice.
+    pub unsafe fn from_raw_interface<I>(ptr: *mut I) -> Self
+    where
+        T: Implements<I>,
+    {
+        // SAFETY: caller guarantees ptr is the I subobject of a boxed T
+        unsafe { Self::from_raw((ptr as *mut u8).sub(T::OFFSET) as *mut T) }
+    }
+}
+
+impl<T: CppClass> Deref for CppBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the box owns a live T
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: CppClass> Drop for CppBox<T> {
+    fn drop(&mut self) {
+        // SAFETY: the box owns the allocation, and nothing uses it afterwards
+        unsafe { T::delete(self.ptr.as_ptr()) }
+    }
+}
+
+impl<T: CppClass + fmt::Debug> fmt::Debug for CppBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -6,6 +6,11 @@
 //! - [`GUID`] - 128-bit globally unique identifier for interfaces
 //! - [`HRESULT`] - COM return type for error handling
 //! - [`IUnknownVTable`] - Base vtable for all COM interfaces
+//! - [`IUnknownWin64`] / [`IUnknownSysv64`] - IUnknown with a fixed x86_64 calling convention
+//! - [`ComPtr`] - Counted interface pointer (`AddRef` on clone, `Release` on drop)
+//! - [`ComWeak`] - Weak interface pointer, through [`IWeakReferenceSource`]
+//! - [`ComRefCount`] / [`ComWeakRefCount`] - Reference counters for COM objects, the latter
+//!   with weak reference support
 //!
 //! ## Example
 //! ```ignore
@@ -23,8 +28,14 @@
 //! When the `windows-compat` feature is enabled, `GUID` and `HRESULT` are re-exported
 //! from the `windows-core` crate for compatibility with projects using the `windows` crate.
 
+use crate::Implements;
 use std::ffi::c_void;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicPtr, AtomicU32, Ordering, fence};
+use std::sync::{Mutex, PoisonError};
 
 // =============================================================================
 // GUID - Globally Unique Identifier
@@ -177,6 +188,9 @@ pub const E_INVALIDARG: HRESULT = HRESULT(0x8007_0057_u32 as i32);
 #[cfg(feature = "windows-compat")]
 /// Not implemented
 pub const E_NOTIMPL: HRESULT = HRESULT(0x8000_4001_u32 as i32);
+#[cfg(feature = "windows-compat")]
+/// Catastrophic failure (also returned when a method panics under `panic = "hresult"`)
+pub const E_UNEXPECTED: HRESULT = HRESULT(0x8000_FFFF_u32 as i32);
 
 // When windows-compat is disabled, use plain i32 values
 #[cfg(not(feature = "windows-compat"))]
@@ -203,6 +217,9 @@ pub const E_INVALIDARG: HRESULT = 0x8007_0057_u32 as i32;
 #[cfg(not(feature = "windows-compat"))]
 /// Not implemented
 pub const E_NOTIMPL: HRESULT = 0x8000_4001_u32 as i32;
+#[cfg(not(feature = "windows-compat"))]
+/// Catastrophic failure (also returned when a method panics under `panic = "hresult"`)
+pub const E_UNEXPECTED: HRESULT = 0x8000_FFFF_u32 as i32;
 
 /// Check if an HRESULT indicates success (non-negative)
 #[cfg(feature = "windows-compat")]
@@ -265,23 +282,78 @@ pub const IID_IUNKNOWN: GUID = GUID::new(
 /// - `IUnknownVTable<T>` struct with function pointers (generic for typed this pointer)
 /// - `IUnknown` wrapper struct with safe methods
 /// - `VTableLayout` impl
-/// - `iunknown_forwarders!` macro for derived interfaces
-/// - `iunknown_base_vtable!` macro for vtable initialization
+/// - `IUnknown!` vtable macro, building the forwarders and vtable of derived interfaces
 ///
 /// The generic parameter `T` represents the concrete type implementing the interface,
 /// allowing type-safe function pointers with `*mut T` instead of `*mut c_void`.
-#[crate::proc::cppvtable(stdcall, no_iid, internal)]
-pub trait IUnknown<T> {
+#[crate::proc::cppvtable(stdcall, abi = "msvc", no_iid, internal)]
+pub trait IUnknown<T = c_void> {
+    /// Query for another interface by GUID.
+    fn query_interface(&self, riid: *const GUID, ppv: *mut *mut c_void) -> HRESULT;
+
+    /// Increment reference count. Returns new count.
+    fn add_ref(&self) -> u32;
+
+    /// Decrement reference count. Returns new count. Implementations take
+    /// `this: *mut Self`, since the last release frees the object.
+    #[raw_receiver]
+    fn release(&mut self) -> u32;
+}
+
+/// IUnknown with the Microsoft x64 convention on every x86_64 target.
+///
+/// Base of `#[com_interface("...", win64)]` interfaces, for COM objects shared with
+/// `ms_abi` code such as Wine or DXVK on Linux. On other architectures it's the same
+/// as [`IUnknown`].
+#[crate::proc::cppvtable(stdcall, win64, abi = "msvc", no_iid, internal)]
+pub trait IUnknownWin64<T = c_void> {
     /// Query for another interface by GUID.
     fn query_interface(&self, riid: *const GUID, ppv: *mut *mut c_void) -> HRESULT;
 
     /// Increment reference count. Returns new count.
     fn add_ref(&self) -> u32;
 
-    /// Decrement reference count. Returns new count.
+    /// Decrement reference count. Returns new count. Implementations take
+    /// `this: *mut Self`, since the last release frees the object.
+    #[raw_receiver]
     fn release(&mut self) -> u32;
 }
 
+/// IUnknown with the System V convention on every x86_64 target.
+///
+/// Base of `#[com_interface("...", sysv64)]` interfaces. On other architectures it's the
+/// same as [`IUnknown`].
+#[crate::proc::cppvtable(stdcall, sysv64, abi = "msvc", no_iid, internal)]
+pub trait IUnknownSysv64<T = c_void> {
+    /// Query for another interface by GUID.
+    fn query_interface(&self, riid: *const GUID, ppv: *mut *mut c_void) -> HRESULT;
+
+    /// Increment reference count. Returns new count.
+    fn add_ref(&self) -> u32;
+
+    /// Decrement reference count. Returns new count. Implementations take
+    /// `this: *mut Self`, since the last release frees the object.
+    #[raw_receiver]
+    fn release(&mut self) -> u32;
+}
+
+// The RTTI identity of all three IUnknown flavours is the stable ID of IUnknown's GUID,
+// like that of `#[com_interface]`s
+impl<T> crate::rtti::RttiInterface for IUnknown<T> {
+    const INTERFACE_ID: crate::rtti::InterfaceId =
+        crate::rtti::stable_interface_id("{00000000-0000-0000-C000-000000000046}");
+}
+
+impl<T> crate::rtti::RttiInterface for IUnknownWin64<T> {
+    const INTERFACE_ID: crate::rtti::InterfaceId =
+        <IUnknown as crate::rtti::RttiInterface>::INTERFACE_ID;
+}
+
+impl<T> crate::rtti::RttiInterface for IUnknownSysv64<T> {
+    const INTERFACE_ID: crate::rtti::InterfaceId =
+        <IUnknown as crate::rtti::RttiInterface>::INTERFACE_ID;
+}
+
 // =============================================================================
 // ComRefCount - Atomic reference counter for COM objects
 // =============================================================================
@@ -289,36 +361,75 @@ pub trait IUnknown<T> {
 /// Atomic reference counter for COM objects.
 ///
 /// Embed this in your COM object struct for automatic reference counting.
-/// Use with `#[com_implement]` for auto-generated AddRef/Release.
-#[repr(transparent)]
-pub struct ComRefCount(AtomicU32);
+/// Use with `#[com_implement]` for auto-generated AddRef/Release, and with
+/// `#[com_implement(I, heap)]` to free the object on the last Release.
+///
+/// An object created with [`aggregated`](Self::aggregated) also remembers the controlling
+/// IUnknown of the outer object aggregating it, see `#[com_implement(I, aggregatable)]`.
+///
+/// Objects supporting weak references use [`ComWeakRefCount`] instead.
+pub struct ComRefCount {
+    count: AtomicU32,
+    outer: *mut c_void,
+}
+
+// SAFETY: the outer IUnknown pointer is never written after construction, and COM objects
+// shared between threads must accept IUnknown calls from any of them
+unsafe impl Send for ComRefCount {}
+unsafe impl Sync for ComRefCount {}
 
 impl ComRefCount {
     /// Create a new reference counter with count = 1
     #[must_use]
     pub const fn new() -> Self {
-        Self(AtomicU32::new(1))
+        Self::aggregated(std::ptr::null_mut())
+    }
+
+    /// Create the reference counter (count = 1) of an object aggregated by `outer`, the
+    /// outer object's controlling IUnknown, or of a standalone object if it is null.
+    ///
+    /// The count then only covers the inner object's non-delegating IUnknown, which the
+    /// outer object holds. The inner object must not add a reference to `outer`, which
+    /// owns it and outlives it.
+    #[must_use]
+    pub const fn aggregated(outer: *mut c_void) -> Self {
+        Self {
+            count: AtomicU32::new(1),
+            outer,
+        }
+    }
+
+    /// The controlling outer IUnknown, if the object is aggregated.
+    #[inline]
+    #[must_use]
+    pub fn outer(&self) -> Option<NonNull<c_void>> {
+        NonNull::new(self.outer)
     }
 
     /// Increment the reference count. Returns the new count.
     #[inline]
     pub fn add_ref(&self) -> u32 {
-        self.0.fetch_add(1, Ordering::Relaxed) + 1
+        self.count.fetch_add(1, Ordering::Relaxed) + 1
     }
 
     /// Decrement the reference count. Returns the new count.
     ///
-    /// When count reaches 0, the caller should destroy the object.
+    /// When count reaches 0, the caller should destroy the object. The last decrement
+    /// acquires every earlier one, so uses through other references happen before that.
     #[inline]
     pub fn release(&self) -> u32 {
-        self.0.fetch_sub(1, Ordering::Release) - 1
+        let count = self.count.fetch_sub(1, Ordering::Release) - 1;
+        if count == 0 {
+            fence(Ordering::Acquire);
+        }
+        count
     }
 
     /// Get the current reference count.
     #[inline]
     #[must_use]
     pub fn count(&self) -> u32 {
-        self.0.load(Ordering::Relaxed)
+        self.count.load(Ordering::Relaxed)
     }
 }
 
@@ -329,15 +440,694 @@ impl Default for ComRefCount {
 }
 
 // =============================================================================
-// Helper trait for COM interface identification
+// ComWeakRefCount - Reference counter for COM objects with weak references
 // =============================================================================
 
-/// Trait for types that have a COM interface ID (IID).
+/// Reference counter for `#[com_implement(I, weak)]` objects, which embed it as their
+/// `ref_count` field instead of a [`ComRefCount`].
+///
+/// Besides the count it owns the object's weak reference once one is asked for, and cuts
+/// it off from the object when dropped. Objects without weak references don't pay for
+/// that pointer.
 ///
-/// Automatically implemented by `#[com_interface]`.
+/// Weak references can't be combined with `aggregatable`: they resolve through the
+/// object's own count and IUnknown, not the outer object's. Nor with `win64` or
+/// `sysv64`: the weak reference objects have default-convention vtables, which a client
+/// speaking the fixed convention can't call, so the combination is a compile error:
+///
+/// ```compile_fail
+/// use cppvtable::com::*;
+/// use cppvtable::proc::{com_implement, com_interface};
+///
+/// #[com_interface("8b2b0f4e-6c64-4c55-9d0f-2f1d5e4a7c31", win64)]
+/// pub trait ICounter {
+///     fn get(&self) -> HRESULT;
+/// }
+///
+/// #[repr(C)]
+/// pub struct Counter {
+///     vtable_i_counter: *const ICounterVTable,
+///     ref_count: ComWeakRefCount,
+/// }
+///
+/// // error: 'weak' can't be combined with 'win64' or 'sysv64'
+/// #[com_implement(ICounter, win64, heap, weak)]
+/// impl Counter {
+///     fn get(&self) -> HRESULT {
+///         0
+///     }
+/// }
+/// ```
+pub struct ComWeakRefCount {
+    count: ComRefCount,
+    weak: AtomicPtr<WeakReference>,
+}
+
+impl ComWeakRefCount {
+    /// Create a new reference counter with count = 1 and no weak reference yet
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            count: ComRefCount::new(),
+            weak: AtomicPtr::new(std::ptr::null_mut()),
+        }
+    }
+
+    /// Increment the reference count. Returns the new count.
+    #[inline]
+    pub fn add_ref(&self) -> u32 {
+        self.count.add_ref()
+    }
+
+    /// Decrement the reference count. Returns the new count, see [`ComRefCount::release`].
+    #[inline]
+    pub fn release(&self) -> u32 {
+        self.count.release()
+    }
+
+    /// Get the current reference count.
+    #[inline]
+    #[must_use]
+    pub fn count(&self) -> u32 {
+        self.count.count()
+    }
+
+    /// Create an [`IWeakReferenceSource`] for the object, returning a new reference to it.
+    ///
+    /// The source is a small heap object holding a reference to the object; its queries
+    /// for other interfaces go to `identity`.
+    ///
+    /// # Safety
+    /// `identity` must be the IUnknown of the live, non-aggregated object holding this
+    /// counter, which must stay at its address until it is dropped.
+    pub unsafe fn weak_reference_source(&self, identity: *mut c_void) -> *mut c_void {
+        // SAFETY: caller guarantees a live identity
+        unsafe { <IUnknown as ComInterface>::raw_add_ref(identity as *mut IUnknown) };
+        let source = Box::new(WeakReferenceSource {
+            vtable: &WEAK_REFERENCE_SOURCE_VTABLE,
+            refs: AtomicU32::new(1),
+            identity,
+            ref_count: self,
+        });
+        Box::into_raw(source) as *mut c_void
+    }
+
+    /// New reference to the object's weak reference, created on first use
+    ///
+    /// # Safety
+    /// Same as [`weak_reference_source`](Self::weak_reference_source).
+    unsafe fn weak_reference(&self, identity: *mut c_void) -> *mut c_void {
+        let mut weak = self.weak.load(Ordering::Acquire);
+        if weak.is_null() {
+            // The counter holds the first reference, given up when it is dropped
+            let created = Box::into_raw(Box::new(WeakReference {
+                vtable: &WEAK_REFERENCE_VTABLE,
+                refs: AtomicU32::new(1),
+                target: Mutex::new(Some(WeakTarget {
+                    identity,
+                    count: &self.count.count,
+                })),
+            }));
+            weak = match self.weak.compare_exchange(
+                std::ptr::null_mut(),
+                created,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => created,
+                Err(existing) => {
+                    // SAFETY: another thread won the race, nothing saw ours
+                    unsafe { drop(Box::from_raw(created)) };
+                    existing
+                }
+            };
+        }
+        // SAFETY: the counter keeps its weak reference alive
+        unsafe { (*weak).refs.fetch_add(1, Ordering::Relaxed) };
+        weak as *mut c_void
+    }
+}
+
+impl Drop for ComWeakRefCount {
+    fn drop(&mut self) {
+        let weak = *self.weak.get_mut();
+        if !weak.is_null() {
+            // SAFETY: the counter holds a reference to its weak reference
+            unsafe {
+                *(*weak)
+                    .target
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = None;
+                WeakReference::release(weak);
+            }
+        }
+    }
+}
+
+impl Default for ComWeakRefCount {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =============================================================================
+// ComInterface - IID and raw IUnknown access for ComPtr
+// =============================================================================
+
+/// A COM interface: its interface ID (IID) and raw access to its IUnknown methods.
+///
+/// Automatically implemented by `#[com_interface]`, and implemented here for
+/// [`IUnknown`] and its fixed-convention variants.
 pub trait ComInterface {
     /// The interface ID (IID) for this interface.
     const IID: GUID;
+
+    /// Whether `iid` names this interface or one it extends, down to IUnknown.
+    fn has_iid(iid: &GUID) -> bool;
+
+    /// Call `QueryInterface` through the interface's vtable.
+    ///
+    /// # Safety
+    /// - `this` must point to a live object implementing the interface
+    /// - `riid` must point to a valid GUID and `ppv` to a writable pointer location
+    unsafe fn raw_query_interface(
+        this: *mut Self,
+        riid: *const GUID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+
+    /// Call `AddRef` through the interface's vtable. Returns the new count.
+    ///
+    /// # Safety
+    /// `this` must point to a live object implementing the interface.
+    unsafe fn raw_add_ref(this: *mut Self) -> u32;
+
+    /// Call `Release` through the interface's vtable. Returns the new count.
+    ///
+    /// # Safety
+    /// `this` must point to a live object implementing the interface, and the caller
+    /// must own the reference it gives up.
+    unsafe fn raw_release(this: *mut Self) -> u32;
+}
+
+/// A COM object that frees itself: its last `Release` drops it as a `Box<Self>`.
+///
+/// Implemented by `#[com_implement(I, heap)]`. Allocate such objects with
+/// [`ComPtr::new`]; objects without it (on the stack, or in a static) are never freed
+/// by `Release`.
+///
+/// # Safety
+/// The object's final `Release` must free it with `Box::from_raw`, and nothing else may.
+pub unsafe trait ComHeap: Sized {}
+
+macro_rules! iunknown_com_interface {
+    ($($iunknown:ident),*) => {
+        $(
+            impl<T> ComInterface for $iunknown<T> {
+                const IID: GUID = IID_IUNKNOWN;
+
+                #[inline]
+                fn has_iid(iid: &GUID) -> bool {
+                    *iid == IID_IUNKNOWN
+                }
+
+                #[inline]
+                unsafe fn raw_query_interface(
+                    this: *mut Self,
+                    riid: *const GUID,
+                    ppv: *mut *mut c_void,
+                ) -> HRESULT {
+                    unsafe { (*this).query_interface(riid, ppv) }
+                }
+
+                #[inline]
+                unsafe fn raw_add_ref(this: *mut Self) -> u32 {
+                    unsafe { (*this).add_ref() }
+                }
+
+                #[inline]
+                unsafe fn raw_release(this: *mut Self) -> u32 {
+                    // Through the vtable without a reference, the call may free the object
+                    unsafe { ((*(*this).vtable).release)(this as *mut T) }
+                }
+            }
+        )*
+    };
+}
+
+iunknown_com_interface!(IUnknown, IUnknownWin64, IUnknownSysv64);
+
+// =============================================================================
+// ComPtr - Reference-counted COM interface pointer
+// =============================================================================
+
+/// A counted reference to a COM interface.
+///
+/// Owns one reference: `Clone` calls `AddRef`, `Drop` calls `Release`. Works the same
+/// for C++ COM objects and `#[com_implement]` objects, since both are reached only
+/// through the interface's vtable.
+///
+/// Derefs to `&I` only, and the interface's methods take `&self`. A `&mut I` would let
+/// safe code swap the vtable pointers of two objects, and `Release` would then run
+/// through the other object's vtable:
+///
+/// ```compile_fail,E0596
+/// # use cppvtable::com::{ComPtr, IUnknown};
+/// # fn swap(a: &mut ComPtr<IUnknown>, b: &mut ComPtr<IUnknown>) {
+/// std::mem::swap(&mut **a, &mut **b);
+/// # }
+/// ```
+///
+/// ```ignore
+/// let mut out = None;
+/// let hr = unsafe { create_calculator(ComPtr::<ICalculator>::as_out_param(&mut out)) };
+/// let calc = out.expect("no calculator");
+/// let scientific: ComPtr<IScientific> = calc.cast()?;
+/// ```
+#[repr(transparent)]
+pub struct ComPtr<I: ComInterface> {
+    ptr: NonNull<I>,
+    _owned: PhantomData<I>,
+}
+
+impl<I: ComInterface> ComPtr<I> {
+    /// Move a `#[com_implement(I, heap)]` object to the heap and take its first
+    /// reference (the count a new `ComRefCount` starts with).
+    ///
+    /// The object is dropped and freed when the last reference is released, whether
+    /// that is by a `ComPtr` or by C++.
+    pub fn new<T>(value: T) -> Self
+    where
+        T: ComHeap + Implements<I>,
+    {
+        Self::from_box(Box::new(value))
+    }
+
+    /// Take the first reference to an already boxed `#[com_implement(I, heap)]` object.
+    ///
+    /// Like [`new`](Self::new), for objects that need their final address before they are
+    /// shared, such as an outer object creating the inner objects it aggregates.
+    pub fn from_box<T>(object: Box<T>) -> Self
+    where
+        T: ComHeap + Implements<I>,
+    {
+        let object = Box::into_raw(object);
+        // SAFETY: Implements guarantees a vtable pointer for I at OFFSET, and the object
+        // owns one reference that nothing else holds
+        unsafe {
+            let iface = (object as *mut u8).add(T::OFFSET) as *mut c_void;
+            Self::from_raw(iface).unwrap_unchecked()
+        }
+    }
+
+    /// Take ownership of one reference to an interface pointer, or `None` if it is null.
+    ///
+    /// # Safety
+    /// - `ptr` must be null or point to a live object implementing `I`
+    /// - The caller must own the reference, which `Drop` releases
+    pub unsafe fn from_raw(ptr: *mut c_void) -> Option<Self> {
+        NonNull::new(ptr as *mut I).map(|ptr| Self {
+            ptr,
+            _owned: PhantomData,
+        })
+    }
+
+    /// Interface pointer, to pass to COM code while the `ComPtr` keeps its reference
+    #[must_use]
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr.as_ptr() as *mut c_void
+    }
+
+    /// Give up the reference without releasing it
+    #[must_use = "the reference leaks unless it is released elsewhere"]
+    pub fn into_raw(this: Self) -> *mut c_void {
+        let ptr = this.as_ptr();
+        std::mem::forget(this);
+        ptr
+    }
+
+    /// Clear `out` and return it as a `void**` out parameter, for functions like
+    /// `QueryInterface` or `CoCreateInstance` that return a new reference.
+    ///
+    /// A non-null pointer written through it is owned by `out`.
+    pub fn as_out_param(out: &mut Option<Self>) -> *mut *mut c_void {
+        *out = None;
+        // Option<ComPtr> has the layout of a nullable interface pointer
+        out as *mut Option<Self> as *mut *mut c_void
+    }
+
+    /// Query the object for interface `J`.
+    ///
+    /// Returns the failing `HRESULT` (usually `E_NOINTERFACE`) if the object doesn't
+    /// implement it.
+    pub fn cast<J: ComInterface>(&self) -> Result<ComPtr<J>, HRESULT> {
+        let mut out = None;
+        // SAFETY: the ComPtr holds a live interface pointer
+        let hr = unsafe {
+            I::raw_query_interface(self.ptr.as_ptr(), &J::IID, ComPtr::as_out_param(&mut out))
+        };
+        if failed(hr) {
+            return Err(hr);
+        }
+        out.ok_or(E_NOINTERFACE)
+    }
+
+    /// Weak reference to the object, through its [`IWeakReferenceSource`].
+    ///
+    /// Returns the failing `HRESULT` (usually `E_NOINTERFACE`) if the object doesn't
+    /// support weak references.
+    pub fn downgrade(&self) -> Result<ComWeak<I>, HRESULT> {
+        let source = self.cast::<IWeakReferenceSource>()?;
+        let mut out = None;
+        // SAFETY: the ComPtr holds a live IWeakReferenceSource
+        let hr =
+            unsafe { (*source.ptr.as_ptr()).get_weak_reference(ComPtr::as_out_param(&mut out)) };
+        if failed(hr) {
+            return Err(hr);
+        }
+        let weak = out.ok_or(E_FAIL)?;
+        Ok(ComWeak {
+            weak,
+            _interface: PhantomData,
+        })
+    }
+}
+
+impl<I: ComInterface> Clone for ComPtr<I> {
+    fn clone(&self) -> Self {
+        // SAFETY: the ComPtr holds a live interface pointer
+        unsafe { I::raw_add_ref(self.ptr.as_ptr()) };
+        Self {
+            ptr: self.ptr,
+            _owned: PhantomData,
+        }
+    }
+}
+
+impl<I: ComInterface> Drop for ComPtr<I> {
+    fn drop(&mut self) {
+        // SAFETY: the ComPtr owns one reference, and nothing uses it afterwards
+        unsafe { I::raw_release(self.ptr.as_ptr()) };
+    }
+}
+
+impl<I: ComInterface> Deref for ComPtr<I> {
+    type Target = I;
+
+    fn deref(&self) -> &I {
+        // SAFETY: the ComPtr holds a live interface pointer
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<I: ComInterface> fmt::Debug for ComPtr<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ComPtr").field(&self.ptr).finish()
+    }
+}
+
+// =============================================================================
+// Weak references - IWeakReferenceSource, IWeakReference and ComWeak
+// =============================================================================
+
+/// A weak reference to a COM object, which hands out strong references while the
+/// object is alive.
+#[crate::proc::com_interface("00000037-0000-0000-c000-000000000046", internal)]
+pub trait IWeakReference {
+    /// Query the object for `riid`. Once the object is gone, sets `*ppv` to null and
+    /// still returns `S_OK`.
+    fn resolve(&self, riid: *const GUID, ppv: *mut *mut c_void) -> HRESULT;
+}
+
+/// Implemented by objects that support weak references.
+#[crate::proc::com_interface("00000038-0000-0000-c000-000000000046", internal)]
+pub trait IWeakReferenceSource {
+    /// Get a new reference to the object's [`IWeakReference`].
+    fn get_weak_reference(&self, weak: *mut *mut c_void) -> HRESULT;
+}
+
+/// Defines vtable functions with the calling convention of `#[com_interface]` methods:
+/// stdcall on x86, `extern "system"` elsewhere.
+macro_rules! com_vtable_fns {
+    ($(unsafe fn $name:ident($($param:ident: $ty:ty),*) -> $ret:ty $body:block)*) => {
+        $(
+            #[cfg(target_arch = "x86")]
+            unsafe extern "stdcall" fn $name($($param: $ty),*) -> $ret $body
+            #[cfg(not(target_arch = "x86"))]
+            unsafe extern "system" fn $name($($param: $ty),*) -> $ret $body
+        )*
+    };
+}
+
+/// The object behind a weak reference, while it is alive
+struct WeakTarget {
+    identity: *mut c_void,
+    count: *const AtomicU32,
+}
+
+// SAFETY: only used under the weak reference's lock, while the object is alive
+unsafe impl Send for WeakTarget {}
+
+/// The object's `IWeakReference`, shared by all its weak references. The lock keeps the
+/// object from being freed while `Resolve` takes a strong reference.
+#[repr(C)]
+struct WeakReference {
+    vtable: *const IWeakReferenceVTable,
+    refs: AtomicU32,
+    target: Mutex<Option<WeakTarget>>,
+}
+
+impl WeakReference {
+    /// Give up one reference, freeing the weak reference on the last one
+    unsafe fn release(this: *mut Self) -> u32 {
+        // SAFETY: caller owns a reference
+        let count = unsafe { (*this).refs.fetch_sub(1, Ordering::Release) } - 1;
+        if count == 0 {
+            fence(Ordering::Acquire);
+            // SAFETY: allocated by ComWeakRefCount::weak_reference, this was the last reference
+            unsafe { drop(Box::from_raw(this)) };
+        }
+        count
+    }
+}
+
+static WEAK_REFERENCE_VTABLE: IWeakReferenceVTable = IWeakReferenceVTable {
+    base: IUnknownVTable {
+        query_interface: weak_reference_query_interface,
+        add_ref: weak_reference_add_ref,
+        release: weak_reference_release,
+    },
+    resolve: weak_reference_resolve,
+};
+
+com_vtable_fns! {
+    unsafe fn weak_reference_query_interface(
+        this: *mut c_void,
+        riid: *const GUID,
+        ppv: *mut *mut c_void
+    ) -> HRESULT {
+        unsafe {
+            if ppv.is_null() {
+                return E_POINTER;
+            }
+            if *riid == IID_IUNKNOWN || *riid == IID_IWEAKREFERENCE {
+                weak_reference_add_ref(this);
+                *ppv = this;
+                return S_OK;
+            }
+            *ppv = std::ptr::null_mut();
+            E_NOINTERFACE
+        }
+    }
+
+    unsafe fn weak_reference_add_ref(this: *mut c_void) -> u32 {
+        unsafe { (*(this as *mut WeakReference)).refs.fetch_add(1, Ordering::Relaxed) + 1 }
+    }
+
+    unsafe fn weak_reference_release(this: *mut c_void) -> u32 {
+        unsafe { WeakReference::release(this as *mut WeakReference) }
+    }
+
+    unsafe fn weak_reference_resolve(
+        this: *mut c_void,
+        riid: *const GUID,
+        ppv: *mut *mut c_void
+    ) -> HRESULT {
+        unsafe {
+            if ppv.is_null() {
+                return E_POINTER;
+            }
+            *ppv = std::ptr::null_mut();
+
+            // Take a strong reference unless the count already reached zero
+            let weak = &*(this as *const WeakReference);
+            let identity = {
+                let target = weak.target.lock().unwrap_or_else(PoisonError::into_inner);
+                match &*target {
+                    Some(target)
+                        if (*target.count)
+                            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |count| {
+                                (count != 0).then_some(count + 1)
+                            })
+                            .is_ok() =>
+                    {
+                        target.identity as *mut IUnknown
+                    }
+                    _ => return S_OK,
+                }
+            };
+
+            let hr = <IUnknown as ComInterface>::raw_query_interface(identity, riid, ppv);
+            <IUnknown as ComInterface>::raw_release(identity);
+            hr
+        }
+    }
+}
+
+/// An `IWeakReferenceSource` handed out by QueryInterface, holding a reference to the
+/// object
+#[repr(C)]
+struct WeakReferenceSource {
+    vtable: *const IWeakReferenceSourceVTable,
+    refs: AtomicU32,
+    identity: *mut c_void,
+    ref_count: *const ComWeakRefCount,
+}
+
+static WEAK_REFERENCE_SOURCE_VTABLE: IWeakReferenceSourceVTable = IWeakReferenceSourceVTable {
+    base: IUnknownVTable {
+        query_interface: weak_reference_source_query_interface,
+        add_ref: weak_reference_source_add_ref,
+        release: weak_reference_source_release,
+    },
+    get_weak_reference: weak_reference_source_get_weak_reference,
+};
+
+com_vtable_fns! {
+    unsafe fn weak_reference_source_query_interface(
+        this: *mut c_void,
+        riid: *const GUID,
+        ppv: *mut *mut c_void
+    ) -> HRESULT {
+        unsafe {
+            if ppv.is_null() {
+                return E_POINTER;
+            }
+            if *riid == IID_IWEAKREFERENCESOURCE {
+                weak_reference_source_add_ref(this);
+                *ppv = this;
+                return S_OK;
+            }
+            // Everything else, IUnknown included, comes from the object
+            let source = &*(this as *const WeakReferenceSource);
+            <IUnknown as ComInterface>::raw_query_interface(
+                source.identity as *mut IUnknown,
+                riid,
+                ppv,
+            )
+        }
+    }
+
+    unsafe fn weak_reference_source_add_ref(this: *mut c_void) -> u32 {
+        unsafe {
+            (*(this as *mut WeakReferenceSource))
+                .refs
+                .fetch_add(1, Ordering::Relaxed)
+                + 1
+        }
+    }
+
+    unsafe fn weak_reference_source_release(this: *mut c_void) -> u32 {
+        unsafe {
+            let source = this as *mut WeakReferenceSource;
+            let count = (*source).refs.fetch_sub(1, Ordering::Release) - 1;
+            if count == 0 {
+                fence(Ordering::Acquire);
+                let source = Box::from_raw(source);
+                <IUnknown as ComInterface>::raw_release(source.identity as *mut IUnknown);
+            }
+            count
+        }
+    }
+
+    unsafe fn weak_reference_source_get_weak_reference(
+        this: *mut c_void,
+        weak: *mut *mut c_void
+    ) -> HRESULT {
+        unsafe {
+            if weak.is_null() {
+                return E_POINTER;
+            }
+            let source = &*(this as *const WeakReferenceSource);
+            *weak = (*source.ref_count).weak_reference(source.identity);
+            S_OK
+        }
+    }
+}
+
+/// A weak reference to a COM interface.
+///
+/// Doesn't keep the object alive: [`upgrade`](Self::upgrade) returns a [`ComPtr`] while
+/// the object is alive and `None` after its last strong reference is released. Created
+/// by [`ComPtr::downgrade`] for objects implementing [`IWeakReferenceSource`], such as
+/// `#[com_implement(I, weak)]` objects.
+pub struct ComWeak<I: ComInterface> {
+    weak: ComPtr<IWeakReference>,
+    _interface: PhantomData<I>,
+}
+
+impl<I: ComInterface> ComWeak<I> {
+    /// A strong reference to the interface, or `None` if the object is gone.
+    pub fn upgrade(&self) -> Option<ComPtr<I>> {
+        let mut out = None;
+        // SAFETY: the ComPtr holds a live IWeakReference
+        let hr =
+            unsafe { (*self.weak.ptr.as_ptr()).resolve(&I::IID, ComPtr::as_out_param(&mut out)) };
+        if failed(hr) {
+            return None;
+        }
+        out
+    }
+}
+
+impl<I: ComInterface> Clone for ComWeak<I> {
+    fn clone(&self) -> Self {
+        Self {
+            weak: self.weak.clone(),
+            _interface: PhantomData,
+        }
+    }
+}
+
+impl<I: ComInterface> fmt::Debug for ComWeak<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ComWeak").field(&self.weak.ptr).finish()
+    }
+}
+
+/// Forward `QueryInterface` to an aggregated inner object, held by the outer object as the
+/// inner object's non-delegating IUnknown.
+///
+/// `#[com_implement(I, aggregate(inner: IInner))]` calls it for the IIDs of `IInner`.
+/// Fails with `E_NOINTERFACE` while there is no inner object.
+///
+/// # Safety
+/// - `riid` must point to a valid GUID
+/// - `ppv` must point to a valid, writable pointer location
+pub unsafe fn query_aggregate<U: ComInterface>(
+    inner: Option<&ComPtr<U>>,
+    riid: *const GUID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    match inner {
+        // SAFETY: the ComPtr holds a live interface pointer, the caller guarantees the rest
+        Some(inner) => unsafe { U::raw_query_interface(inner.ptr.as_ptr(), riid, ppv) },
+        None => {
+            // SAFETY: caller guarantees a writable ppv
+            unsafe { *ppv = std::ptr::null_mut() };
+            E_NOINTERFACE
+        }
+    }
 }
 
 // =============================================================================
@@ -346,10 +1136,129 @@ pub trait ComInterface {
 
 /// Generates the IUnknown method implementations for a COM object.
 ///
-/// Expects the struct to have a `ref_count: ComRefCount` field.
+/// Expects the struct to have a `ref_count: ComRefCount` field. The object's interfaces
+/// are listed as `[IFirst => vtable_i_first, ISecond => vtable_i_second]`: QueryInterface
+/// answers for each of them and the interfaces they extend, and the first one is the
+/// object's identity (what IUnknown queries return). A following
+/// `tear_off [IRare => RareTearOff]` creates a tear-off for each query for `IRare`, with
+/// `RareTearOff::new_tear_off(&self)`. Each `, inner => [IInner]` after that forwards
+/// queries for `IInner` (and its bases) to the aggregated object held in the
+/// `inner: Option<ComPtr<_>>` field, see [`query_aggregate`]. The older form takes a
+/// single vtable field and IID constant.
+///
+/// With a leading `heap`, `release` drops and frees the object (allocated by
+/// [`ComPtr::new`]) when the count reaches zero. With `weak` next, QueryInterface also
+/// answers [`IWeakReferenceSource`] for the identity, see
+/// [`ComWeakRefCount::weak_reference_source`]; the `ref_count` field is then a
+/// [`ComWeakRefCount`].
+///
+/// With `non_delegating` before the struct type, the methods are the non-delegating
+/// IUnknown of an aggregatable object (`non_delegating_query_interface` and so on), whose
+/// identity is the `vtable_non_delegating` field. The object then supplies `add_ref`,
+/// which QueryInterface calls for every other interface.
 #[macro_export]
 macro_rules! iunknown_methods {
-    ($struct_type:ty, $vtable_field:ident, $iid_const:ident) => {
+    (@methods [$($heap:ident)?] [$($weak:ident)?] non_delegating $struct_type:ty,
+        [$($interface:ty => $vtable_field:ident),+ $(,)?] $($rest:tt)*) => {
+        $crate::iunknown_methods!(
+            @query_interface non_delegating_query_interface,
+            vtable_non_delegating,
+            non_delegating_add_ref,
+            [$($weak)?],
+            [$($interface => $vtable_field),+]
+            $($rest)*
+        );
+        $crate::iunknown_methods!(@add_ref non_delegating_add_ref);
+        $crate::iunknown_methods!(@release non_delegating_release $($heap)?);
+    };
+    (@methods [$($heap:ident)?] [$($weak:ident)?] $struct_type:ty,
+        [$first_interface:ty => $first_field:ident $(, $interface:ty => $vtable_field:ident)* $(,)?]
+        $($rest:tt)*) => {
+        $crate::iunknown_methods!(
+            @query_interface query_interface,
+            $first_field,
+            add_ref,
+            [$($weak)?],
+            [$first_interface => $first_field $(, $interface => $vtable_field)*]
+            $($rest)*
+        );
+        $crate::iunknown_methods!(@add_ref add_ref);
+        $crate::iunknown_methods!(@release release $($heap)?);
+    };
+    (@methods [$($heap:ident)?] [] $struct_type:ty, $vtable_field:ident, $iid_const:ident) => {
+        $crate::iunknown_methods!(@query_interface $vtable_field, $iid_const);
+        $crate::iunknown_methods!(@add_ref add_ref);
+        $crate::iunknown_methods!(@release release $($heap)?);
+    };
+    (@query_interface $query_interface:ident, $identity:ident, $identity_add_ref:ident,
+        [$($weak_reference_source:ident)?],
+        [$($interface:ty => $vtable_field:ident),+ $(,)?]
+        $(tear_off [$($tear_off_interface:ty => $tear_off:ident),+ $(,)?])?
+        $(, $inner:ident => [$($aggregated:ty),+ $(,)?])*) => {
+        /// Query for another interface by GUID.
+        ///
+        /// Returns `S_OK` with the interface's pointer if the object implements it (or
+        /// an interface extending it), `E_NOINTERFACE` otherwise. IUnknown always comes
+        /// back as the same pointer, the object's identity.
+        ///
+        /// # Safety
+        /// - `riid` must point to a valid GUID
+        /// - `ppv` must point to a valid, writable pointer location
+        pub unsafe fn $query_interface(
+            &self,
+            riid: *const $crate::GUID,
+            ppv: *mut *mut ::std::ffi::c_void,
+        ) -> $crate::HRESULT {
+            unsafe {
+                if ppv.is_null() {
+                    return $crate::E_POINTER;
+                }
+
+                let riid_ref = &*riid;
+
+                if *riid_ref == $crate::IID_IUNKNOWN {
+                    *ppv = &self.$identity as *const _ as *mut ::std::ffi::c_void;
+                    self.$identity_add_ref();
+                    return $crate::S_OK;
+                }
+
+                $(
+                    if *riid_ref == $crate::com::IID_IWEAKREFERENCESOURCE {
+                        *ppv = self.ref_count.$weak_reference_source(
+                            &self.$identity as *const _ as *mut ::std::ffi::c_void,
+                        );
+                        return $crate::S_OK;
+                    }
+                )?
+
+                $(
+                    if <$interface as $crate::ComInterface>::has_iid(riid_ref) {
+                        let ptr = &self.$vtable_field as *const _ as *mut ::std::ffi::c_void;
+                        *ppv = ptr;
+                        self.add_ref();
+                        return $crate::S_OK;
+                    }
+                )+
+
+                $($(
+                    if <$tear_off_interface as $crate::ComInterface>::has_iid(riid_ref) {
+                        *ppv = $tear_off::new_tear_off(self);
+                        return $crate::S_OK;
+                    }
+                )+)?
+
+                $(
+                    if $(<$aggregated as $crate::ComInterface>::has_iid(riid_ref))||+ {
+                        return $crate::com::query_aggregate(self.$inner.as_ref(), riid, ppv);
+                    }
+                )*
+
+                *ppv = ::std::ptr::null_mut();
+                $crate::E_NOINTERFACE
+            }
+        }
+    };
+    (@query_interface $vtable_field:ident, $iid_const:ident) => {
         /// Query for another interface by GUID.
         ///
         /// Returns `S_OK` if the interface is supported, `E_NOINTERFACE` otherwise.
@@ -381,15 +1290,73 @@ macro_rules! iunknown_methods {
                 $crate::E_NOINTERFACE
             }
         }
-
+    };
+    (@add_ref $add_ref:ident) => {
         /// Increment the reference count.
-        pub fn add_ref(&self) -> u32 {
+        pub fn $add_ref(&self) -> u32 {
             self.ref_count.add_ref()
         }
-
+    };
+    (@release $release:ident heap) => {
+        /// Decrement the reference count, dropping and freeing the object on the last
+        /// release.
+        ///
+        /// Takes a raw pointer because the object may be gone on return.
+        ///
+        /// # Safety
+        /// `this` must point to a live object, and the caller must own the reference it
+        /// gives up.
+        pub unsafe fn $release(this: *mut Self) -> u32 {
+            // SAFETY: the caller guarantees a live object; the borrow of the count ends
+            // before the object is freed
+            let count = unsafe { (*this).ref_count.release() };
+            if count == 0 {
+                // SAFETY: `heap` objects are allocated by ComPtr::new, and this was the
+                // last reference
+                unsafe { drop(::std::boxed::Box::from_raw(this)) };
+            }
+            count
+        }
+    };
+    (@release $release:ident) => {
         /// Decrement the reference count.
-        pub fn release(&mut self) -> u32 {
-            self.ref_count.release()
+        ///
+        /// # Safety
+        /// `this` must point to a live object, and the caller must own the reference it
+        /// gives up.
+        pub unsafe fn $release(this: *mut Self) -> u32 {
+            // SAFETY: the caller guarantees a live object
+            unsafe { (*this).ref_count.release() }
         }
     };
+    (heap weak $($args:tt)*) => {
+        $crate::iunknown_methods!(@methods [heap] [weak_reference_source] $($args)*);
+    };
+    (heap $($args:tt)*) => {
+        $crate::iunknown_methods!(@methods [heap] [] $($args)*);
+    };
+    (weak $($args:tt)*) => {
+        $crate::iunknown_methods!(@methods [] [weak_reference_source] $($args)*);
+    };
+    ($($args:tt)*) => {
+        $crate::iunknown_methods!(@methods [] [] $($args)*);
+    };
+}
+
+/// [`iunknown_methods!`] for `#[com_implement(I, win64)]` objects.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! iunknownwin64_methods {
+    ($($args:tt)*) => {
+        $crate::iunknown_methods!($($args)*);
+    };
+}
+
+/// [`iunknown_methods!`] for `#[com_implement(I, sysv64)]` objects.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! iunknownsysv64_methods {
+    ($($args:tt)*) => {
+        $crate::iunknown_methods!($($args)*);
+    };
 }
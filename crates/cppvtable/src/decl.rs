@@ -179,6 +179,27 @@ macro_rules! define_interface {
 ///     fn fly(&self) { }
 /// }
 /// ```
+///
+/// # Virtual Inheritance
+/// The virtual base's vtable pointer goes after the fields, and a `vbptr_*` field
+/// follows the derived interface's vtable pointer.
+/// ```ignore
+/// define_class! {
+///     class Badge : ILabeled(virtual INamed) {
+///         id: i32,
+///     }
+/// }
+///
+/// #[cppvtable_impl(INamed)]
+/// impl Badge {
+///     fn id(&self) -> i32 { self.id }
+/// }
+///
+/// #[cppvtable_impl(ILabeled, extends(virtual INamed))]
+/// impl Badge {
+///     fn label(&self) -> i32 { 1 }
+/// }
+/// ```
 #[macro_export]
 macro_rules! define_class {
     // Single inheritance
@@ -219,6 +240,68 @@ macro_rules! define_class {
         }
     };
 
+    // Single inheritance with a virtual base
+    (
+        $(#[$meta:meta])*
+        $vis:vis class $name:ident : $base:ident(virtual $vbase:ident) {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field_name:ident : $field_ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $crate::paste! {
+            $(#[$meta])*
+            #[repr(C)]
+            $vis struct $name {
+                /// VTable pointer for $base interface
+                pub [<vtable_ $base:snake>]: *const [<$base VTable>],
+                /// vbtable pointer for $base (read by MSVC to find $vbase)
+                pub [<vbptr_ $base:snake>]: *const i32,
+                $(
+                    $(#[$field_meta])*
+                    $field_vis $field_name: $field_ty,
+                )*
+                /// VTable pointer for the virtual base $vbase
+                pub [<vtable_ $vbase:snake>]: *const [<$vbase VTable>],
+            }
+
+            impl $name {
+                /// Cast to interface (no adjustment needed)
+                #[inline]
+                pub fn [<as_ $base:snake>](&self) -> &$base {
+                    unsafe { &*(self as *const Self as *const $base) }
+                }
+
+                /// Cast to interface (mutable)
+                #[inline]
+                pub fn [<as_ $base:snake _mut>](&mut self) -> &mut $base {
+                    unsafe { &mut *(self as *mut Self as *mut $base) }
+                }
+
+                /// Cast to the virtual base (fixed offset within this class)
+                #[inline]
+                pub fn [<as_ $vbase:snake>](&self) -> &$vbase {
+                    unsafe {
+                        let ptr = (self as *const Self as *const u8)
+                            .add(::std::mem::offset_of!(Self, [<vtable_ $vbase:snake>]));
+                        &*(ptr as *const $vbase)
+                    }
+                }
+
+                /// Cast to the virtual base (mutable)
+                #[inline]
+                pub fn [<as_ $vbase:snake _mut>](&mut self) -> &mut $vbase {
+                    unsafe {
+                        let ptr = (self as *mut Self as *mut u8)
+                            .add(::std::mem::offset_of!(Self, [<vtable_ $vbase:snake>]));
+                        &mut *(ptr as *mut $vbase)
+                    }
+                }
+            }
+        }
+    };
+
     // Multiple inheritance (two bases)
     (
         $(#[$meta:meta])*
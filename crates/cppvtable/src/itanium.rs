@@ -0,0 +1,209 @@
+//! Itanium C++ ABI support (g++, clang)
+//!
+//! The Itanium ABI stores two extra entries in front of every vtable:
+//!
+//! ```text
+//! VTable in memory (Itanium):
+//! ┌─────────────────┐
+//! │ offset_to_top   │  ← slot -2: displacement from this subobject to the complete object
+//! ├─────────────────┤
+//! │ type_info*      │  ← slot -1: std::type_info for the complete object (null without RTTI)
+//! ├─────────────────┤
+//! │ method_0        │  ← slot 0 (vtable pointer points here)
+//! │ method_1        │  ← slot 1
+//! │ ...             │
+//! └─────────────────┘
+//! ```
+//!
+//! For a class with several bases, g++ emits one vtable per base subobject, each with
+//! its own prefix. The secondary vtables carry a negative `offset_to_top` (minus the
+//! offset of that subobject), which is what `dynamic_cast<void*>` reads. They follow the
+//! primary vtable in one vtable group; `#[cppvtable_impl]` emits the same group with
+//! `vtable_group(...)` on the primary impl block and `grouped` on the others.
+//!
+//! `#[cppvtable_impl]` wraps its static vtable in [`ItaniumVTable`] when the Itanium
+//! ABI is selected (explicitly with `abi = "itanium"`, or by default on non-MSVC targets).
+//! Its typeinfo slot is null (or a Rust [`TypeInfo`](crate::rtti::TypeInfo) with `rtti`),
+//! never a `std::type_info`: C++ `typeid` and `dynamic_cast` other than to `void*` are
+//! unsupported on Rust objects and would read through that pointer.
+//!
+//! [`rtti`] reads the `std::type_info` that g++ emits for C++ objects: the dynamic type's
+//! name and its base classes, for `dynamic_cast` to a named base.
+//!
+//! ## Virtual Bases
+//!
+//! A class with virtual bases stores the offset from its subobject to each virtual base
+//! in front of offset-to-top. Casting to a virtual base reads it, see
+//! [`virtual_base_offset`]. An interface whose only base is virtual shares its vtable
+//! pointer with that base (the base is its primary base), so its vtable still begins
+//! with the base's methods, and one vcall offset per virtual function of the base sits
+//! between the vbase offset and offset-to-top. Implementations use [`ItaniumVirtualVTable`].
+//!
+//! ```text
+//! ┌─────────────────┐
+//! │ vbase_offset    │  ← slot -3 - V: displacement from this subobject to the virtual base
+//! ├─────────────────┤
+//! │ vcall_offset    │  ← slots -2 - V to -3: one per virtual function of the base (V)
+//! │ ...             │
+//! ├─────────────────┤
+//! │ offset_to_top   │  ← slot -2
+//! ├─────────────────┤
+//! │ type_info*      │  ← slot -1
+//! ├─────────────────┤
+//! │ method_0        │  ← slot 0 (the virtual base's methods, then the class's own)
+//! │ ...             │
+//! └─────────────────┘
+//! ```
+
+use std::ffi::c_void;
+
+pub mod rtti;
+
+/// A vtable with the Itanium prefix (offset-to-top and typeinfo pointer) before slot 0.
+///
+/// The object's vtable pointer must point at `methods`, see [`vtable_ptr`](Self::vtable_ptr).
+#[repr(C)]
+pub struct ItaniumVTable<T> {
+    /// Displacement from the subobject holding this vtable pointer to the complete object
+    pub offset_to_top: isize,
+    /// `std::type_info*` of the complete object, or null when no RTTI is emitted
+    pub type_info: *const c_void,
+    /// The actual vtable methods (slot 0 onwards)
+    pub methods: T,
+}
+
+// SAFETY: the prefix only holds an offset and a pointer to immutable static data
+unsafe impl<T: Sync> Sync for ItaniumVTable<T> {}
+
+impl<T> ItaniumVTable<T> {
+    /// Create a new vtable with Itanium prefix
+    pub const fn new(offset_to_top: isize, type_info: *const c_void, methods: T) -> Self {
+        Self {
+            offset_to_top,
+            type_info,
+            methods,
+        }
+    }
+
+    /// Get a pointer to the methods (what the object's vtable pointer should store)
+    pub const fn vtable_ptr(&self) -> *const T {
+        &self.methods
+    }
+}
+
+/// A vtable with virtual base and vcall offsets in front of the Itanium prefix.
+///
+/// The object's vtable pointer must point at `methods`, see [`vtable_ptr`](Self::vtable_ptr).
+#[repr(C)]
+pub struct ItaniumVirtualVTable<T, const N: usize, const V: usize> {
+    /// Displacements from this subobject to its virtual bases, last virtual base first,
+    /// so virtual base `i` sits at slot `-3 - V - i`
+    pub vbase_offsets: [isize; N],
+    /// Adjustments to the final overrider for each virtual function of the primary
+    /// virtual base, used by C++ virtual thunks
+    pub vcall_offsets: [isize; V],
+    /// Displacement from the subobject holding this vtable pointer to the complete object
+    pub offset_to_top: isize,
+    /// `std::type_info*` of the complete object, or null when no RTTI is emitted
+    pub type_info: *const c_void,
+    /// The actual vtable methods (slot 0 onwards)
+    pub methods: T,
+}
+
+// SAFETY: the prefix only holds offsets and a pointer to immutable static data
+unsafe impl<T: Sync, const N: usize, const V: usize> Sync for ItaniumVirtualVTable<T, N, V> {}
+
+impl<T, const N: usize, const V: usize> ItaniumVirtualVTable<T, N, V> {
+    /// Create a new vtable with virtual base and vcall offsets and the Itanium prefix
+    pub const fn new(
+        vbase_offsets: [isize; N],
+        vcall_offsets: [isize; V],
+        offset_to_top: isize,
+        type_info: *const c_void,
+        methods: T,
+    ) -> Self {
+        Self {
+            vbase_offsets,
+            vcall_offsets,
+            offset_to_top,
+            type_info,
+            methods,
+        }
+    }
+
+    /// Get a pointer to the methods (what the object's vtable pointer should store)
+    pub const fn vtable_ptr(&self) -> *const T {
+        &self.methods
+    }
+}
+
+/// The Itanium vtable of a struct's interface whose `#[cppvtable_impl]` block says
+/// `grouped`, for the vtable group emitted by the block with `vtable_group(...)`.
+#[doc(hidden)]
+pub trait GroupedVTable<I> {
+    /// [`ItaniumVTable`] or [`ItaniumVirtualVTable`] of the interface's vtable struct
+    type VTable: 'static;
+    /// The vtable, with its prefix for this struct
+    const VTABLE: Self::VTable;
+}
+
+/// Read offset-to-top (slot -2) from the vtable of a polymorphic subobject.
+///
+/// # Safety
+/// - `object` must point to a live object whose first field is a vtable pointer
+///   laid out according to the Itanium ABI
+#[inline]
+pub unsafe fn offset_to_top(object: *const c_void) -> isize {
+    // SAFETY: caller guarantees an Itanium vtable with the two-entry prefix
+    unsafe {
+        let vtable = *(object as *const *const isize);
+        *vtable.offset(-2)
+    }
+}
+
+/// Read the `std::type_info*` (slot -1) from the vtable of a polymorphic subobject.
+///
+/// # Safety
+/// - Same requirements as [`offset_to_top`]
+#[inline]
+pub unsafe fn type_info_ptr(object: *const c_void) -> *const c_void {
+    // SAFETY: caller guarantees an Itanium vtable with the two-entry prefix
+    unsafe {
+        let vtable = *(object as *const *const *const c_void);
+        *vtable.offset(-1)
+    }
+}
+
+/// Adjust a subobject pointer to the start of the complete object.
+///
+/// This is what `dynamic_cast<void*>` does on Itanium targets.
+///
+/// # Safety
+/// - Same requirements as [`offset_to_top`]
+#[inline]
+pub unsafe fn complete_object(object: *const c_void) -> *const c_void {
+    // SAFETY: caller guarantees an Itanium vtable with the two-entry prefix
+    unsafe { (object as *const u8).offset(offset_to_top(object)) as *const c_void }
+}
+
+/// Read the displacement from a subobject to its `index`-th virtual base, found in front
+/// of `vcall_offsets` vcall offsets (slot `-3 - vcall_offsets - index`).
+///
+/// This is what a cast to a virtual base does on Itanium targets.
+///
+/// # Safety
+/// - `object` must point to a live object whose first field is a vtable pointer laid out
+///   according to the Itanium ABI, for a class with more than `index` virtual bases and
+///   `vcall_offsets` vcall offsets
+#[inline]
+pub unsafe fn virtual_base_offset(
+    object: *const c_void,
+    vcall_offsets: usize,
+    index: usize,
+) -> isize {
+    // SAFETY: caller guarantees the vbase and vcall offsets in front of the two-entry prefix
+    unsafe {
+        let vtable = *(object as *const *const isize);
+        *vtable.offset(-3 - vcall_offsets as isize - index as isize)
+    }
+}
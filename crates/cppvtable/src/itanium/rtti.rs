@@ -0,0 +1,284 @@
+//! Reader for the C++ RTTI that g++ and clang emit (Itanium ABI)
+//!
+//! Slot -1 of an Itanium vtable points to the `std::type_info` of the complete object.
+//! For classes it is one of three `__cxxabiv1` types:
+//!
+//! ```text
+//! __class_type_info        vptr, name                            (no bases)
+//! __si_class_type_info     vptr, name, base*                     (one public non-virtual base at offset 0)
+//! __vmi_class_type_info    vptr, name, flags, base_count,
+//!                          { base*, offset_flags } * base_count  (any other bases)
+//! ```
+//!
+//! `offset_flags` holds the base's offset (`>> 8`) and its virtual (`0x1`) and public
+//! (`0x2`) flags. For a virtual base the offset locates the vbase offset in the vtable of
+//! the derived subobject, as with [`super::virtual_base_offset`].
+//!
+//! The names are mangled type names (`7CppDuck`, `N3zoo6ParrotE`), which
+//! `abi::__cxa_demangle` or `c++filt -t` turn back into C++ names:
+//!
+//! ```ignore
+//! let name = unsafe { itanium::rtti::type_name(animal) }; // Some(c"7CppDuck")
+//! let swimmer = unsafe { itanium::rtti::dynamic_cast(animal, c"8ISwimmer") };
+//! ```
+//!
+//! Vtables built by `#[cppvtable_impl]` hold null in slot -1 (or a Rust
+//! [`TypeInfo`](crate::rtti::TypeInfo) with `rtti`), so this only reads C++ objects.
+
+use std::ffi::{CStr, c_char, c_long, c_uint, c_void};
+use std::fmt;
+
+/// `std::type_info`: its own vtable pointer and the mangled name
+#[repr(C)]
+struct RawTypeInfo {
+    vtable: *const *const c_void,
+    name: *const c_char,
+}
+
+/// `__cxxabiv1::__si_class_type_info`
+#[repr(C)]
+struct RawSiClassTypeInfo {
+    type_info: RawTypeInfo,
+    base_type: *const RawTypeInfo,
+}
+
+/// `__cxxabiv1::__vmi_class_type_info`, followed by `base_count` base descriptions
+#[repr(C)]
+struct RawVmiClassTypeInfo {
+    type_info: RawTypeInfo,
+    flags: c_uint,
+    base_count: c_uint,
+}
+
+/// `__cxxabiv1::__base_class_type_info`
+#[repr(C)]
+struct RawBaseClassTypeInfo {
+    base_type: *const RawTypeInfo,
+    offset_flags: c_long,
+}
+
+const VIRTUAL_MASK: c_long = 0x1;
+const PUBLIC_MASK: c_long = 0x2;
+const OFFSET_SHIFT: u32 = 8;
+
+/// Mangled names of the `__cxxabiv1` classes describing classes
+const CLASS_TYPE_INFO: &CStr = c"N10__cxxabiv117__class_type_infoE";
+const SI_CLASS_TYPE_INFO: &CStr = c"N10__cxxabiv120__si_class_type_infoE";
+const VMI_CLASS_TYPE_INFO: &CStr = c"N10__cxxabiv121__vmi_class_type_infoE";
+
+/// Which `std::type_info` subclass describes a type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeInfoKind {
+    /// `__class_type_info`: a class without bases
+    Class,
+    /// `__si_class_type_info`: a class with one public non-virtual base at offset 0
+    SingleInheritance,
+    /// `__vmi_class_type_info`: a class with several, virtual or non-public bases
+    VirtualMultipleInheritance,
+    /// Not a class (fundamental, pointer, ... type)
+    Other,
+}
+
+/// A C++ `std::type_info` emitted by g++ or clang.
+///
+/// Compares by mangled name, like `std::type_info::operator==` across shared libraries.
+#[derive(Clone, Copy)]
+pub struct CxxTypeInfo {
+    raw: *const RawTypeInfo,
+}
+
+/// A direct base class, from a `__vmi_class_type_info` or `__si_class_type_info`
+#[derive(Clone, Copy, Debug)]
+pub struct BaseClass {
+    /// The base's type info
+    pub type_info: CxxTypeInfo,
+    /// Offset of the base subobject for a non-virtual base; for a virtual base, offset
+    /// in the derived subobject's vtable of the entry holding the vbase offset
+    pub offset: isize,
+    /// Whether the base is virtual
+    pub is_virtual: bool,
+    /// Whether the base is public (only public bases are `dynamic_cast` targets)
+    pub is_public: bool,
+}
+
+impl CxxTypeInfo {
+    /// Wrap a `std::type_info*`, `None` if null.
+    ///
+    /// # Safety
+    /// - `type_info` must be null or point to a `std::type_info` emitted by an Itanium
+    ///   C++ compiler, valid as long as this value is used
+    #[must_use]
+    pub unsafe fn from_ptr(type_info: *const c_void) -> Option<Self> {
+        (!type_info.is_null()).then_some(Self {
+            raw: type_info as *const RawTypeInfo,
+        })
+    }
+
+    /// The `std::type_info*`
+    #[must_use]
+    pub fn as_ptr(&self) -> *const c_void {
+        self.raw as *const c_void
+    }
+
+    /// The mangled type name, e.g. `7CppDuck` for `CppDuck` or `N3zoo6ParrotE` for
+    /// `zoo::Parrot`, without the `*` marking types local to a library
+    #[must_use]
+    pub fn name(&self) -> &'static CStr {
+        // SAFETY: `from_ptr` requires a valid type_info, whose name is a static string
+        let name = unsafe { CStr::from_ptr((*self.raw).name) };
+        let bytes = name.to_bytes_with_nul();
+        match bytes.strip_prefix(b"*") {
+            // SAFETY: the rest of a nul-terminated string is nul-terminated
+            Some(rest) => unsafe { CStr::from_bytes_with_nul_unchecked(rest) },
+            None => name,
+        }
+    }
+
+    /// Which `std::type_info` subclass this is, read from its own RTTI
+    #[must_use]
+    pub fn kind(&self) -> TypeInfoKind {
+        // SAFETY: a type_info is polymorphic, so its vtable has the Itanium prefix
+        let meta = unsafe { Self::from_ptr(*(*self.raw).vtable.offset(-1)) };
+        match meta.map(|meta| meta.name()) {
+            Some(name) if name == CLASS_TYPE_INFO => TypeInfoKind::Class,
+            Some(name) if name == SI_CLASS_TYPE_INFO => TypeInfoKind::SingleInheritance,
+            Some(name) if name == VMI_CLASS_TYPE_INFO => TypeInfoKind::VirtualMultipleInheritance,
+            _ => TypeInfoKind::Other,
+        }
+    }
+
+    /// The direct base classes, in declaration order
+    #[must_use]
+    pub fn bases(&self) -> Vec<BaseClass> {
+        match self.kind() {
+            TypeInfoKind::SingleInheritance => {
+                // SAFETY: `kind` identified an __si_class_type_info
+                let base = unsafe { (*(self.raw as *const RawSiClassTypeInfo)).base_type };
+                vec![BaseClass {
+                    type_info: Self { raw: base },
+                    offset: 0,
+                    is_virtual: false,
+                    is_public: true,
+                }]
+            }
+            TypeInfoKind::VirtualMultipleInheritance => {
+                // SAFETY: `kind` identified a __vmi_class_type_info, whose base
+                // descriptions follow it
+                unsafe {
+                    let vmi = self.raw as *const RawVmiClassTypeInfo;
+                    let bases = vmi.add(1) as *const RawBaseClassTypeInfo;
+                    (0..(*vmi).base_count as usize)
+                        .map(|i| {
+                            let base = &*bases.add(i);
+                            BaseClass {
+                                type_info: Self {
+                                    raw: base.base_type,
+                                },
+                                offset: (base.offset_flags >> OFFSET_SHIFT) as isize,
+                                is_virtual: base.offset_flags & VIRTUAL_MASK != 0,
+                                is_public: base.offset_flags & PUBLIC_MASK != 0,
+                            }
+                        })
+                        .collect()
+                }
+            }
+            TypeInfoKind::Class | TypeInfoKind::Other => Vec::new(),
+        }
+    }
+}
+
+impl PartialEq for CxxTypeInfo {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.raw, other.raw) || self.name() == other.name()
+    }
+}
+
+impl Eq for CxxTypeInfo {}
+
+impl fmt::Debug for CxxTypeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CxxTypeInfo").field(&self.name()).finish()
+    }
+}
+
+/// The `std::type_info` of a C++ object's dynamic type, from slot -1 of its vtable.
+///
+/// `None` when the vtable carries no type info (code built with `-fno-rtti`).
+///
+/// # Safety
+/// - `object` must point to a live polymorphic subobject of a class compiled by an
+///   Itanium C++ compiler
+#[must_use]
+pub unsafe fn type_info_of(object: *const c_void) -> Option<CxxTypeInfo> {
+    // SAFETY: caller guarantees a C++ vtable, whose slot -1 is null or a type_info
+    unsafe { CxxTypeInfo::from_ptr(super::type_info_ptr(object)) }
+}
+
+/// The mangled name of a C++ object's dynamic type, like `typeid(*object).name()`.
+///
+/// # Safety
+/// - Same requirements as [`type_info_of`]
+#[must_use]
+pub unsafe fn type_name(object: *const c_void) -> Option<&'static CStr> {
+    // SAFETY: forwarded to the caller
+    unsafe { type_info_of(object) }.map(|type_info| type_info.name())
+}
+
+/// `dynamic_cast` to the base class with mangled name `base`: the address of the unique
+/// public `base` subobject of the complete object, or null when there is none or it is
+/// ambiguous. Works from any subobject, so it also cross-casts between sibling bases.
+///
+/// # Safety
+/// - Same requirements as [`type_info_of`]
+#[must_use = "dynamic_cast returns the adjusted pointer; discarding it is likely a bug"]
+pub unsafe fn dynamic_cast(object: *const c_void, base: &CStr) -> *const c_void {
+    // SAFETY: caller guarantees a C++ vtable with the Itanium prefix
+    let Some(type_info) = (unsafe { type_info_of(object) }) else {
+        return std::ptr::null();
+    };
+    let complete = unsafe { super::complete_object(object) };
+
+    let mut found = None;
+    // SAFETY: `complete` is an object of type `type_info`
+    let unique = unsafe { find_base(type_info, complete, base, &mut found) };
+    match found {
+        Some(subobject) if unique => subobject,
+        _ => std::ptr::null(),
+    }
+}
+
+/// Look for the `base` subobjects of `object`, of type `type_info`, reachable through
+/// public bases. Returns false once two different ones were found.
+///
+/// # Safety
+/// - `object` must point to a live C++ object of type `type_info`
+unsafe fn find_base(
+    type_info: CxxTypeInfo,
+    object: *const c_void,
+    base: &CStr,
+    found: &mut Option<*const c_void>,
+) -> bool {
+    if type_info.name() == base {
+        match *found {
+            Some(previous) if previous != object => return false,
+            _ => *found = Some(object),
+        }
+    }
+    for class in type_info.bases().iter().filter(|class| class.is_public) {
+        // SAFETY: the offsets come from the type_info of the object's class; a class with
+        // virtual bases has a vtable pointer
+        let subobject = unsafe {
+            if class.is_virtual {
+                let vtable = *(object as *const *const u8);
+                let vbase_offset = *(vtable.offset(class.offset) as *const isize);
+                object.byte_offset(vbase_offset)
+            } else {
+                object.byte_offset(class.offset)
+            }
+        };
+        if !unsafe { find_base(class.type_info, subobject, base, found) } {
+            return false;
+        }
+    }
+    true
+}
@@ -1,6 +1,8 @@
-//! C++ VTable interop for Rust (MSVC ABI)
+//! C++ VTable interop for Rust (MSVC and Itanium ABIs)
 //!
 //! This crate provides C++ compatible vtable layouts with optional RTTI support.
+//! The MSVC layout is used on `*-msvc` targets and the Itanium layout (g++/clang)
+//! everywhere else; either can be forced with `abi = "msvc"` / `abi = "itanium"`.
 //!
 //! ## RTTI (Runtime Type Information)
 //!
@@ -57,16 +59,55 @@
 //! | Feature | Declarative | Proc-macro |
 //! |---------|-------------|------------|
 //! | Slot indices `[N]` / `#[slot(N)]` | ✅ | ✅ |
-//! | thiscall (x86) | ✅ | ✅ |
+//! | thiscall/cdecl (x86) | ✅ | ✅ |
 //! | Clean Rust syntax | ❌ | ✅ |
 //! | No separate crate | ✅ | N/A |
 //! | RTTI support | ✅ | ✅ |
 //! | Multiple inheritance | ✅ | ✅ |
 
+pub mod borrow;
+pub mod boxed;
 pub mod com;
 pub mod decl;
+pub mod itanium;
+pub mod msvc;
+pub mod owned;
+pub mod panic;
 pub mod rtti;
 
+// =============================================================================
+// CppAbi - Which C++ compiler ABI a vtable follows
+// =============================================================================
+
+/// The C++ ABI a vtable layout follows.
+///
+/// Selected per interface with `#[cppvtable(abi = "...")]`, which implementations follow
+/// unless they repeat it with `#[cppvtable_impl(IFoo, abi = "...")]`. Without the option,
+/// [`CppAbi::NATIVE`] is used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CppAbi {
+    /// Microsoft Visual C++ layout
+    Msvc,
+    /// Itanium C++ ABI layout (g++, clang on non-MSVC targets)
+    Itanium,
+}
+
+impl CppAbi {
+    /// The ABI used by the target's C++ compiler: MSVC on `*-msvc` targets, Itanium elsewhere.
+    #[cfg(target_env = "msvc")]
+    pub const NATIVE: CppAbi = CppAbi::Msvc;
+
+    /// The ABI used by the target's C++ compiler: MSVC on `*-msvc` targets, Itanium elsewhere.
+    #[cfg(not(target_env = "msvc"))]
+    pub const NATIVE: CppAbi = CppAbi::Itanium;
+
+    /// Const-compatible equality check
+    #[must_use]
+    pub const fn same_as(self, other: CppAbi) -> bool {
+        self as u8 == other as u8
+    }
+}
+
 // =============================================================================
 // VTableLayout - Trait for interface inheritance
 // =============================================================================
@@ -94,10 +135,163 @@ pub trait VTableLayout {
     /// The number of vtable slots used by this interface (including inherited slots).
     const SLOT_COUNT: usize;
 
+    /// The number of virtual functions, which is the slot count except that the Itanium
+    /// destructor pair (D1 and D0) counts once. An Itanium class with this interface as
+    /// its virtual primary base has one vcall offset per function.
+    const FUNCTION_COUNT: usize = Self::SLOT_COUNT;
+
+    /// The C++ ABI this interface's vtable layout follows.
+    const ABI: CppAbi = CppAbi::NATIVE;
+
+    /// What the generated entry points of this interface do when a method panics.
+    const PANIC_POLICY: PanicPolicy = PanicPolicy::Abort;
+
     /// The vtable struct type for this interface.
     type VTable;
 }
 
+// =============================================================================
+// Implementation checks - compile-time errors for #[cppvtable_impl]
+// =============================================================================
+
+/// Fails const evaluation, naming every method of `interface` (`declared`) that
+/// `implementation` doesn't define in `implemented`.
+///
+/// `#[cppvtable_impl]` calls this from a `const _` item, so an incomplete impl is a
+/// compile error instead of a vtable entry that panics when called:
+///
+/// ```compile_fail,E0080
+/// use cppvtable::proc::{cppvtable, cppvtable_impl};
+///
+/// #[cppvtable]
+/// pub trait IAnimal {
+///     fn speak(&self) -> i32;
+///     fn legs(&self) -> i32;
+/// }
+///
+/// #[repr(C)]
+/// pub struct Dog {
+///     vtable_i_animal: *const IAnimalVTable,
+/// }
+///
+/// // error: cppvtable_impl: Dog is missing methods of IAnimal: legs
+/// #[cppvtable_impl(IAnimal)]
+/// impl Dog {
+///     fn speak(&self) -> i32 {
+///         1
+///     }
+/// }
+/// ```
+///
+/// A method the interface doesn't declare is reported on its name, next to the lookups
+/// of the interface items it would need:
+///
+/// ```compile_fail,E0080
+/// use cppvtable::proc::{cppvtable, cppvtable_impl};
+///
+/// #[cppvtable]
+/// pub trait IAnimal {
+///     fn speak(&self) -> i32;
+/// }
+///
+/// #[repr(C)]
+/// pub struct Dog {
+///     vtable_i_animal: *const IAnimalVTable,
+/// }
+///
+/// #[cppvtable_impl(IAnimal)]
+/// impl Dog {
+///     fn speak(&self) -> i32 {
+///         1
+///     }
+///     // error: cppvtable_impl: 'bark' is not a method of IAnimal
+///     fn bark(&self) -> i32 {
+///         2
+///     }
+/// }
+/// ```
+#[doc(hidden)]
+pub const fn check_impl_methods(
+    implementation: &str,
+    interface: &str,
+    declared: &[&str],
+    implemented: &[&str],
+) {
+    const CAPACITY: usize = 512;
+    let mut message = [0u8; CAPACITY];
+    let mut len = 0;
+    let mut missing = 0;
+    let mut i = 0;
+    while i < declared.len() {
+        if !declares_method(implemented, declared[i]) {
+            if missing == 0 {
+                len = append(&mut message, len, "cppvtable_impl: ");
+                len = append(&mut message, len, implementation);
+                len = append(&mut message, len, " is missing methods of ");
+                len = append(&mut message, len, interface);
+                len = append(&mut message, len, ": ");
+            } else {
+                len = append(&mut message, len, ", ");
+            }
+            len = append(&mut message, len, declared[i]);
+            missing += 1;
+        }
+        i += 1;
+    }
+    if missing > 0 {
+        let (message, _) = message.split_at(len);
+        let message = match std::str::from_utf8(message) {
+            Ok(message) => message,
+            // A long list cut inside a character
+            Err(error) => match std::str::from_utf8(message.split_at(error.valid_up_to()).0) {
+                Ok(message) => message,
+                Err(_) => "",
+            },
+        };
+        panic!("{}", message);
+    }
+}
+
+/// Whether `name` is one of `names`, an interface's `__METHODS`
+#[doc(hidden)]
+pub const fn declares_method(names: &[&str], name: &str) -> bool {
+    let mut i = 0;
+    while i < names.len() {
+        if bytes_eq(names[i].as_bytes(), name.as_bytes()) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Const byte slice equality
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Append `text` to `buffer` at `len`, as far as it fits; returns the new length
+const fn append<const N: usize>(buffer: &mut [u8; N], mut len: usize, text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() && len < N {
+        buffer[len] = bytes[i];
+        len += 1;
+        i += 1;
+    }
+    len
+}
+
 /// Proc-macro approach - re-exports from cppvtable-macro crate
 pub mod proc {
     pub use cppvtable_macro::{com_implement, com_interface};
@@ -114,6 +308,11 @@ pub use std::ffi::c_void;
 #[doc(hidden)]
 pub use std::sync::atomic::{Ordering, compiler_fence};
 
+pub use borrow::BorrowFlag;
+pub use boxed::{CppBox, CppClass, Implements};
+pub use owned::{CppOwned, OwnedView, VirtualDestructor};
+pub use panic::PanicPolicy;
+
 // Re-export RTTI types for macro-generated code
 #[doc(hidden)]
 pub use rtti::{InterfaceInfo, TypeInfo};
@@ -121,6 +320,7 @@ pub use rtti::{InterfaceInfo, TypeInfo};
 // Re-export COM types for macro-generated code
 #[doc(hidden)]
 pub use com::{
-    ComRefCount, E_NOINTERFACE, E_POINTER, GUID, HRESULT, IID_IUNKNOWN, IUnknown, IUnknownVTable,
-    S_OK, make_guid,
+    ComHeap, ComInterface, ComRefCount, E_NOINTERFACE, E_POINTER, E_UNEXPECTED, GUID, HRESULT,
+    IID_IUNKNOWN, IUnknown, IUnknownSysv64, IUnknownSysv64VTable, IUnknownVTable, IUnknownWin64,
+    IUnknownWin64VTable, S_OK, make_guid,
 };
@@ -0,0 +1,46 @@
+//! MSVC C++ ABI support
+//!
+//! MSVC keeps virtual bases out of the vtable. A class with virtual bases gets a virtual
+//! base pointer (vbptr) right after its vtable pointer, or first when the class adds no
+//! virtual functions of its own. The vbptr points at a vbtable of 32-bit offsets, and
+//! the virtual bases themselves are placed at the end of the complete object:
+//!
+//! ```text
+//! Object (class X : public virtual IBase)       vbtable
+//! ┌─────────────────┐                         ┌──────────────────────────┐
+//! │ vfptr (X)       │                         │ vbptr → start of X (-8)  │  ← entry 0
+//! ├─────────────────┤                         ├──────────────────────────┤
+//! │ vbptr           │ ──────────────────────→ │ vbptr → IBase            │  ← entry 1
+//! ├─────────────────┤                         └──────────────────────────┘
+//! │ X's fields      │
+//! ├─────────────────┤
+//! │ vfptr (IBase)   │  ← virtual base subobject
+//! └─────────────────┘
+//! ```
+//!
+//! Casting to a virtual base reads the vbtable, see [`virtual_base_offset`].
+//! `#[cppvtable_impl(I, extends(virtual IBase))]` emits the vbtable for a Rust object.
+
+use std::ffi::c_void;
+
+/// Read the displacement from a subobject to one of its virtual bases.
+///
+/// `vbptr_offset` is where the subobject keeps its vbptr (the pointer size when it has
+/// a vtable pointer of its own, else 0), and `index` the virtual base's vbtable entry,
+/// starting at 1.
+///
+/// # Safety
+/// - `object` must point to a live object with a vbptr at `vbptr_offset`, whose vbtable
+///   has more than `index` entries
+#[inline]
+pub unsafe fn virtual_base_offset(
+    object: *const c_void,
+    vbptr_offset: isize,
+    index: usize,
+) -> isize {
+    // SAFETY: caller guarantees the vbptr and the vbtable entry
+    unsafe {
+        let vbptr = (object as *const u8).offset(vbptr_offset) as *const *const i32;
+        vbptr_offset + *(*vbptr).add(index) as isize
+    }
+}
@@ -0,0 +1,150 @@
+//! Owning handles for objects created by C++
+//!
+//! `IAnimal::from_ptr_mut` borrows a C++ object for an unbounded lifetime and leaves
+//! freeing it to the caller. [`CppOwned`] owns the object instead, and `delete`s it
+//! through the interface's virtual destructor when dropped. As long as it is alive the
+//! object's methods are safe to call: it derefs to the interface's [`OwnedView`]
+//! (`IAnimalOwned`), whose methods forward to the wrapper's `unsafe` ones.
+//!
+//! ```ignore
+//! let dog = unsafe { CppOwned::<IAnimal>::from_raw(create_cpp_dog("Rex")) };
+//! dog.speak();
+//! // dropping `dog` calls the deleting destructor (MSVC flags 1, Itanium D0)
+//! ```
+//!
+//! Methods taking raw pointers stay `unsafe`, for their arguments. The view of an
+//! interface declared with `extends(IBase)` derefs to the view of `IBase`.
+//!
+//! The view's methods take `&self`, and the handle hands out no safe `&mut` to the view
+//! or the wrapper: both are only the vtable pointer, and swapping two of them would make
+//! the drop delete each object through the other one's destructor.
+
+use std::ffi::c_void;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr::NonNull;
+
+/// The view of an interface that [`CppOwned`] derefs to.
+///
+/// Implemented by `#[cppvtable]` for every interface, with a `{Name}Owned` struct whose
+/// methods call the interface's methods without `unsafe`.
+///
+/// # Safety
+/// `View` must be `#[repr(transparent)]` over `Self`, and its methods may only rely on
+/// the object being alive.
+pub unsafe trait OwnedView {
+    /// The `{Name}Owned` view
+    type View;
+}
+
+/// An interface with a virtual destructor.
+///
+/// Implemented by `#[cppvtable]` for interfaces with a `#[destructor]` method, and for
+/// interfaces extending one.
+///
+/// # Safety
+/// `delete` must destroy and free the object the way C++ `delete` would.
+pub unsafe trait VirtualDestructor: OwnedView {
+    /// Destroy and free the object like C++ `delete`: the MSVC scalar deleting
+    /// destructor with flags 1, or the Itanium D0 destructor.
+    ///
+    /// # Safety
+    /// `this` must point to a live heap object implementing the interface, which must
+    /// not be used afterwards.
+    unsafe fn delete(this: *mut Self);
+}
+
+/// An owned pointer to a C++ object, deleted through its virtual destructor on drop.
+pub struct CppOwned<I: VirtualDestructor> {
+    ptr: NonNull<I>,
+    _owned: PhantomData<I>,
+}
+
+impl<I: VirtualDestructor> CppOwned<I> {
+    /// Take ownership of an interface pointer returned by C++.
+    ///
+    /// # Safety
+    /// - `ptr` must be a non-null pointer to a live object implementing `I`, allocated the
+    ///   way its deleting destructor frees it (usually C++ `new`)
+    /// - Nothing else may use or free the object while the handle owns it
+    pub unsafe fn from_raw(ptr: *mut c_void) -> Self {
+        Self {
+            // SAFETY: caller guarantees a non-null pointer
+            ptr: unsafe { NonNull::new_unchecked(ptr as *mut I) },
+            _owned: PhantomData,
+        }
+    }
+
+    /// Interface pointer, to pass to C++ while the handle keeps ownership
+    #[must_use]
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr.as_ptr() as *mut c_void
+    }
+
+    /// The interface wrapper, for its `unsafe` methods and helpers
+    #[must_use]
+    pub fn as_interface(&self) -> &I {
+        // SAFETY: the handle owns a live object
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// The interface wrapper, mutably
+    ///
+    /// # Safety
+    /// The caller must not overwrite the vtable pointer through the reference, by
+    /// assignment or by swapping it with another wrapper: the drop would then `delete`
+    /// the object through the wrong destructor.
+    ///
+    /// ```compile_fail,E0133
+    /// # use cppvtable::{CppOwned, VirtualDestructor};
+    /// # fn swap<I: VirtualDestructor>(a: &mut CppOwned<I>, b: &mut CppOwned<I>) {
+    /// std::mem::swap(a.as_interface_mut(), b.as_interface_mut());
+    /// # }
+    /// ```
+    ///
+    /// The views have no `DerefMut` either:
+    ///
+    /// ```compile_fail,E0596
+    /// # use cppvtable::{CppOwned, VirtualDestructor};
+    /// # fn swap<I: VirtualDestructor>(a: &mut CppOwned<I>, b: &mut CppOwned<I>) {
+    /// std::mem::swap(&mut **a, &mut **b);
+    /// # }
+    /// ```
+    #[must_use]
+    pub unsafe fn as_interface_mut(&mut self) -> &mut I {
+        // SAFETY: the handle owns a live object, borrowed uniquely through &mut self, and
+        // the caller leaves the vtable pointer in place
+        unsafe { self.ptr.as_mut() }
+    }
+
+    /// Give up ownership without deleting the object
+    #[must_use = "the object leaks unless it is deleted elsewhere"]
+    pub fn into_raw(this: Self) -> *mut c_void {
+        let ptr = this.as_ptr();
+        std::mem::forget(this);
+        ptr
+    }
+}
+
+impl<I: VirtualDestructor> Deref for CppOwned<I> {
+    type Target = I::View;
+
+    fn deref(&self) -> &I::View {
+        // SAFETY: the handle owns a live object, and the view is transparent over `I`
+        unsafe { &*(self.ptr.as_ptr() as *const I::View) }
+    }
+}
+
+impl<I: VirtualDestructor> Drop for CppOwned<I> {
+    fn drop(&mut self) {
+        // SAFETY: the handle owns the object, and nothing uses it afterwards
+        unsafe { I::delete(self.ptr.as_ptr()) }
+    }
+}
+
+impl<I: VirtualDestructor> fmt::Debug for CppOwned<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CppOwned").field(&self.ptr).finish()
+    }
+}
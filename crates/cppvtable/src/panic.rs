@@ -0,0 +1,121 @@
+//! Panic containment for generated FFI entry points
+//!
+//! Vtable slots are `extern` functions called by C++, so a panic must not unwind out of
+//! them. Every generated wrapper runs the Rust method under [`catch_unwind`](std::panic::catch_unwind)
+//! and, if it panics, records the payload and applies the interface's policy:
+//!
+//! ```ignore
+//! #[cppvtable(panic = "default")]            // return Default::default()
+//! pub trait ICounter { fn next(&mut self) -> i32; }
+//!
+//! #[com_interface("...", panic = "hresult")] // HRESULT methods return E_UNEXPECTED
+//! pub trait IParser { fn parse(&self, text: *const u8) -> HRESULT; }
+//! ```
+//!
+//! The default policy is `"abort"`. The payload of the last contained panic stays on the
+//! thread that called the entry point, for [`take_last_panic`]:
+//!
+//! ```ignore
+//! if parser.parse(text) == E_UNEXPECTED {
+//!     let panic = cppvtable::panic::take_last_panic().unwrap();
+//!     eprintln!("{} panicked: {:?}", panic.location(), panic.message());
+//! }
+//! ```
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+/// What a generated entry point does when the Rust method it calls panics.
+///
+/// Selected per interface with `#[cppvtable(panic = "...")]` or
+/// `#[com_interface("...", panic = "...")]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PanicPolicy {
+    /// Print the panic and abort the process (`panic = "abort"`)
+    #[default]
+    Abort,
+    /// Return `Default::default()` (`panic = "default"`)
+    Default,
+    /// Return `E_UNEXPECTED` from methods returning `HRESULT`, abort in the others
+    /// (`panic = "hresult"`)
+    HResult,
+}
+
+/// A panic caught at an FFI entry point.
+pub struct FfiPanic {
+    location: &'static str,
+    payload: Box<dyn Any + Send>,
+}
+
+impl FfiPanic {
+    /// The entry point that panicked, as `Interface::method`
+    #[must_use]
+    pub fn location(&self) -> &'static str {
+        self.location
+    }
+
+    /// The panic message, when the payload is a string (as with `panic!`)
+    #[must_use]
+    pub fn message(&self) -> Option<&str> {
+        if let Some(message) = self.payload.downcast_ref::<&'static str>() {
+            Some(message)
+        } else {
+            self.payload.downcast_ref::<String>().map(String::as_str)
+        }
+    }
+
+    /// The payload, e.g. to continue unwinding with [`std::panic::resume_unwind`]
+    #[must_use]
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.payload
+    }
+}
+
+impl fmt::Debug for FfiPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FfiPanic")
+            .field("location", &self.location)
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+thread_local! {
+    static LAST_PANIC: RefCell<Option<FfiPanic>> = const { RefCell::new(None) };
+}
+
+/// Take the last panic contained on this thread, leaving none.
+///
+/// A later panic replaces one that was never taken.
+#[must_use]
+pub fn take_last_panic() -> Option<FfiPanic> {
+    LAST_PANIC.with(|last| last.borrow_mut().take())
+}
+
+/// Run `f`, recording its panic (if any) as the thread's last panic.
+///
+/// Returns `None` when `f` panicked; the caller then applies its policy.
+#[doc(hidden)]
+pub fn catch<R>(location: &'static str, f: impl FnOnce() -> R) -> Option<R> {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => Some(value),
+        Err(payload) => {
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(FfiPanic { location, payload }));
+            None
+        }
+    }
+}
+
+/// The `Abort` policy: report the panic recorded by [`catch`] and abort.
+#[doc(hidden)]
+pub fn abort(location: &'static str) -> ! {
+    let message = take_last_panic();
+    let message = message
+        .as_ref()
+        .and_then(FfiPanic::message)
+        .unwrap_or("Box<dyn Any>");
+    eprintln!("cppvtable: panic in {location} can't unwind into the caller, aborting: {message}");
+    std::process::abort()
+}
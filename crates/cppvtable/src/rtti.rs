@@ -3,17 +3,13 @@
 //! This module provides **Rust-side RTTI** for runtime interface casting.
 //! This is completely separate from C++ RTTI and does not interoperate with it.
 //!
-//! ## Important: No C++ RTTI Support
+//! ## C++ RTTI
 //!
-//! This crate does **not** support C++ native RTTI (`dynamic_cast`, `typeid`).
-//! C++ RTTI uses complex ABI-specific structures (MSVC's `_RTTICompleteObjectLocator`,
-//! Itanium's `__class_type_info`) stored at vtable slot -1. Parsing these would require:
-//! - ABI-specific code for MSVC vs GCC/Clang
-//! - Walking complex class hierarchy descriptors
-//! - Handling virtual inheritance offsets
-//!
-//! If you need to cast C++ objects at runtime, the C++ code should expose its own
-//! casting mechanism (like COM's `QueryInterface`).
+//! C++ objects carry their compiler's RTTI at vtable slot -1 instead (MSVC's
+//! `_RTTICompleteObjectLocator`, Itanium's `__class_type_info`). For g++ and clang
+//! objects, [`crate::itanium::rtti`] reads it: the dynamic type's name and
+//! `dynamic_cast` to a named base. MSVC's RTTI isn't supported; there the C++ code
+//! should expose its own casting mechanism (like COM's `QueryInterface`).
 //!
 //! ## What This Module Provides
 //!
@@ -27,6 +23,7 @@
 //! ```text
 //! VTable in memory (with Rust RTTI):
 //! ┌─────────────────┐
+//! │ offset_to_top   │  ← slot -2: displacement from this vtable pointer to the object start
 //! │ TypeInfo*       │  ← slot -1 (offset -8 on x64, -4 on x86)
 //! ├─────────────────┤
 //! │ method_0        │  ← slot 0 (vtable pointer points here)
@@ -36,36 +33,123 @@
 //! ```
 //!
 //! The object's vtable pointer points to slot 0. To access TypeInfo,
-//! we read the pointer at offset -1.
+//! we read the pointer at offset -1. Slot -2 matches the Itanium prefix, so casts from
+//! any interface pointer find the object start the same way on both ABIs.
+//!
+//! `#[cppvtable_impl(IFoo, rtti)]` emits the vtable this way: inside [`VTableWithRtti`]
+//! for the MSVC ABI, and with the `TypeInfo` in the typeinfo slot of the Itanium prefix.
+//! One impl block per struct lists all its interfaces, `rtti(IFoo, IBar)`, which builds
+//! the struct's [`TypeInfo`] and implements [`HasTypeInfo`]:
+//!
+//! ```ignore
+//! #[cppvtable_impl(ISwimmer, rtti(ISwimmer, IFlyer))]
+//! impl Duck { /* ... */ }
+//!
+//! #[cppvtable_impl(IFlyer, rtti)]
+//! impl Duck { /* ... */ }
+//!
+//! // `duck` starts with the ISwimmer vtable pointer
+//! let flyer = unsafe { type_info_of(duck).cast_to(duck, IFlyer::interface_id_ptr()) };
+//! ```
+//!
+//! The interface wrappers do the same with types: `query::<I>()` returns the object's
+//! interface `I`, from any of its interfaces, and `implements::<I>()` checks for it:
+//!
+//! ```ignore
+//! let swimmer: &mut ISwimmer = unsafe { ISwimmer::from_ptr_mut(duck) };
+//! if let Some(flyer) = unsafe { swimmer.query_mut::<IFlyer>() } {
+//!     flyer.fly();
+//! }
+//! ```
+//!
+//! The Itanium typeinfo slot then holds a Rust `TypeInfo` rather than a
+//! `std::type_info`, so C++ `typeid`/`dynamic_cast` must not be used on these objects.
+//!
+//! ## Derived Interfaces
+//!
+//! An `IDerived` declared with `extends(IBase)` shares its vtable pointer with `IBase`.
+//! The generated [`TypeInfo`] lists each interface of `rtti(...)` followed by the bases
+//! in its [`InterfaceChain`], at the same offset, so is-a checks and casts to `IBase` work
+//! by ID. [`TypeInfo::hierarchy`] groups them by vtable pointer. A virtual base isn't in
+//! the chain: list it in `rtti(...)` with its own vtable field.
+//!
+//! ## Stable Interface IDs
+//!
+//! An interface ID is the address of a static by default, so every library containing
+//! the interface has its own. `#[cppvtable(stable_id)]` and `#[cppvtable(id = "...")]`
+//! use [`stable_interface_id`] instead, a 128-bit hash that is the same in a plugin and
+//! its host. [`InterfaceId`] keeps the two kinds apart, so a hash never passes for an
+//! address, and [`InterfaceInfo`] and [`TypeInfo`] compare by value.
+//!
+//! ## Virtual Bases
+//!
+//! A virtual base is listed once in [`TypeInfo::interfaces`], however many interfaces
+//! derive from it. Its offset is fixed within the complete Rust object, so
+//! [`cast_to()`](TypeInfo::cast_to) needs no vbtable or vbase offset lookup.
 
 use std::ffi::c_void;
 
+/// The RTTI identity of an interface
+///
+/// Compares by value: addresses are equal for the same static, stable IDs for the same
+/// hash in whichever library they were computed.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InterfaceId {
+    /// Address of a static marker, different in every copy of the interface's code
+    Address(*const u8),
+    /// 128-bit hash from [`stable_interface_id`], the same in every library
+    Stable(u128),
+}
+
+// SAFETY: the address is only compared, never dereferenced
+unsafe impl Send for InterfaceId {}
+unsafe impl Sync for InterfaceId {}
+
+impl std::fmt::Debug for InterfaceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Address(address) => write!(f, "Address({:#x})", address.addr()),
+            Self::Stable(hash) => write!(f, "Stable({:#034x})", hash),
+        }
+    }
+}
+
+impl From<*const u8> for InterfaceId {
+    fn from(address: *const u8) -> Self {
+        Self::Address(address)
+    }
+}
+
 /// Information about a single interface implementation
+///
+/// Compares by value: the same interface at the same offset, which holds across
+/// libraries when the interface uses a stable ID.
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct InterfaceInfo {
-    /// Unique identifier for the interface (address of a static marker)
-    pub interface_id: *const u8,
+    /// Unique identifier for the interface
+    pub interface_id: InterfaceId,
     /// Byte offset from object start to this interface's vtable pointer
     pub offset: isize,
 }
 
-// SAFETY: InterfaceInfo only contains a pointer to a static and an offset
-unsafe impl Send for InterfaceInfo {}
-unsafe impl Sync for InterfaceInfo {}
-
 impl std::fmt::Debug for InterfaceInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("InterfaceInfo")
-            .field("interface_id", &(self.interface_id as usize))
+            .field("interface_id", &self.interface_id)
             .field("offset", &self.offset)
             .finish()
     }
 }
 
 impl InterfaceInfo {
-    /// Create a new InterfaceInfo
+    /// Create a new InterfaceInfo for a pointer-based interface ID
     pub const fn new(interface_id: *const u8, offset: isize) -> Self {
+        Self::with_id(InterfaceId::Address(interface_id), offset)
+    }
+
+    /// Create a new InterfaceInfo for any interface ID
+    pub const fn with_id(interface_id: InterfaceId, offset: isize) -> Self {
         Self {
             interface_id,
             offset,
@@ -74,11 +158,14 @@ impl InterfaceInfo {
 }
 
 /// Runtime type information for a concrete class
+///
+/// Compares by value, so the `TypeInfo` of one struct is equal in every library that
+/// contains it when its interfaces use stable IDs.
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct TypeInfo {
     /// Unique identifier for this concrete type
-    pub type_id: usize,
+    pub type_id: u128,
     /// Human-readable type name (for debugging)
     pub type_name: &'static str,
     /// List of implemented interfaces with their offsets
@@ -88,7 +175,7 @@ pub struct TypeInfo {
 impl TypeInfo {
     /// Create a new TypeInfo
     pub const fn new(
-        type_id: usize,
+        type_id: u128,
         type_name: &'static str,
         interfaces: &'static [InterfaceInfo],
     ) -> Self {
@@ -107,10 +194,11 @@ impl TypeInfo {
     pub unsafe fn cast_to(
         &self,
         object_ptr: *const c_void,
-        interface_id: *const u8,
+        interface_id: impl Into<InterfaceId>,
     ) -> *const c_void {
+        let interface_id = interface_id.into();
         for info in self.interfaces {
-            if std::ptr::eq(info.interface_id, interface_id) {
+            if info.interface_id == interface_id {
                 // SAFETY: Caller guarantees object_ptr is valid and offset is correct for this type
                 return unsafe { (object_ptr as *const u8).offset(info.offset) as *const c_void };
             }
@@ -120,17 +208,66 @@ impl TypeInfo {
 
     /// Check if this type implements a given interface
     #[must_use]
-    pub fn implements(&self, interface_id: *const u8) -> bool {
+    pub fn implements(&self, interface_id: impl Into<InterfaceId>) -> bool {
+        let interface_id = interface_id.into();
+        self.interfaces
+            .iter()
+            .any(|i| i.interface_id == interface_id)
+    }
+
+    /// The interfaces grouped by vtable pointer: each group is one interface and the bases
+    /// it extends, most derived first (as listed by `rtti(...)`).
+    pub fn hierarchy(&self) -> impl Iterator<Item = &'static [InterfaceInfo]> {
+        self.interfaces.chunk_by(|a, b| a.offset == b.offset)
+    }
+
+    /// Byte offset of an interface's vtable pointer from the object start, if implemented.
+    ///
+    /// An interface reached through two `extends` chains is listed at both offsets; this
+    /// returns the first.
+    #[must_use]
+    pub fn offset_of(&self, interface_id: impl Into<InterfaceId>) -> Option<isize> {
+        let interface_id = interface_id.into();
         self.interfaces
             .iter()
-            .any(|i| std::ptr::eq(i.interface_id, interface_id))
+            .find(|i| i.interface_id == interface_id)
+            .map(|i| i.offset)
     }
 }
 
 /// Trait for types that have RTTI
+///
+/// Implemented by `#[cppvtable_impl(IFoo, rtti(IFoo, IBar, ...))]`, whose `TypeInfo` lists
+/// the given interfaces.
 pub trait HasTypeInfo {
+    /// The TypeInfo for this type, at slot -1 of its `rtti` vtables
+    const TYPE_INFO: &'static TypeInfo;
+
     /// Get the TypeInfo for this type
-    fn type_info() -> &'static TypeInfo;
+    fn type_info() -> &'static TypeInfo {
+        Self::TYPE_INFO
+    }
+}
+
+/// Type ID for generated [`TypeInfo`]s: a 128-bit FNV-1a hash of the type's path.
+#[must_use]
+pub const fn type_id_from_path(path: &str) -> u128 {
+    let bytes = path.as_bytes();
+    let mut hash: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u128;
+        hash = hash.wrapping_mul(0x0000_0000_0100_0000_0000_0000_0000_013b);
+        i += 1;
+    }
+    hash
+}
+
+/// Stable interface ID, from `#[cppvtable(stable_id)]` or `#[cppvtable(id = "...")]`:
+/// the 128-bit FNV-1a hash of `key`, on every target.
+#[must_use]
+pub const fn stable_interface_id(key: &str) -> InterfaceId {
+    InterfaceId::Stable(type_id_from_path(key))
 }
 
 /// Retrieve TypeInfo from a vtable pointer (slot -1)
@@ -147,34 +284,172 @@ pub unsafe fn get_type_info(vtable_ptr: *const c_void) -> &'static TypeInfo {
     }
 }
 
-/// Helper to generate a unique interface ID from a static address
+/// Retrieve TypeInfo through an interface pointer (reads its vtable's slot -1)
+///
+/// Works on any interface pointer into an object whose vtables were generated with
+/// `#[cppvtable_impl(IFoo, rtti)]`.
+///
+/// # Safety
+/// - `object` must point to a live vtable pointer of such an object
+#[inline]
+pub unsafe fn type_info_of(object: *const c_void) -> &'static TypeInfo {
+    // SAFETY: caller guarantees a vtable pointer with RTTI at slot -1
+    unsafe { get_type_info(*(object as *const *const c_void)) }
+}
+
+/// An interface with a Rust RTTI identity, the target of the generated `query::<I>()`
+///
+/// Implemented by every `#[cppvtable]` interface (except `no_iid` ones) and every
+/// `#[com_interface]`, whose ID is a stable ID from its GUID.
+pub trait RttiInterface {
+    /// The interface ID listed in [`TypeInfo::interfaces`]: the address of
+    /// `IFoo::interface_id_ptr()`, or the stable ID
+    const INTERFACE_ID: InterfaceId;
+
+    /// This interface and the ones it extends non-virtually, which share its vtable
+    const CHAIN: &'static InterfaceChain = &InterfaceChain::new(Self::INTERFACE_ID, None);
+}
+
+/// An interface ID and the chain of its base interfaces, most derived first.
+///
+/// Bases reached through `extends(IBase)` share the derived interface's vtable pointer;
+/// a virtual base has its own, so `extends(virtual IBase)` ends the chain.
+#[derive(Clone, Copy, Debug)]
+pub struct InterfaceChain {
+    /// The interface's ID
+    pub interface_id: InterfaceId,
+    /// The chain of the interface it extends
+    pub base: Option<&'static InterfaceChain>,
+}
+
+impl InterfaceChain {
+    /// Create a chain link
+    pub const fn new(interface_id: InterfaceId, base: Option<&'static InterfaceChain>) -> Self {
+        Self { interface_id, base }
+    }
+
+    /// Number of interfaces in the chain, this one included
+    #[must_use]
+    pub const fn depth(&self) -> usize {
+        let mut depth = 1;
+        let mut link = self;
+        while let Some(base) = link.base {
+            depth += 1;
+            link = base;
+        }
+        depth
+    }
+}
+
+/// Flatten interface chains into the table of a generated [`TypeInfo`]: each vtable's
+/// most derived interface, followed by its bases at the same offset.
+#[doc(hidden)]
+pub const fn interface_table<const N: usize>(
+    vtables: &[(isize, &'static InterfaceChain)],
+) -> [InterfaceInfo; N] {
+    let mut table = [InterfaceInfo::new(std::ptr::null(), 0); N];
+    let mut len = 0;
+    let mut i = 0;
+    while i < vtables.len() {
+        let (offset, chain) = vtables[i];
+        let mut link = Some(chain);
+        while let Some(interface) = link {
+            table[len] = InterfaceInfo::with_id(interface.interface_id, offset);
+            len += 1;
+            link = interface.base;
+        }
+        i += 1;
+    }
+    assert!(len == N, "interface table size doesn't match the chains");
+    table
+}
+
+/// Read offset-to-top (slot -2) through an interface pointer: the displacement from it
+/// back to the object start.
 ///
-/// Usage: `static IID_IFOO: InterfaceId = interface_id!();`
+/// # Safety
+/// - Same requirements as [`type_info_of`]
+#[inline]
+pub unsafe fn offset_to_top(object: *const c_void) -> isize {
+    // SAFETY: caller guarantees a vtable pointer with RTTI, so with slot -2
+    unsafe {
+        let vtable = *(object as *const *const isize);
+        *vtable.offset(-2)
+    }
+}
+
+/// Cast between two interfaces of an object through its RTTI, returning null if the
+/// object doesn't implement `to`.
+///
+/// `this` may be any of the object's interface pointers: it is moved back to the object
+/// start by the offset-to-top of its vtable before the offset of `to` is applied. That
+/// holds even when its interface is listed at several offsets.
+///
+/// # Safety
+/// - `this` must point to a live vtable pointer of an object whose vtables were generated
+///   with `#[cppvtable_impl(IFoo, rtti)]`
+#[must_use]
+pub unsafe fn query(this: *const c_void, to: InterfaceId) -> *const c_void {
+    // SAFETY: caller guarantees a vtable pointer with RTTI at slots -1 and -2
+    unsafe {
+        let top = this.byte_offset(offset_to_top(this));
+        type_info_of(this).cast_to(top, to)
+    }
+}
+
+/// Helper to generate a unique interface ID from a static address, or a stable
+/// [`InterfaceId`] from a key (see [`stable_interface_id`])
+///
+/// Usage: `let id: usize = interface_id!();` or
+/// `const ID: InterfaceId = interface_id!("my.IFoo");`
 #[macro_export]
 macro_rules! interface_id {
     () => {{
         static __ID: u8 = 0;
         &__ID as *const u8 as usize
     }};
+    ($key:expr) => {
+        $crate::rtti::stable_interface_id($key)
+    };
 }
 
 /// Wrapper for vtables with RTTI at slot -1
 ///
-/// This struct is laid out so that `methods` is at offset sizeof(pointer),
-/// allowing the vtable pointer to point to `methods` while `rtti` is at
-/// the negative offset.
+/// This struct is laid out so that `methods` is at offset 2 * sizeof(pointer),
+/// allowing the vtable pointer to point to `methods` while `rtti` and `offset_to_top`
+/// are at negative offsets.
 #[repr(C)]
 pub struct VTableWithRtti<T> {
+    /// Displacement from the vtable pointer to the object start (slot -2), zero or
+    /// negative like Itanium's
+    pub offset_to_top: isize,
     /// TypeInfo pointer (slot -1 when viewed from methods pointer)
     pub rtti: *const TypeInfo,
     /// The actual vtable methods
     pub methods: T,
 }
 
+// SAFETY: the TypeInfo pointer refers to immutable static data
+unsafe impl<T: Sync> Sync for VTableWithRtti<T> {}
+
 impl<T> VTableWithRtti<T> {
-    /// Create a new vtable wrapper with RTTI
+    /// Create a new vtable wrapper with RTTI, for a vtable pointer at the object start
     pub const fn new(rtti: &'static TypeInfo, methods: T) -> Self {
-        Self { rtti, methods }
+        Self::with_offset_to_top(0, rtti, methods)
+    }
+
+    /// Create a new vtable wrapper with RTTI, for a vtable pointer `-offset_to_top` bytes
+    /// into the object
+    pub const fn with_offset_to_top(
+        offset_to_top: isize,
+        rtti: &'static TypeInfo,
+        methods: T,
+    ) -> Self {
+        Self {
+            offset_to_top,
+            rtti,
+            methods,
+        }
     }
 
     /// Get a pointer to the methods (what the object's vtable pointer should store)
@@ -205,7 +480,7 @@ mod tests {
     #[test]
     fn test_interface_info_new() {
         let info = InterfaceInfo::new(first_id(), 8);
-        assert!(std::ptr::eq(info.interface_id, first_id()));
+        assert_eq!(info.interface_id, InterfaceId::Address(first_id()));
         assert_eq!(info.offset, 8);
     }
 
@@ -237,11 +512,11 @@ mod tests {
     fn test_type_info_with_interfaces() {
         static INTERFACES: [InterfaceInfo; 2] = [
             InterfaceInfo {
-                interface_id: std::ptr::null(), // Will compare by address anyway
+                interface_id: InterfaceId::Address(std::ptr::null()), // Will compare by address anyway
                 offset: 0,
             },
             InterfaceInfo {
-                interface_id: std::ptr::null(),
+                interface_id: InterfaceId::Address(std::ptr::null()),
                 offset: 8,
             },
         ];
@@ -255,11 +530,11 @@ mod tests {
     fn test_implements_returns_true() {
         static INTERFACES: [InterfaceInfo; 2] = [
             InterfaceInfo {
-                interface_id: &IID_FIRST,
+                interface_id: InterfaceId::Address(&IID_FIRST),
                 offset: 0,
             },
             InterfaceInfo {
-                interface_id: &IID_SECOND,
+                interface_id: InterfaceId::Address(&IID_SECOND),
                 offset: 8,
             },
         ];
@@ -273,11 +548,11 @@ mod tests {
     fn test_implements_returns_false_for_unknown() {
         static INTERFACES: [InterfaceInfo; 2] = [
             InterfaceInfo {
-                interface_id: &IID_FIRST,
+                interface_id: InterfaceId::Address(&IID_FIRST),
                 offset: 0,
             },
             InterfaceInfo {
-                interface_id: &IID_SECOND,
+                interface_id: InterfaceId::Address(&IID_SECOND),
                 offset: 8,
             },
         ];
@@ -298,11 +573,11 @@ mod tests {
     fn test_cast_to_primary_interface() {
         static INTERFACES: [InterfaceInfo; 2] = [
             InterfaceInfo {
-                interface_id: &IID_FIRST,
+                interface_id: InterfaceId::Address(&IID_FIRST),
                 offset: 0,
             },
             InterfaceInfo {
-                interface_id: &IID_SECOND,
+                interface_id: InterfaceId::Address(&IID_SECOND),
                 offset: 8,
             },
         ];
@@ -321,11 +596,11 @@ mod tests {
     fn test_cast_to_secondary_interface() {
         static INTERFACES: [InterfaceInfo; 2] = [
             InterfaceInfo {
-                interface_id: &IID_FIRST,
+                interface_id: InterfaceId::Address(&IID_FIRST),
                 offset: 0,
             },
             InterfaceInfo {
-                interface_id: &IID_SECOND,
+                interface_id: InterfaceId::Address(&IID_SECOND),
                 offset: 8,
             },
         ];
@@ -345,11 +620,11 @@ mod tests {
     fn test_cast_to_unknown_returns_null() {
         static INTERFACES: [InterfaceInfo; 2] = [
             InterfaceInfo {
-                interface_id: &IID_FIRST,
+                interface_id: InterfaceId::Address(&IID_FIRST),
                 offset: 0,
             },
             InterfaceInfo {
-                interface_id: &IID_SECOND,
+                interface_id: InterfaceId::Address(&IID_SECOND),
                 offset: 8,
             },
         ];
@@ -428,4 +703,19 @@ mod tests {
         assert!(!std::ptr::eq(second_id(), third_id()));
         assert!(!std::ptr::eq(first_id(), third_id()));
     }
+
+    #[test]
+    fn test_stable_interface_ids() {
+        const SHAPE: InterfaceId = stable_interface_id("plugin::IShape");
+        assert_eq!(SHAPE, stable_interface_id("plugin::IShape"));
+        assert_ne!(SHAPE, stable_interface_id("plugin::INamed"));
+        // All 128 bits of the hash, on every target
+        assert_eq!(
+            SHAPE,
+            InterfaceId::Stable(type_id_from_path("plugin::IShape"))
+        );
+        assert!(type_id_from_path("plugin::IShape") > u128::from(u64::MAX));
+        // Never equal to an address
+        assert_ne!(SHAPE, InterfaceId::from(first_id()));
+    }
 }
@@ -0,0 +1,143 @@
+//! Tests for CppBox: heap-owned objects, typed interface casts and raw ownership transfer
+
+use cppvtable::proc::{cppvtable, cppvtable_impl};
+use cppvtable::{CppBox, CppClass, Implements};
+use std::mem::offset_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cppvtable]
+pub trait IReader {
+    fn read(&self) -> i32;
+}
+
+#[cppvtable]
+pub trait IWriter {
+    fn write(&mut self, value: i32);
+}
+
+#[repr(C)]
+pub struct Buffer {
+    vtable_i_reader: *const IReaderVTable,
+    vtable_i_writer: *const IWriterVTable,
+    value: i32,
+}
+
+#[cppvtable_impl(IReader)]
+impl Buffer {
+    fn read(&self) -> i32 {
+        self.value
+    }
+}
+
+#[cppvtable_impl(IWriter)]
+impl Buffer {
+    fn write(&mut self, value: i32) {
+        self.value = value;
+    }
+}
+
+impl CppClass for Buffer {}
+
+impl Buffer {
+    fn new(value: i32) -> Self {
+        Self {
+            vtable_i_reader: Self::VTABLE_I_READER,
+            vtable_i_writer: Self::VTABLE_I_WRITER,
+            value,
+        }
+    }
+}
+
+#[test]
+fn test_implements_offsets() {
+    assert_eq!(<Buffer as Implements<IReader>>::OFFSET, 0);
+    assert_eq!(
+        <Buffer as Implements<IWriter>>::OFFSET,
+        offset_of!(Buffer, vtable_i_writer)
+    );
+}
+
+#[test]
+fn test_interface_casts() {
+    let mut buffer = CppBox::new(Buffer::new(1));
+    let base = buffer.as_ptr() as *mut u8;
+
+    assert_eq!(buffer.as_interface_ptr::<IReader>() as *mut u8, base);
+    assert_eq!(
+        buffer.as_interface_ptr::<IWriter>() as *mut u8,
+        base.wrapping_add(offset_of!(Buffer, vtable_i_writer))
+    );
+
+    unsafe {
+        buffer.as_interface_mut::<IWriter>().write(5);
+        assert_eq!(buffer.as_interface_mut::<IReader>().read(), 5);
+    }
+    assert_eq!(buffer.value, 5);
+
+    buffer.as_mut().value = 6;
+    assert_eq!(buffer.read(), 6);
+}
+
+#[test]
+fn test_raw_round_trip() {
+    let buffer = CppBox::new(Buffer::new(3));
+    let address = buffer.as_ptr();
+
+    let raw = CppBox::into_raw(buffer);
+    assert_eq!(raw, address);
+    let buffer = unsafe { CppBox::from_raw(raw) };
+
+    let writer = CppBox::into_raw_interface::<IWriter>(buffer);
+    unsafe { (*writer).write(8) };
+    let buffer = unsafe { CppBox::<Buffer>::from_raw_interface(writer) };
+    assert_eq!(buffer.as_ptr(), address);
+    assert_eq!(buffer.value, 8);
+}
+
+// =============================================================================
+// Freeing through the destructor slot
+// =============================================================================
+
+static HANDLES_DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+#[cppvtable]
+pub trait IHandle {
+    #[destructor]
+    fn destructor(&mut self);
+    fn id(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Handle {
+    vtable_i_handle: *const IHandleVTable,
+    id: i32,
+}
+
+#[cppvtable_impl(IHandle)]
+impl Handle {
+    #[destructor]
+    fn destructor(&mut self);
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        HANDLES_DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_drop_uses_destructor_slot() {
+    let handle = CppBox::new(Handle {
+        vtable_i_handle: Handle::VTABLE_I_HANDLE,
+        id: 9,
+    });
+    assert_eq!(handle.id(), 9);
+
+    let before = HANDLES_DROPPED.load(Ordering::SeqCst);
+    drop(handle);
+    assert_eq!(HANDLES_DROPPED.load(Ordering::SeqCst), before + 1);
+}
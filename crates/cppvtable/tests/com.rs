@@ -1,10 +1,27 @@
 //! Tests for COM interface support
 
-use cppvtable::com::{ComRefCount, IUnknownVTable, S_OK};
+use cppvtable::com::{
+    ComInterface, ComPtr, ComRefCount, ComWeakRefCount, E_NOINTERFACE, IUnknownVTable,
+    IWeakReferenceSource, S_OK,
+};
 use cppvtable::proc::{com_implement, com_interface};
 use cppvtable::{IUnknown, VTableLayout};
 use std::ffi::c_void;
 use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Declares a mock vtable function with COM's calling convention: stdcall on every x86
+/// target (`extern "system"` is cdecl on i686 Linux), `extern "system"` elsewhere.
+macro_rules! com_fn {
+    ($(fn $name:ident($($param:ident: $ty:ty),*) -> $ret:ty $body:block)*) => {
+        $(
+            #[cfg(target_arch = "x86")]
+            unsafe extern "stdcall" fn $name($($param: $ty),*) -> $ret $body
+            #[cfg(not(target_arch = "x86"))]
+            unsafe extern "system" fn $name($($param: $ty),*) -> $ret $body
+        )*
+    };
+}
 
 // =============================================================================
 // Test: Basic COM interface definition
@@ -196,7 +213,7 @@ fn test_com_query_interface() {
 // =============================================================================
 
 // Define IScientificCalculator extending ICalculator
-// This tests that the auto-generated icalculator_forwarders! and icalculator_base_vtable! macros exist
+// This tests that the auto-generated ICalculator! vtable macro exists
 #[cppvtable::proc::cppvtable(stdcall, extends(ICalculator))]
 pub trait IScientificCalculator {
     fn square(&self, x: i32) -> i32;
@@ -278,25 +295,27 @@ fn test_generic_vtable_function_pointer_types() {
     }
 
     // Create a mock vtable with correctly typed function pointers
-    // These must be extern "C" and unsafe to match the vtable signature
-    unsafe extern "C" fn mock_open(_this: *mut PluginHandler, _stream: *mut c_void) -> HRESULT {
-        S_OK
-    }
-    unsafe extern "C" fn mock_close(_this: *mut PluginHandler) -> HRESULT {
-        S_OK
-    }
-    unsafe extern "C" fn mock_query_interface(
-        _this: *mut c_void,
-        _riid: *const cppvtable::com::GUID,
-        _ppv: *mut *mut c_void,
-    ) -> HRESULT {
-        S_OK
-    }
-    unsafe extern "C" fn mock_add_ref(_this: *mut c_void) -> u32 {
-        1
-    }
-    unsafe extern "C" fn mock_release(_this: *mut c_void) -> u32 {
-        0
+    // These must use COM's calling convention and be unsafe to match the vtable signature
+    com_fn! {
+        fn mock_open(_this: *mut PluginHandler, _stream: *mut c_void) -> HRESULT {
+            S_OK
+        }
+        fn mock_close(_this: *mut PluginHandler) -> HRESULT {
+            S_OK
+        }
+        fn mock_query_interface(
+            _this: *mut PluginHandler,
+            _riid: *const cppvtable::com::GUID,
+            _ppv: *mut *mut c_void
+        ) -> HRESULT {
+            S_OK
+        }
+        fn mock_add_ref(_this: *mut PluginHandler) -> u32 {
+            1
+        }
+        fn mock_release(_this: *mut PluginHandler) -> u32 {
+            0
+        }
     }
 
     // This should compile because vtable expects fn(*mut PluginHandler, ...)
@@ -310,3 +329,641 @@ fn test_generic_vtable_function_pointer_types() {
         close: mock_close,
     };
 }
+
+// =============================================================================
+// Test: Upcasting derived wrappers to their base
+// =============================================================================
+
+#[test]
+fn test_derived_wrapper_derefs_to_iunknown() {
+    let mut calc = Calculator::new(10);
+
+    unsafe {
+        let iface = ICalculator::from_ptr_mut(&mut calc as *mut _ as *mut c_void);
+        let unknown: &mut IUnknown = iface;
+
+        // Calls IUnknown's own wrapper through the base vtable
+        assert_eq!(unknown.add_ref(), 2);
+        assert_eq!(unknown.release(), 1);
+    }
+}
+
+#[test]
+fn test_generic_wrapper_derefs_to_generic_iunknown() {
+    // `this` is typed as the whole object, vtable pointer included
+    #[repr(C)]
+    struct Archive {
+        vtable: *const IInArchiveVTable<Archive>,
+        refs: u32,
+    }
+
+    com_fn! {
+        fn archive_add_ref(this: *mut Archive) -> u32 {
+            unsafe {
+                (*this).refs += 1;
+                (*this).refs
+            }
+        }
+        fn archive_release(this: *mut Archive) -> u32 {
+            unsafe {
+                (*this).refs -= 1;
+                (*this).refs
+            }
+        }
+        fn archive_query_interface(
+            _this: *mut Archive,
+            _riid: *const cppvtable::com::GUID,
+            _ppv: *mut *mut c_void
+        ) -> HRESULT {
+            S_OK
+        }
+        fn archive_open(_this: *mut Archive, _stream: *mut c_void) -> HRESULT {
+            S_OK
+        }
+        fn archive_close(_this: *mut Archive) -> HRESULT {
+            S_OK
+        }
+    }
+
+    fn upcast<T>(archive: &mut IInArchive<T>) -> &mut IUnknown<T> {
+        archive
+    }
+
+    let vtable = IInArchiveVTable {
+        base: IUnknownVTable {
+            query_interface: archive_query_interface,
+            add_ref: archive_add_ref,
+            release: archive_release,
+        },
+        open: archive_open,
+        close: archive_close,
+    };
+    let mut object = Archive {
+        vtable: &vtable,
+        refs: 1,
+    };
+
+    unsafe {
+        let archive = &mut *(&mut object as *mut Archive as *mut IInArchive<Archive>);
+        assert_eq!(archive.close(), S_OK);
+        assert_eq!(upcast(archive).add_ref(), 2);
+    }
+    assert_eq!(object.refs, 2);
+}
+
+// =============================================================================
+// Test: ComPtr
+// =============================================================================
+
+#[test]
+fn test_com_interface_impl() {
+    assert_eq!(<ICalculator as ComInterface>::IID, IID_ICALCULATOR);
+    assert_eq!(
+        <IUnknown as ComInterface>::IID,
+        cppvtable::com::IID_IUNKNOWN
+    );
+    assert_eq!(<IInArchive<()> as ComInterface>::IID, IID_IINARCHIVE);
+}
+
+#[test]
+fn test_com_ptr_clone_and_drop() {
+    let mut calc = Calculator::new(10);
+
+    unsafe {
+        let ptr = ComPtr::<ICalculator>::from_raw(&mut calc as *mut _ as *mut c_void).unwrap();
+        calc.add_ref();
+
+        let copy = ptr.clone();
+        assert_eq!(calc.ref_count.count(), 3);
+        assert_eq!(copy.add(1, 2), 13);
+        drop(copy);
+        assert_eq!(calc.ref_count.count(), 2);
+        assert_eq!(ptr.multiply(2, 2), 40);
+    }
+    assert_eq!(calc.ref_count.count(), 1);
+}
+
+#[test]
+fn test_com_ptr_cast() {
+    let mut calc = Calculator::new(10);
+
+    unsafe {
+        let ptr = ComPtr::<ICalculator>::from_raw(&mut calc as *mut _ as *mut c_void).unwrap();
+        calc.add_ref();
+
+        let unknown = ptr.cast::<IUnknown>().unwrap();
+        assert_eq!(unknown.as_ptr(), ptr.as_ptr());
+        assert_eq!(calc.ref_count.count(), 3);
+
+        let again = unknown.cast::<ICalculator>().unwrap();
+        assert_eq!(again.add(0, 0), 10);
+
+        let missing = ptr.cast::<IInArchive<()>>();
+        assert_eq!(missing.unwrap_err(), E_NOINTERFACE);
+    }
+    assert_eq!(calc.ref_count.count(), 1);
+}
+
+#[test]
+fn test_com_ptr_raw_round_trip() {
+    let mut calc = Calculator::new(10);
+
+    unsafe {
+        assert!(ComPtr::<ICalculator>::from_raw(ptr::null_mut()).is_none());
+
+        // QueryInterface fills the out parameter with a new reference
+        let iface = ICalculator::from_ptr(&calc as *const _ as *mut c_void);
+        let mut out = None;
+        let hr = iface.query_interface(
+            ICalculator::iid(),
+            ComPtr::<ICalculator>::as_out_param(&mut out),
+        );
+        assert_eq!(hr, S_OK);
+        assert_eq!(calc.ref_count.count(), 2);
+
+        let raw = ComPtr::into_raw(out.unwrap());
+        assert_eq!(raw, &mut calc as *mut _ as *mut c_void);
+        assert_eq!(calc.ref_count.count(), 2);
+        drop(ComPtr::<ICalculator>::from_raw(raw));
+    }
+    assert_eq!(calc.ref_count.count(), 1);
+}
+
+// =============================================================================
+// Test: Heap objects freed by the last Release
+// =============================================================================
+
+static ACCUMULATORS_DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+#[repr(C)]
+pub struct Accumulator {
+    vtable_i_calculator: *const ICalculatorVTable,
+    ref_count: ComRefCount,
+    total: i32,
+}
+
+#[com_implement(ICalculator, heap)]
+impl Accumulator {
+    fn add(&self, a: i32, b: i32) -> i32 {
+        self.total + a + b
+    }
+
+    fn multiply(&self, a: i32, b: i32) -> i32 {
+        self.total * a * b
+    }
+}
+
+impl Drop for Accumulator {
+    fn drop(&mut self) {
+        ACCUMULATORS_DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Accumulator {
+    pub fn new(total: i32) -> Self {
+        Self {
+            vtable_i_calculator: Self::VTABLE_I_CALCULATOR,
+            ref_count: ComRefCount::new(),
+            total,
+        }
+    }
+}
+
+#[test]
+fn test_heap_object_freed_by_last_release() {
+    let before = ACCUMULATORS_DROPPED.load(Ordering::SeqCst);
+
+    let calc = ComPtr::<ICalculator>::new(Accumulator::new(5));
+    let unknown = calc.cast::<IUnknown>().unwrap();
+    assert_eq!(unsafe { calc.add(1, 1) }, 7);
+
+    drop(calc);
+    assert_eq!(ACCUMULATORS_DROPPED.load(Ordering::SeqCst), before);
+
+    // The final Release through the vtable frees it, as it would from C++
+    let raw = ComPtr::into_raw(unknown);
+    unsafe {
+        let iface = IUnknown::from_ptr_mut(raw);
+        assert_eq!(iface.release(), 0);
+    }
+    assert_eq!(ACCUMULATORS_DROPPED.load(Ordering::SeqCst), before + 1);
+}
+
+// =============================================================================
+// Test: QueryInterface across every interface of an object
+// =============================================================================
+
+#[com_interface("5b0c9a7e-2d41-4f3a-8e6b-0c1d2e3f4a01")]
+pub trait IPrinter {
+    fn print(&self, value: i32) -> i32;
+}
+
+#[com_interface("5b0c9a7e-2d41-4f3a-8e6b-0c1d2e3f4a02", extends(ICalculator))]
+pub trait IScientific {
+    fn square(&self, x: i32) -> i32;
+}
+
+#[repr(C)]
+pub struct Workbench {
+    vtable_i_scientific: *const IScientificVTable,
+    vtable_i_printer: *const IPrinterVTable,
+    ref_count: ComRefCount,
+    base_value: i32,
+}
+
+#[com_implement(IScientific, IPrinter)]
+impl Workbench {
+    fn add(&self, a: i32, b: i32) -> i32 {
+        self.base_value + a + b
+    }
+
+    fn multiply(&self, a: i32, b: i32) -> i32 {
+        self.base_value * a * b
+    }
+
+    fn square(&self, x: i32) -> i32 {
+        x * x
+    }
+
+    fn print(&self, value: i32) -> i32 {
+        -value
+    }
+}
+
+impl Workbench {
+    pub fn new(base_value: i32) -> Self {
+        Self {
+            vtable_i_scientific: Self::VTABLE_I_SCIENTIFIC,
+            vtable_i_printer: Self::VTABLE_I_PRINTER,
+            ref_count: ComRefCount::new(),
+            base_value,
+        }
+    }
+}
+
+#[test]
+fn test_com_interface_has_inherited_iids() {
+    assert!(IScientific::has_iid(&IID_ISCIENTIFIC));
+    assert!(IScientific::has_iid(&IID_ICALCULATOR));
+    assert!(IScientific::has_iid(&cppvtable::com::IID_IUNKNOWN));
+    assert!(!IScientific::has_iid(&IID_IPRINTER));
+    assert_eq!(<IScientific as VTableLayout>::SLOT_COUNT, 6);
+}
+
+#[test]
+fn test_query_interface_between_interfaces() {
+    let mut bench = Workbench::new(1);
+    let scientific_ptr = &mut bench.vtable_i_scientific as *mut _ as *mut c_void;
+    let printer_ptr = &mut bench.vtable_i_printer as *mut _ as *mut c_void;
+
+    unsafe {
+        let printer = ComPtr::<IPrinter>::from_raw(printer_ptr).unwrap();
+        bench.add_ref();
+        assert_eq!(printer.clone().print(3), -3);
+
+        let calculator = printer.cast::<ICalculator>().unwrap();
+        assert_eq!(calculator.as_ptr(), scientific_ptr);
+        assert_eq!(calculator.add(2, 3), 6);
+
+        let scientific = calculator.cast::<IScientific>().unwrap();
+        assert_eq!(scientific.square(4), 16);
+        assert_eq!(scientific.multiply(2, 3), 6);
+
+        let printer_again = scientific.cast::<IPrinter>().unwrap();
+        assert_eq!(printer_again.as_ptr(), printer_ptr);
+        assert_eq!(printer_again.print(7), -7);
+
+        assert_eq!(printer.cast::<IInArchive<()>>().unwrap_err(), E_NOINTERFACE);
+        assert_eq!(bench.ref_count.count(), 5);
+    }
+    assert_eq!(bench.ref_count.count(), 1);
+}
+
+#[test]
+fn test_query_interface_unknown_identity() {
+    let mut bench = Workbench::new(1);
+    let scientific_ptr = &mut bench.vtable_i_scientific as *mut _ as *mut c_void;
+    let printer_ptr = &mut bench.vtable_i_printer as *mut _ as *mut c_void;
+
+    unsafe {
+        for ptr in [scientific_ptr, printer_ptr] {
+            let mut unknown = ptr::null_mut();
+            let iface = IUnknown::from_ptr_mut(ptr);
+            assert_eq!(
+                iface.query_interface(&cppvtable::com::IID_IUNKNOWN, &mut unknown),
+                S_OK
+            );
+            assert_eq!(unknown, scientific_ptr);
+            iface.release();
+        }
+    }
+    assert_eq!(bench.ref_count.count(), 1);
+}
+
+// =============================================================================
+// Test: Aggregation, inner IUnknown calls delegating to the outer object
+// =============================================================================
+
+#[com_interface("5b0c9a7e-2d41-4f3a-8e6b-0c1d2e3f4a03")]
+pub trait IEngine {
+    fn rpm(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Engine {
+    vtable_non_delegating: *const IUnknownVTable,
+    vtable_i_engine: *const IEngineVTable,
+    ref_count: ComRefCount,
+    rpm: i32,
+    drops: &'static AtomicUsize,
+}
+
+#[com_implement(IEngine, heap, aggregatable)]
+impl Engine {
+    fn rpm(&self) -> i32 {
+        self.rpm
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Engine {
+    /// `outer` is the aggregating object's IUnknown, or null for a standalone engine
+    pub fn new(outer: *mut c_void, rpm: i32, drops: &'static AtomicUsize) -> Self {
+        Self {
+            vtable_non_delegating: Self::VTABLE_NON_DELEGATING,
+            vtable_i_engine: Self::VTABLE_I_ENGINE,
+            ref_count: ComRefCount::aggregated(outer),
+            rpm,
+            drops,
+        }
+    }
+}
+
+static CARS_DROPPED: AtomicUsize = AtomicUsize::new(0);
+static CAR_ENGINES_DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+#[repr(C)]
+pub struct Car {
+    vtable_i_printer: *const IPrinterVTable,
+    ref_count: ComRefCount,
+    engine: Option<ComPtr<IUnknown>>,
+}
+
+#[com_implement(IPrinter, heap, aggregate(engine: IEngine))]
+impl Car {
+    fn print(&self, value: i32) -> i32 {
+        value * 2
+    }
+}
+
+impl Drop for Car {
+    fn drop(&mut self) {
+        CARS_DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Car {
+    pub fn create(rpm: i32) -> ComPtr<IPrinter> {
+        let mut car = Box::new(Self {
+            vtable_i_printer: Self::VTABLE_I_PRINTER,
+            ref_count: ComRefCount::new(),
+            engine: None,
+        });
+        let outer = &mut car.vtable_i_printer as *mut _ as *mut c_void;
+        car.engine = Some(ComPtr::new(Engine::new(outer, rpm, &CAR_ENGINES_DROPPED)));
+        ComPtr::from_box(car)
+    }
+}
+
+#[test]
+fn test_aggregated_interface_delegates_to_outer() {
+    let car = Car::create(3000);
+    let engine = car.cast::<IEngine>().unwrap();
+    assert_eq!(unsafe { engine.rpm() }, 3000);
+    assert_eq!(unsafe { car.print(4) }, 8);
+
+    // The inner object's IUnknown methods answer as the outer object
+    let printer = engine.cast::<IPrinter>().unwrap();
+    assert_eq!(printer.as_ptr(), car.as_ptr());
+    let unknown = engine.cast::<IUnknown>().unwrap();
+    assert_eq!(unknown.as_ptr(), car.as_ptr());
+    assert_eq!(engine.cast::<ICalculator>().unwrap_err(), E_NOINTERFACE);
+
+    // The engine reference keeps the whole object alive
+    drop((car, printer, unknown));
+    assert_eq!(CARS_DROPPED.load(Ordering::SeqCst), 0);
+    assert_eq!(unsafe { engine.rpm() }, 3000);
+
+    drop(engine);
+    assert_eq!(CARS_DROPPED.load(Ordering::SeqCst), 1);
+    assert_eq!(CAR_ENGINES_DROPPED.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_aggregatable_object_standalone() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    let unknown = ComPtr::<IUnknown>::new(Engine::new(ptr::null_mut(), 900, &DROPPED));
+    let engine = unknown.cast::<IEngine>().unwrap();
+    assert_eq!(unsafe { engine.rpm() }, 900);
+
+    // Without an outer object, the non-delegating IUnknown is the identity
+    assert_eq!(
+        engine.cast::<IUnknown>().unwrap().as_ptr(),
+        unknown.as_ptr()
+    );
+    assert_eq!(engine.cast::<IPrinter>().unwrap_err(), E_NOINTERFACE);
+
+    drop(unknown);
+    assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+    drop(engine);
+    assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+}
+
+// =============================================================================
+// Test: Tear-off interfaces, allocated per query
+// =============================================================================
+
+#[repr(C)]
+pub struct Toolbox {
+    vtable_i_calculator: *const ICalculatorVTable,
+    ref_count: ComRefCount,
+    scale: i32,
+    drops: &'static AtomicUsize,
+}
+
+#[com_implement(ICalculator, heap, tear_off(IPrinter, IEngine))]
+impl Toolbox {
+    fn add(&self, a: i32, b: i32) -> i32 {
+        a + b
+    }
+
+    fn multiply(&self, a: i32, b: i32) -> i32 {
+        a * b
+    }
+
+    fn print(&self, value: i32) -> i32 {
+        value * self.scale
+    }
+
+    fn rpm(&self) -> i32 {
+        self.scale * 1000
+    }
+}
+
+impl Drop for Toolbox {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Toolbox {
+    pub fn new(scale: i32, drops: &'static AtomicUsize) -> Self {
+        Self {
+            vtable_i_calculator: Self::VTABLE_I_CALCULATOR,
+            ref_count: ComRefCount::new(),
+            scale,
+            drops,
+        }
+    }
+}
+
+#[test]
+fn test_tear_off_interfaces() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    let calc = ComPtr::<ICalculator>::new(Toolbox::new(3, &DROPPED));
+    // Only the one vtable field: the tear-offs live outside the object
+    assert_eq!(
+        std::mem::offset_of!(Toolbox, ref_count),
+        std::mem::size_of::<*const c_void>()
+    );
+
+    let printer = calc.cast::<IPrinter>().unwrap();
+    assert_eq!(unsafe { printer.print(5) }, 15);
+    let engine = printer.cast::<IEngine>().unwrap();
+    assert_eq!(unsafe { engine.rpm() }, 3000);
+
+    // Its own interface comes back as the same tear-off, everything else from the owner
+    assert_eq!(
+        printer.cast::<IPrinter>().unwrap().as_ptr(),
+        printer.as_ptr()
+    );
+    assert_eq!(
+        printer.cast::<ICalculator>().unwrap().as_ptr(),
+        calc.as_ptr()
+    );
+    assert_eq!(engine.cast::<IUnknown>().unwrap().as_ptr(), calc.as_ptr());
+    assert_eq!(
+        calc.cast::<IUnknown>().unwrap().as_ptr(),
+        printer.cast::<IUnknown>().unwrap().as_ptr()
+    );
+
+    // Tear-offs keep the owner alive
+    drop(calc);
+    assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+    assert_eq!(unsafe { printer.print(1) }, 3);
+    drop(printer);
+    assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+    drop(engine);
+    assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+}
+
+// =============================================================================
+// Test: Weak references
+// =============================================================================
+
+#[repr(C)]
+pub struct Notebook {
+    vtable_i_calculator: *const ICalculatorVTable,
+    ref_count: ComWeakRefCount,
+    drops: &'static AtomicUsize,
+}
+
+#[com_implement(ICalculator, heap, weak)]
+impl Notebook {
+    fn add(&self, a: i32, b: i32) -> i32 {
+        a + b
+    }
+
+    fn multiply(&self, a: i32, b: i32) -> i32 {
+        a * b
+    }
+}
+
+impl Drop for Notebook {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Notebook {
+    pub fn new(drops: &'static AtomicUsize) -> Self {
+        Self {
+            vtable_i_calculator: Self::VTABLE_I_CALCULATOR,
+            ref_count: ComWeakRefCount::new(),
+            drops,
+        }
+    }
+}
+
+#[test]
+fn test_com_weak_upgrade_until_last_release() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    let calc = ComPtr::<ICalculator>::new(Notebook::new(&DROPPED));
+    let weak = calc.downgrade().unwrap();
+    let weak_again = calc.downgrade().unwrap();
+
+    let upgraded = weak.upgrade().unwrap();
+    assert_eq!(upgraded.as_ptr(), calc.as_ptr());
+    assert_eq!(unsafe { upgraded.add(2, 3) }, 5);
+
+    // Weak references don't keep the object alive
+    drop((calc, upgraded));
+    assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+    assert!(weak.upgrade().is_none());
+    assert!(weak_again.clone().upgrade().is_none());
+}
+
+#[test]
+fn test_weak_reference_source_identity() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    let calc = ComPtr::<ICalculator>::new(Notebook::new(&DROPPED));
+    let source = calc.cast::<IWeakReferenceSource>().unwrap();
+    assert_eq!(source.cast::<IUnknown>().unwrap().as_ptr(), calc.as_ptr());
+    assert_eq!(
+        source.cast::<ICalculator>().unwrap().as_ptr(),
+        calc.as_ptr()
+    );
+
+    // The source holds a strong reference
+    drop(calc);
+    assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+    drop(source);
+    assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_downgrade_without_weak_support() {
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    let calc = ComPtr::<ICalculator>::new(Toolbox::new(1, &DROPPED));
+    assert_eq!(calc.downgrade().unwrap_err(), E_NOINTERFACE);
+}
+
+#[test]
+fn test_weak_state_only_in_weak_objects() {
+    // A count and the outer IUnknown; the weak reference pointer is in ComWeakRefCount
+    assert_eq!(size_of::<ComRefCount>(), 2 * size_of::<usize>());
+    assert_eq!(
+        size_of::<ComWeakRefCount>(),
+        size_of::<ComRefCount>() + size_of::<usize>()
+    );
+}
@@ -0,0 +1,322 @@
+//! Tests for calling convention selection: `win64`/`sysv64` on x86_64, and
+//! thiscall/cdecl on x86 (run the x86 ones with `--target i686-unknown-linux-gnu`)
+
+use cppvtable::com::{ComRefCount, GUID, IUnknownWin64, IUnknownWin64VTable, S_OK};
+use cppvtable::proc::{com_implement, com_interface, cppvtable, cppvtable_impl};
+use std::ffi::c_void;
+use std::ptr;
+
+// =============================================================================
+// C++ interfaces
+// =============================================================================
+
+#[cppvtable(win64)]
+pub trait IMsCounter {
+    fn get(&self) -> i32;
+    fn add(&mut self, a: i32, b: i32, c: i32, d: i32, e: i32) -> i32;
+}
+
+#[cppvtable(sysv64)]
+pub trait ISysvCounter {
+    fn current(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Counter {
+    vtable_i_ms_counter: *const IMsCounterVTable,
+    vtable_i_sysv_counter: *const ISysvCounterVTable,
+    value: i32,
+}
+
+#[cppvtable_impl(IMsCounter, win64)]
+impl Counter {
+    fn get(&self) -> i32 {
+        self.value
+    }
+    fn add(&mut self, a: i32, b: i32, c: i32, d: i32, e: i32) -> i32 {
+        self.value += a + b + c + d + e;
+        self.value
+    }
+}
+
+#[cppvtable_impl(ISysvCounter, sysv64)]
+impl Counter {
+    fn current(&self) -> i32 {
+        self.value
+    }
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn test_function_pointer_types() {
+    // The vtable fields only type-check against the selected ABI
+    unsafe {
+        let ms: unsafe extern "win64" fn(*mut c_void) -> i32 = (*Counter::VTABLE_I_MS_COUNTER).get;
+        let sysv: unsafe extern "sysv64" fn(*mut c_void) -> i32 =
+            (*Counter::VTABLE_I_SYSV_COUNTER).current;
+        assert_ne!(ms as usize, 0);
+        assert_ne!(sysv as usize, 0);
+    }
+}
+
+#[test]
+fn test_calls_through_both_conventions() {
+    let mut counter = Counter {
+        vtable_i_ms_counter: Counter::VTABLE_I_MS_COUNTER,
+        vtable_i_sysv_counter: Counter::VTABLE_I_SYSV_COUNTER,
+        value: 1,
+    };
+    let offset = std::mem::offset_of!(Counter, vtable_i_sysv_counter);
+
+    unsafe {
+        let this = &mut counter as *mut Counter as *mut c_void;
+        let ms = IMsCounter::from_ptr_mut(this);
+        assert_eq!(ms.add(1, 2, 3, 4, 5), 16);
+        assert_eq!(ms.get(), 16);
+
+        let sysv = ISysvCounter::from_ptr_mut((this as *mut u8).add(offset) as *mut c_void);
+        assert_eq!(sysv.current(), 16);
+    }
+}
+
+// =============================================================================
+// Implementations take the convention and ABI of the interface
+// =============================================================================
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Extent {
+    pub width: i32,
+    pub height: i32,
+}
+
+#[cppvtable(win64, abi = "msvc")]
+pub trait IMsPanel {
+    fn width(&self) -> i32;
+    fn extent(&self) -> Extent;
+}
+
+#[repr(C)]
+pub struct Panel {
+    vtable_i_ms_panel: *const IMsPanelVTable,
+    extent: Extent,
+}
+
+#[cppvtable_impl(IMsPanel)]
+impl Panel {
+    fn width(&self) -> i32 {
+        self.extent.width
+    }
+    fn extent(&self) -> Extent {
+        self.extent
+    }
+}
+
+#[test]
+fn test_impl_follows_interface_convention_and_abi() {
+    let panel = Panel {
+        vtable_i_ms_panel: Panel::VTABLE_I_MS_PANEL,
+        extent: Extent {
+            width: 3,
+            height: 4,
+        },
+    };
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        let _: unsafe extern "win64" fn(*mut c_void) -> i32 = (*Panel::VTABLE_I_MS_PANEL).width;
+    }
+    unsafe {
+        let this = &panel as *const Panel as *mut c_void;
+        let iface = IMsPanel::from_ptr_mut(this);
+        assert_eq!(iface.width(), 3);
+        assert_eq!(iface.extent(), panel.extent);
+    }
+}
+
+// =============================================================================
+// COM interfaces
+// =============================================================================
+
+#[com_interface("0b1e3c5a-7d2f-4e6b-9a8c-1d3f5b7e9a2c", win64)]
+pub trait IMsCalculator {
+    fn add(&self, a: i32, b: i32) -> i32;
+}
+
+#[repr(C)]
+pub struct MsCalculator {
+    vtable_i_ms_calculator: *const IMsCalculatorVTable,
+    ref_count: ComRefCount,
+}
+
+#[com_implement(IMsCalculator, win64)]
+impl MsCalculator {
+    fn add(&self, a: i32, b: i32) -> i32 {
+        a + b
+    }
+}
+
+#[test]
+fn test_com_base_is_win64_iunknown() {
+    let _: fn(&IMsCalculatorVTable) -> &IUnknownWin64VTable = |vtable| &vtable.base;
+    assert_eq!(std::mem::offset_of!(IMsCalculatorVTable, base), 0);
+}
+
+#[test]
+fn test_com_win64_calls() {
+    let mut calc = MsCalculator {
+        vtable_i_ms_calculator: MsCalculator::VTABLE_I_MS_CALCULATOR,
+        ref_count: ComRefCount::new(),
+    };
+
+    unsafe {
+        let iface = &mut *(&mut calc as *mut MsCalculator as *mut IMsCalculator);
+        assert_eq!(iface.add(2, 3), 5);
+        assert_eq!(iface.add_ref(), 2);
+        assert_eq!(iface.release(), 1);
+
+        let mut out: *mut c_void = ptr::null_mut();
+        let iid: *const GUID = &IID_IMSCALCULATOR;
+        assert_eq!(iface.query_interface(iid, &mut out), S_OK);
+        assert_eq!(out, iface as *mut IMsCalculator as *mut c_void);
+        assert_eq!(iface.release(), 1);
+    }
+}
+
+// =============================================================================
+// x86 conventions (thiscall on Windows and for the MSVC ABI, cdecl elsewhere)
+// =============================================================================
+
+#[cppvtable]
+pub trait IAutoCc {
+    fn auto_value(&self, x: i32) -> i32;
+}
+
+#[cppvtable(abi = "msvc")]
+pub trait IMsvcCc {
+    fn msvc_value(&self, x: i32) -> i32;
+}
+
+#[cppvtable(thiscall)]
+pub trait IThiscallCc {
+    fn thiscall_value(&self, x: i32) -> i32;
+}
+
+#[cppvtable(cdecl)]
+pub trait ICdeclCc {
+    fn cdecl_value(&self, x: i32) -> i32;
+}
+
+#[repr(C)]
+pub struct Conventions {
+    vtable_i_auto_cc: *const IAutoCcVTable,
+    vtable_i_msvc_cc: *const IMsvcCcVTable,
+    vtable_i_thiscall_cc: *const IThiscallCcVTable,
+    vtable_i_cdecl_cc: *const ICdeclCcVTable,
+    value: i32,
+}
+
+#[cppvtable_impl(IAutoCc)]
+impl Conventions {
+    fn auto_value(&self, x: i32) -> i32 {
+        self.value + x
+    }
+}
+
+#[cppvtable_impl(IMsvcCc, abi = "msvc")]
+impl Conventions {
+    fn msvc_value(&self, x: i32) -> i32 {
+        self.value * x
+    }
+}
+
+#[cppvtable_impl(IThiscallCc, thiscall)]
+impl Conventions {
+    fn thiscall_value(&self, x: i32) -> i32 {
+        self.value - x
+    }
+}
+
+#[cppvtable_impl(ICdeclCc, cdecl)]
+impl Conventions {
+    fn cdecl_value(&self, x: i32) -> i32 {
+        x - self.value
+    }
+}
+
+impl Conventions {
+    fn new(value: i32) -> Self {
+        Self {
+            vtable_i_auto_cc: Self::VTABLE_I_AUTO_CC,
+            vtable_i_msvc_cc: Self::VTABLE_I_MSVC_CC,
+            vtable_i_thiscall_cc: Self::VTABLE_I_THISCALL_CC,
+            vtable_i_cdecl_cc: Self::VTABLE_I_CDECL_CC,
+            value,
+        }
+    }
+}
+
+#[test]
+fn test_x86_convention_calls() {
+    let obj = Conventions::new(10);
+    let base = &obj as *const Conventions as *mut u8;
+
+    unsafe {
+        let this = |offset: usize| base.add(offset) as *mut c_void;
+        let auto = IAutoCc::from_ptr_mut(this(std::mem::offset_of!(Conventions, vtable_i_auto_cc)));
+        let msvc = IMsvcCc::from_ptr_mut(this(std::mem::offset_of!(Conventions, vtable_i_msvc_cc)));
+        let thiscall = IThiscallCc::from_ptr_mut(this(std::mem::offset_of!(
+            Conventions,
+            vtable_i_thiscall_cc
+        )));
+        let cdecl =
+            ICdeclCc::from_ptr_mut(this(std::mem::offset_of!(Conventions, vtable_i_cdecl_cc)));
+
+        assert_eq!(auto.auto_value(3), 13);
+        assert_eq!(msvc.msvc_value(3), 30);
+        assert_eq!(thiscall.thiscall_value(3), 7);
+        assert_eq!(cdecl.cdecl_value(3), -7);
+    }
+}
+
+/// g++/clang on i686 Linux pass `this` on the stack (cdecl), unless forced or MSVC ABI
+#[test]
+#[cfg(all(target_arch = "x86", not(target_os = "windows")))]
+fn test_i686_linux_function_pointer_types() {
+    unsafe {
+        let _: unsafe extern "C" fn(*mut c_void, i32) -> i32 =
+            (*Conventions::VTABLE_I_AUTO_CC).auto_value;
+        let _: unsafe extern "thiscall" fn(*mut c_void, i32) -> i32 =
+            (*Conventions::VTABLE_I_MSVC_CC).msvc_value;
+        let _: unsafe extern "thiscall" fn(*mut c_void, i32) -> i32 =
+            (*Conventions::VTABLE_I_THISCALL_CC).thiscall_value;
+        let _: unsafe extern "C" fn(*mut c_void, i32) -> i32 =
+            (*Conventions::VTABLE_I_CDECL_CC).cdecl_value;
+        // COM keeps stdcall
+        let _: unsafe extern "stdcall" fn(*mut c_void, i32, i32) -> i32 =
+            (*MsCalculator::VTABLE_I_MS_CALCULATOR).add;
+    }
+}
+
+/// MSVC and MinGW use thiscall for member functions
+#[test]
+#[cfg(all(target_arch = "x86", target_os = "windows"))]
+fn test_i686_windows_function_pointer_types() {
+    unsafe {
+        let _: unsafe extern "thiscall" fn(*mut c_void, i32) -> i32 =
+            (*Conventions::VTABLE_I_AUTO_CC).auto_value;
+        let _: unsafe extern "C" fn(*mut c_void, i32) -> i32 =
+            (*Conventions::VTABLE_I_CDECL_CC).cdecl_value;
+    }
+}
+
+/// The x86 options leave x86_64 on `extern "system"`
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn test_x86_options_on_x86_64() {
+    unsafe {
+        let _: unsafe extern "system" fn(*mut c_void, i32) -> i32 =
+            (*Conventions::VTABLE_I_THISCALL_CC).thiscall_value;
+        let _: unsafe extern "system" fn(*mut c_void, i32) -> i32 =
+            (*Conventions::VTABLE_I_CDECL_CC).cdecl_value;
+    }
+}
@@ -0,0 +1,388 @@
+//! Tests for `#[destructor]` virtual destructor slots
+
+use cppvtable::proc::{cppvtable, cppvtable_impl};
+use cppvtable::{CppBox, CppOwned, VTableLayout};
+use std::ffi::c_void;
+use std::mem::ManuallyDrop;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// =============================================================================
+// MSVC: one scalar deleting destructor slot
+// =============================================================================
+
+#[cppvtable(abi = "msvc")]
+pub trait IMsvcResource {
+    #[destructor]
+    fn destructor(&mut self);
+    fn id(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct MsvcResource {
+    vtable_i_msvc_resource: *const IMsvcResourceVTable,
+    id: i32,
+    drops: &'static AtomicUsize,
+}
+
+#[cppvtable_impl(IMsvcResource, abi = "msvc")]
+impl MsvcResource {
+    #[destructor]
+    fn destructor(&mut self);
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl MsvcResource {
+    fn new(id: i32, drops: &'static AtomicUsize) -> Self {
+        MsvcResource {
+            vtable_i_msvc_resource: Self::VTABLE_I_MSVC_RESOURCE,
+            id,
+            drops,
+        }
+    }
+}
+
+impl Drop for MsvcResource {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_msvc_destructor_layout() {
+    let ptr_size = std::mem::size_of::<*const ()>();
+    // Scalar deleting destructor + id
+    assert_eq!(<IMsvcResource as VTableLayout>::SLOT_COUNT, 2);
+    assert_eq!(std::mem::size_of::<IMsvcResourceVTable>(), 2 * ptr_size);
+}
+
+#[test]
+fn test_msvc_destructor_without_free() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let mut obj = ManuallyDrop::new(MsvcResource::new(3, &DROPS));
+
+    unsafe {
+        let iface = &mut *(&mut *obj as *mut MsvcResource as *mut IMsvcResource);
+        assert_eq!(iface.id(), 3);
+        iface.destructor();
+    }
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_msvc_scalar_deleting_destructor_flags() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let obj = Box::into_raw(Box::new(MsvcResource::new(5, &DROPS)));
+
+    unsafe {
+        let iface = obj as *mut IMsvcResource;
+        // Call the slot directly with flags = 1, like `delete p;` compiled by MSVC
+        let returned = ((*iface).vtable().destructor)(iface as *mut _, 1);
+        assert_eq!(returned, obj as *mut _);
+    }
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+}
+
+// =============================================================================
+// Itanium: D1 (complete) + D0 (deleting) destructor pair
+// =============================================================================
+
+#[cppvtable(abi = "itanium")]
+pub trait IItaniumResource {
+    #[destructor]
+    fn destructor(&mut self);
+    fn id(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct ItaniumResource {
+    vtable_i_itanium_resource: *const IItaniumResourceVTable,
+    id: i32,
+    drops: &'static AtomicUsize,
+}
+
+#[cppvtable_impl(IItaniumResource, abi = "itanium")]
+impl ItaniumResource {
+    #[destructor]
+    fn destructor(&mut self);
+
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+impl ItaniumResource {
+    fn new(id: i32, drops: &'static AtomicUsize) -> Self {
+        ItaniumResource {
+            vtable_i_itanium_resource: Self::VTABLE_I_ITANIUM_RESOURCE,
+            id,
+            drops,
+        }
+    }
+}
+
+impl Drop for ItaniumResource {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_itanium_destructor_layout() {
+    let ptr_size = std::mem::size_of::<*const ()>();
+    // D1 + D0 + id
+    assert_eq!(<IItaniumResource as VTableLayout>::SLOT_COUNT, 3);
+    // The destructor pair is one virtual function
+    assert_eq!(<IItaniumResource as VTableLayout>::FUNCTION_COUNT, 2);
+    assert_eq!(std::mem::size_of::<IItaniumResourceVTable>(), 3 * ptr_size);
+}
+
+#[test]
+fn test_itanium_complete_and_deleting_destructors() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    let mut on_stack = ManuallyDrop::new(ItaniumResource::new(1, &DROPS));
+    let boxed = Box::into_raw(Box::new(ItaniumResource::new(2, &DROPS)));
+    unsafe {
+        let iface = &mut *(&mut *on_stack as *mut ItaniumResource as *mut IItaniumResource);
+        iface.destructor();
+        assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+
+        let iface = &mut *(boxed as *mut IItaniumResource);
+        assert_eq!(iface.id(), 2);
+        iface.destructor_deleting();
+    }
+    assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+}
+
+// =============================================================================
+// Native ABI, destructor on a secondary interface
+// =============================================================================
+
+#[cppvtable]
+pub trait IFirst {
+    fn first(&self) -> i32;
+}
+
+#[cppvtable]
+pub trait ISecond {
+    fn second(&self) -> i32;
+    #[destructor]
+    fn destroy(&mut self);
+    fn after(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Pair {
+    vtable_i_first: *const IFirstVTable,
+    vtable_i_second: *const ISecondVTable,
+    drops: &'static AtomicUsize,
+}
+
+#[cppvtable_impl(IFirst)]
+impl Pair {
+    fn first(&self) -> i32 {
+        1
+    }
+}
+
+#[cppvtable_impl(ISecond)]
+impl Pair {
+    fn second(&self) -> i32 {
+        2
+    }
+    #[destructor]
+    fn destroy(&mut self);
+    fn after(&self) -> i32 {
+        3
+    }
+}
+
+impl Drop for Pair {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_delete_through_secondary_interface() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let pair = Box::into_raw(Box::new(Pair {
+        vtable_i_first: Pair::VTABLE_I_FIRST,
+        vtable_i_second: Pair::VTABLE_I_SECOND,
+        drops: &DROPS,
+    }));
+
+    unsafe {
+        let offset = std::mem::offset_of!(Pair, vtable_i_second);
+        let second = &mut *((pair as *mut u8).add(offset) as *mut ISecond);
+        assert_eq!((*(pair as *mut IFirst)).first(), 1);
+        assert_eq!(second.second(), 2);
+        // Methods after the destructor keep working, whatever the destructor's slot width
+        assert_eq!(second.after(), 3);
+        second.destroy_deleting();
+    }
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+}
+
+// =============================================================================
+// Destructors in two interfaces: one CppClass for the struct
+// =============================================================================
+
+#[cppvtable]
+pub trait IFront {
+    #[destructor]
+    fn destroy(&mut self);
+    fn front(&self) -> i32;
+}
+
+#[cppvtable]
+pub trait IBack {
+    #[destructor]
+    fn destroy(&mut self);
+    fn back(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Card {
+    vtable_i_front: *const IFrontVTable,
+    vtable_i_back: *const IBackVTable,
+    drops: &'static AtomicUsize,
+}
+
+#[cppvtable_impl(IFront)]
+impl Card {
+    #[destructor]
+    fn destroy(&mut self);
+    fn front(&self) -> i32 {
+        1
+    }
+}
+
+#[cppvtable_impl(IBack, no_cpp_class)]
+impl Card {
+    #[destructor]
+    fn destroy(&mut self);
+    fn back(&self) -> i32 {
+        2
+    }
+}
+
+impl Drop for Card {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_destructors_in_two_interfaces() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let new_card = || Card {
+        vtable_i_front: Card::VTABLE_I_FRONT,
+        vtable_i_back: Card::VTABLE_I_BACK,
+        drops: &DROPS,
+    };
+
+    // CppBox frees through the IFront block's CppClass
+    let card = CppBox::new(new_card());
+    assert_eq!(card.back(), 2);
+    drop(card);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+
+    // Either deleting destructor frees the whole object
+    let card = CppBox::into_raw_interface::<IBack>(CppBox::new(new_card()));
+    unsafe {
+        assert_eq!((*card).back(), 2);
+        (*card).destroy_deleting();
+    }
+    assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+}
+
+// =============================================================================
+// CppOwned: deleting through the destructor slot on drop
+// =============================================================================
+
+#[test]
+fn test_cpp_owned_deletes_on_drop() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let msvc = Box::into_raw(Box::new(MsvcResource::new(7, &DROPS)));
+    let itanium = Box::into_raw(Box::new(ItaniumResource::new(8, &DROPS)));
+
+    // MSVC scalar deleting destructor with flags 1
+    let owned = unsafe { CppOwned::<IMsvcResource>::from_raw(msvc as *mut c_void) };
+    assert_eq!(owned.id(), 7);
+    drop(owned);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+
+    // Itanium D0
+    let mut owned = unsafe { CppOwned::<IItaniumResource>::from_raw(itanium as *mut c_void) };
+    assert_eq!(owned.as_ptr(), itanium as *mut c_void);
+    assert_eq!(owned.id(), 8);
+    // The wrapper is still there for its unsafe methods
+    assert_eq!(unsafe { owned.as_interface_mut().id() }, 8);
+    drop(owned);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_cpp_owned_into_raw_keeps_object() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let obj = Box::into_raw(Box::new(MsvcResource::new(1, &DROPS)));
+
+    let owned = unsafe { CppOwned::<IMsvcResource>::from_raw(obj as *mut c_void) };
+    assert_eq!(CppOwned::into_raw(owned), obj as *mut c_void);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 0);
+
+    drop(unsafe { Box::from_raw(obj) });
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+}
+
+#[cppvtable(extends(ISecond))]
+pub trait IThird {
+    fn third(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Triple {
+    vtable_i_third: *const IThirdVTable,
+    drops: &'static AtomicUsize,
+}
+
+#[cppvtable_impl(IThird, extends(ISecond))]
+impl Triple {
+    fn second(&self) -> i32 {
+        2
+    }
+    #[destructor]
+    fn destroy(&mut self);
+    fn after(&self) -> i32 {
+        3
+    }
+    fn third(&self) -> i32 {
+        4
+    }
+}
+
+impl Drop for Triple {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn test_cpp_owned_inherited_destructor() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    let triple = Box::into_raw(Box::new(Triple {
+        vtable_i_third: Triple::VTABLE_I_THIRD,
+        drops: &DROPS,
+    }));
+
+    let owned = unsafe { CppOwned::<IThird>::from_raw(triple as *mut c_void) };
+    assert_eq!(owned.third(), 4);
+    // Inherited methods through the view of ISecond
+    assert_eq!(owned.after(), 3);
+    drop(owned);
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+}
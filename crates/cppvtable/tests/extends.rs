@@ -0,0 +1,461 @@
+//! Tests for implementing derived (`extends`) interfaces with `#[cppvtable_impl]`
+
+use cppvtable::VTableLayout;
+use cppvtable::proc::{cppvtable, cppvtable_impl};
+use cppvtable::rtti::{HasTypeInfo, RttiInterface, type_info_of};
+use std::ffi::c_void;
+
+#[cppvtable]
+pub trait IBase {
+    fn base_value(&self) -> i32;
+    fn scale(&mut self, factor: i32);
+}
+
+#[cppvtable(extends(IBase))]
+pub trait IDerived {
+    fn derived_value(&self) -> i32;
+    #[slot(2)]
+    fn late(&self) -> i32;
+}
+
+#[cppvtable(extends(IDerived))]
+pub trait IMostDerived {
+    fn most_value(&self) -> i32;
+}
+
+// =============================================================================
+// One impl block covering base and derived methods
+// =============================================================================
+
+#[repr(C)]
+pub struct Single {
+    vtable_i_derived: *const IDerivedVTable,
+    value: i32,
+}
+
+#[cppvtable_impl(IDerived, extends(IBase))]
+impl Single {
+    fn base_value(&self) -> i32 {
+        self.value
+    }
+    fn scale(&mut self, factor: i32) {
+        self.value *= factor;
+    }
+    fn derived_value(&self) -> i32 {
+        self.value + 1
+    }
+    fn late(&self) -> i32 {
+        99
+    }
+}
+
+#[test]
+fn test_derived_layout() {
+    // IBase (2) + derived_value, reserved slot 1, late
+    assert_eq!(<IDerived as VTableLayout>::SLOT_COUNT, 5);
+    assert_eq!(<IMostDerived as VTableLayout>::SLOT_COUNT, 6);
+    assert_eq!(
+        std::mem::size_of::<IDerivedVTable>(),
+        5 * std::mem::size_of::<*const ()>()
+    );
+}
+
+#[test]
+fn test_single_impl_block() {
+    let mut obj = Single {
+        vtable_i_derived: Single::VTABLE_I_DERIVED,
+        value: 10,
+    };
+    let this = &mut obj as *mut Single as *mut c_void;
+
+    unsafe {
+        let vtable = &*obj.vtable_i_derived;
+        assert_eq!((vtable.base.base_value)(this), 10);
+        (vtable.base.scale)(this, 3);
+        assert_eq!((vtable.derived_value)(this), 31);
+        assert_eq!((vtable.late)(this), 99);
+
+        let derived = IDerived::from_ptr_mut(this);
+        assert_eq!(derived.derived_value(), 31);
+    }
+}
+
+// =============================================================================
+// Multi-level chain, methods spread over several impl blocks
+// =============================================================================
+
+#[repr(C)]
+pub struct Spread {
+    vtable_i_most_derived: *const IMostDerivedVTable,
+    value: i32,
+}
+
+impl Spread {
+    fn base_value(&self) -> i32 {
+        self.value
+    }
+    fn scale(&mut self, factor: i32) {
+        self.value *= factor;
+    }
+}
+
+impl Spread {
+    fn derived_value(&self) -> i32 {
+        self.value * 2
+    }
+    fn late(&self) -> i32 {
+        7
+    }
+}
+
+#[cppvtable_impl(IMostDerived, extends(IDerived))]
+impl Spread {
+    fn most_value(&self) -> i32 {
+        self.value * 3
+    }
+}
+
+#[test]
+fn test_multi_level_chain() {
+    let mut obj = Spread {
+        vtable_i_most_derived: Spread::VTABLE_I_MOST_DERIVED,
+        value: 2,
+    };
+    let this = &mut obj as *mut Spread as *mut c_void;
+
+    unsafe {
+        let vtable = &*obj.vtable_i_most_derived;
+        (vtable.base.base.scale)(this, 5);
+        assert_eq!((vtable.base.base.base_value)(this), 10);
+        assert_eq!((vtable.base.derived_value)(this), 20);
+        assert_eq!((vtable.base.late)(this), 7);
+        assert_eq!((vtable.most_value)(this), 30);
+    }
+}
+
+// =============================================================================
+// Derived interface as a secondary base (this-adjustment through the chain)
+// =============================================================================
+
+#[cppvtable]
+pub trait IOther {
+    fn other(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Secondary {
+    vtable_i_other: *const IOtherVTable,
+    vtable_i_derived: *const IDerivedVTable,
+    value: i32,
+}
+
+#[cppvtable_impl(IOther)]
+impl Secondary {
+    fn other(&self) -> i32 {
+        -1
+    }
+}
+
+#[cppvtable_impl(IDerived, extends(IBase))]
+impl Secondary {
+    fn base_value(&self) -> i32 {
+        self.value
+    }
+    fn scale(&mut self, factor: i32) {
+        self.value *= factor;
+    }
+    fn derived_value(&self) -> i32 {
+        self.value + 100
+    }
+    fn late(&self) -> i32 {
+        0
+    }
+}
+
+#[test]
+fn test_secondary_derived_interface() {
+    let mut obj = Secondary {
+        vtable_i_other: Secondary::VTABLE_I_OTHER,
+        vtable_i_derived: Secondary::VTABLE_I_DERIVED,
+        value: 4,
+    };
+    let offset = std::mem::offset_of!(Secondary, vtable_i_derived);
+
+    unsafe {
+        let this = (&mut obj as *mut Secondary as *mut u8).add(offset) as *mut c_void;
+        let vtable = &*obj.vtable_i_derived;
+        (vtable.base.scale)(this, 2);
+        assert_eq!((vtable.base.base_value)(this), 8);
+        assert_eq!((vtable.derived_value)(this), 108);
+        assert_eq!((*(&mut obj as *mut Secondary as *mut IOther)).other(), -1);
+    }
+}
+
+// =============================================================================
+// Upcasting wrappers through the chain
+// =============================================================================
+
+#[test]
+fn test_deref_to_base_wrappers() {
+    let mut obj = Spread {
+        vtable_i_most_derived: Spread::VTABLE_I_MOST_DERIVED,
+        value: 3,
+    };
+
+    unsafe {
+        let most = IMostDerived::from_ptr_mut(&mut obj as *mut Spread as *mut c_void);
+        assert_eq!(most.most_value(), 9);
+
+        // Base methods resolve through Deref/DerefMut at every level
+        most.scale(2);
+        assert_eq!(most.derived_value(), 12);
+        assert_eq!(most.base_value(), 6);
+
+        let base: &mut IBase = most;
+        assert_eq!(base.base_value(), 6);
+    }
+}
+
+// =============================================================================
+// RTTI lists the bases of each derived interface
+// =============================================================================
+
+#[repr(C)]
+pub struct Layered {
+    vtable_i_other: *const IOtherVTable,
+    vtable_i_most_derived: *const IMostDerivedVTable,
+    value: i32,
+}
+
+#[cppvtable_impl(IOther, rtti(IOther, IMostDerived))]
+impl Layered {
+    fn other(&self) -> i32 {
+        -2
+    }
+}
+
+#[cppvtable_impl(IMostDerived, extends(IDerived), rtti)]
+impl Layered {
+    fn base_value(&self) -> i32 {
+        self.value
+    }
+    fn scale(&mut self, factor: i32) {
+        self.value *= factor;
+    }
+    fn derived_value(&self) -> i32 {
+        self.value + 1
+    }
+    fn late(&self) -> i32 {
+        0
+    }
+    fn most_value(&self) -> i32 {
+        self.value + 2
+    }
+}
+
+impl Layered {
+    fn new(value: i32) -> Self {
+        Self {
+            vtable_i_other: Self::VTABLE_I_OTHER,
+            vtable_i_most_derived: Self::VTABLE_I_MOST_DERIVED,
+            value,
+        }
+    }
+}
+
+#[test]
+fn test_interface_chains() {
+    let chain = <IMostDerived as RttiInterface>::CHAIN;
+    assert_eq!(chain.depth(), 3);
+    let ids: Vec<_> = std::iter::successors(Some(chain), |link| link.base)
+        .map(|link| link.interface_id)
+        .collect();
+    assert_eq!(
+        ids,
+        [
+            IMostDerived::interface_id_ptr().into(),
+            IDerived::interface_id_ptr().into(),
+            IBase::interface_id_ptr().into()
+        ]
+    );
+    assert_eq!(<IOther as RttiInterface>::CHAIN.depth(), 1);
+}
+
+#[test]
+fn test_type_info_hierarchy() {
+    let offset = std::mem::offset_of!(Layered, vtable_i_most_derived) as isize;
+    let type_info = Layered::type_info();
+    assert_eq!(type_info.interfaces.len(), 4);
+    assert!(type_info.implements(IBase::interface_id_ptr()));
+    assert_eq!(
+        type_info.offset_of(IDerived::interface_id_ptr()),
+        Some(offset)
+    );
+
+    let hierarchy: Vec<Vec<_>> = type_info
+        .hierarchy()
+        .map(|vtable| vtable.iter().map(|i| (i.interface_id, i.offset)).collect())
+        .collect();
+    assert_eq!(
+        hierarchy,
+        [
+            vec![(IOther::interface_id_ptr().into(), 0)],
+            vec![
+                (IMostDerived::interface_id_ptr().into(), offset),
+                (IDerived::interface_id_ptr().into(), offset),
+                (IBase::interface_id_ptr().into(), offset),
+            ],
+        ]
+    );
+}
+
+#[test]
+fn test_cast_to_base_interfaces() {
+    let mut obj = Layered::new(5);
+    let object = &mut obj as *mut Layered as *mut c_void;
+
+    unsafe {
+        let base = type_info_of(object).cast_to(object, IBase::interface_id_ptr());
+        assert_eq!(base, &raw const obj.vtable_i_most_derived as *const c_void);
+
+        let other = IOther::from_ptr_mut(object);
+        assert!(other.implements::<IDerived>());
+        let base = other.query_mut::<IBase>().unwrap();
+        base.scale(2);
+        assert_eq!(base.base_value(), 10);
+
+        // Back from a base reached through Deref
+        let most = other.query_mut::<IMostDerived>().unwrap();
+        let derived: &mut IDerived = most;
+        assert_eq!(derived.query_mut::<IOther>().unwrap().other(), -2);
+        assert_eq!(
+            derived.query_mut::<IMostDerived>().unwrap().most_value(),
+            12
+        );
+    }
+}
+
+// =============================================================================
+// A base shared by two chains: RTTI lists it at both offsets
+// =============================================================================
+
+#[cppvtable(extends(IBase))]
+pub trait ISibling {
+    fn sibling_value(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Twins {
+    vtable_i_derived: *const IDerivedVTable,
+    vtable_i_sibling: *const ISiblingVTable,
+    value: i32,
+}
+
+#[cppvtable_impl(IDerived, extends(IBase), rtti(IDerived, ISibling))]
+impl Twins {
+    fn base_value(&self) -> i32 {
+        self.value
+    }
+    fn scale(&mut self, factor: i32) {
+        self.value *= factor;
+    }
+    fn derived_value(&self) -> i32 {
+        self.value + 1
+    }
+    fn late(&self) -> i32 {
+        0
+    }
+}
+
+#[cppvtable_impl(ISibling, extends(IBase), rtti)]
+impl Twins {
+    fn sibling_value(&self) -> i32 {
+        self.value + 3
+    }
+}
+
+#[test]
+fn test_query_from_shared_base() {
+    let mut obj = Twins {
+        vtable_i_derived: Twins::VTABLE_I_DERIVED,
+        vtable_i_sibling: Twins::VTABLE_I_SIBLING,
+        value: 7,
+    };
+    let sibling_offset = std::mem::offset_of!(Twins, vtable_i_sibling) as isize;
+    let type_info = Twins::type_info();
+    let base_offsets: Vec<_> = type_info
+        .interfaces
+        .iter()
+        .filter(|i| i.interface_id == IBase::interface_id_ptr().into())
+        .map(|i| i.offset)
+        .collect();
+    assert_eq!(base_offsets, [0, sibling_offset]);
+
+    unsafe {
+        // The IBase of the second chain: its own offset-to-top leads to the object start
+        let sibling = ISibling::from_ptr_mut(&raw mut obj.vtable_i_sibling as *mut c_void);
+        let base: &mut IBase = sibling;
+        let derived = base.query_mut::<IDerived>().unwrap();
+        assert!(std::ptr::eq(
+            derived as *const IDerived as *const Twins,
+            &raw const obj
+        ));
+        assert_eq!(derived.derived_value(), 8);
+        assert_eq!(derived.query::<ISibling>().unwrap().sibling_value(), 10);
+    }
+}
+
+// =============================================================================
+// Interfaces declared after their implementation, in another module
+// =============================================================================
+
+mod early_impl {
+    use super::later_interfaces::{IChild, IChildVTable, IParent, IParentVTable};
+    use cppvtable::proc::cppvtable_impl;
+
+    #[repr(C)]
+    pub struct Early {
+        pub vtable_i_child: *const IChildVTable,
+        pub value: i32,
+    }
+
+    #[cppvtable_impl(IChild, extends(IParent))]
+    impl Early {
+        fn parent_value(&self) -> i32 {
+            self.value
+        }
+        fn child_value(&self) -> i32 {
+            self.value * 10
+        }
+    }
+}
+
+mod later_interfaces {
+    use cppvtable::proc::cppvtable;
+
+    #[cppvtable]
+    pub trait IParent {
+        fn parent_value(&self) -> i32;
+    }
+
+    #[cppvtable(extends(IParent))]
+    pub trait IChild {
+        fn child_value(&self) -> i32;
+    }
+}
+
+#[test]
+fn test_interface_macros_resolve_by_path() {
+    use early_impl::Early;
+    use later_interfaces::IChild;
+
+    let mut obj = Early {
+        vtable_i_child: Early::VTABLE_I_CHILD,
+        value: 3,
+    };
+    unsafe {
+        let child = IChild::from_ptr_mut(&mut obj as *mut Early as *mut c_void);
+        assert_eq!(child.child_value(), 30);
+        assert_eq!(child.parent_value(), 3);
+    }
+}
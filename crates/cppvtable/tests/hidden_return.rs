@@ -0,0 +1,241 @@
+//! Tests for struct return values (MSVC hidden return pointer)
+
+use cppvtable::proc::{cppvtable, cppvtable_impl};
+use std::ffi::c_void;
+use std::mem::MaybeUninit;
+use std::ptr::NonNull;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+/// Small enough for a free function to return it in RAX on x64
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Size {
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Alias of a scalar the macro can't see through
+pub type Handle = u64;
+
+// =============================================================================
+// MSVC: aggregates go through a hidden pointer after `this`
+// =============================================================================
+
+#[cppvtable(abi = "msvc")]
+pub trait IMsvcWidget {
+    fn bounds(&self) -> Rect;
+    fn scaled(&self, factor: i32) -> Size;
+    fn id(&self) -> i32;
+    #[scalar_return]
+    fn handle(&self) -> Handle;
+}
+
+#[repr(C)]
+pub struct MsvcWidget {
+    vtable_i_msvc_widget: *const IMsvcWidgetVTable,
+    size: Size,
+}
+
+#[cppvtable_impl(IMsvcWidget, abi = "msvc")]
+impl MsvcWidget {
+    fn bounds(&self) -> Rect {
+        Rect {
+            left: 1,
+            top: 2,
+            right: 1 + self.size.width,
+            bottom: 2 + self.size.height,
+        }
+    }
+    fn scaled(&self, factor: i32) -> Size {
+        Size {
+            width: self.size.width * factor,
+            height: self.size.height * factor,
+        }
+    }
+    fn id(&self) -> i32 {
+        42
+    }
+    // Returned in a register: `#[scalar_return]` comes from the interface
+    fn handle(&self) -> Handle {
+        0xdead_beef
+    }
+}
+
+impl MsvcWidget {
+    fn new(width: i32, height: i32) -> Self {
+        MsvcWidget {
+            vtable_i_msvc_widget: Self::VTABLE_I_MSVC_WIDGET,
+            size: Size { width, height },
+        }
+    }
+}
+
+#[test]
+fn test_msvc_struct_return_through_wrapper() {
+    let mut widget = MsvcWidget::new(10, 20);
+    unsafe {
+        let iface = &mut *(&mut widget as *mut MsvcWidget as *mut IMsvcWidget);
+        assert_eq!(
+            iface.bounds(),
+            Rect {
+                left: 1,
+                top: 2,
+                right: 11,
+                bottom: 22
+            }
+        );
+        assert_eq!(
+            iface.scaled(3),
+            Size {
+                width: 30,
+                height: 60
+            }
+        );
+        assert_eq!(iface.id(), 42);
+        assert_eq!(iface.handle(), 0xdead_beef);
+    }
+}
+
+#[test]
+fn test_msvc_hidden_pointer_slot_signature() {
+    let widget = MsvcWidget::new(4, 5);
+    let this = &widget as *const MsvcWidget as *mut c_void;
+    unsafe {
+        let vtable = &*widget.vtable_i_msvc_widget;
+
+        // Hidden pointer comes right after `this`, before the declared parameters,
+        // and is handed back as the return value
+        let mut out = MaybeUninit::<Size>::uninit();
+        let returned = (vtable.scaled)(this, out.as_mut_ptr(), 2);
+        assert_eq!(returned, out.as_mut_ptr());
+        assert_eq!(
+            out.assume_init(),
+            Size {
+                width: 8,
+                height: 10
+            }
+        );
+
+        // Scalars (including opted-out aliases) are returned directly
+        assert_eq!((vtable.id)(this), 42);
+        assert_eq!((vtable.handle)(this), 0xdead_beef);
+    }
+}
+
+// =============================================================================
+// MSVC: Option is a pointer only around a non-null pointer
+// =============================================================================
+
+#[cppvtable(abi = "msvc")]
+pub trait IMsvcLookup {
+    fn find(&self, key: i32) -> Option<NonNull<Size>>;
+    fn callback(&self) -> Option<extern "C" fn() -> i32>;
+    fn count(&self, key: i32) -> Option<i32>;
+}
+
+#[repr(C)]
+pub struct MsvcLookup {
+    vtable_i_msvc_lookup: *const IMsvcLookupVTable,
+    size: Size,
+}
+
+extern "C" fn seven() -> i32 {
+    7
+}
+
+#[cppvtable_impl(IMsvcLookup, abi = "msvc")]
+impl MsvcLookup {
+    fn find(&self, key: i32) -> Option<NonNull<Size>> {
+        (key == 1).then(|| NonNull::from(&self.size))
+    }
+    fn callback(&self) -> Option<extern "C" fn() -> i32> {
+        Some(seven)
+    }
+    fn count(&self, key: i32) -> Option<i32> {
+        (key > 0).then_some(key * 10)
+    }
+}
+
+#[test]
+fn test_msvc_option_return_slot_signature() {
+    let mut lookup = MsvcLookup {
+        vtable_i_msvc_lookup: MsvcLookup::VTABLE_I_MSVC_LOOKUP,
+        size: Size {
+            width: 3,
+            height: 4,
+        },
+    };
+    let this = &lookup as *const MsvcLookup as *mut c_void;
+    unsafe {
+        let vtable = &*lookup.vtable_i_msvc_lookup;
+
+        // Nullable pointers come back in a register
+        assert_eq!((vtable.find)(this, 1), Some(NonNull::from(&lookup.size)));
+        assert_eq!((vtable.find)(this, 2), None);
+        assert_eq!((vtable.callback)(this).map(|f| f()), Some(7));
+
+        // Option<i32> is an enum, returned through the hidden pointer
+        let mut out = MaybeUninit::<Option<i32>>::uninit();
+        let returned = (vtable.count)(this, out.as_mut_ptr(), 4);
+        assert_eq!(returned, out.as_mut_ptr());
+        assert_eq!(out.assume_init(), Some(40));
+
+        let iface = &mut *(&mut lookup as *mut MsvcLookup as *mut IMsvcLookup);
+        assert_eq!(iface.count(0), None);
+    }
+}
+
+// =============================================================================
+// Itanium: aggregates are returned like from a C function
+// =============================================================================
+
+#[cppvtable(abi = "itanium")]
+pub trait IItaniumWidget {
+    fn scaled(&self, factor: i32) -> Size;
+}
+
+#[repr(C)]
+pub struct ItaniumWidget {
+    vtable_i_itanium_widget: *const IItaniumWidgetVTable,
+    size: Size,
+}
+
+#[cppvtable_impl(IItaniumWidget, abi = "itanium")]
+impl ItaniumWidget {
+    fn scaled(&self, factor: i32) -> Size {
+        Size {
+            width: self.size.width * factor,
+            height: self.size.height * factor,
+        }
+    }
+}
+
+#[test]
+fn test_itanium_struct_return_is_direct() {
+    let widget = ItaniumWidget {
+        vtable_i_itanium_widget: ItaniumWidget::VTABLE_I_ITANIUM_WIDGET,
+        size: Size {
+            width: 2,
+            height: 3,
+        },
+    };
+    let this = &widget as *const ItaniumWidget as *mut c_void;
+    unsafe {
+        let vtable = &*widget.vtable_i_itanium_widget;
+        assert_eq!(
+            (vtable.scaled)(this, 5),
+            Size {
+                width: 10,
+                height: 15
+            }
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Tests for the Itanium ABI vtable layout (`abi = "itanium"`)
+
+use cppvtable::itanium::{self, ItaniumVTable};
+use cppvtable::proc::{cppvtable, cppvtable_impl};
+use cppvtable::{CppAbi, VTableLayout};
+use std::ffi::c_void;
+
+#[cppvtable(abi = "itanium")]
+pub trait IWalker {
+    fn walk(&self) -> i32;
+}
+
+#[cppvtable(abi = "itanium")]
+pub trait ISwimmer {
+    fn swim(&self) -> i32;
+    fn depth(&self) -> i32;
+}
+
+#[cppvtable(abi = "msvc")]
+pub trait IMsvcOnly {
+    fn value(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Otter {
+    vtable_i_walker: *const IWalkerVTable,
+    vtable_i_swimmer: *const ISwimmerVTable,
+    speed: i32,
+}
+
+#[cppvtable_impl(IWalker, abi = "itanium", vtable_group(ISwimmer))]
+impl Otter {
+    fn walk(&self) -> i32 {
+        self.speed
+    }
+}
+
+#[cppvtable_impl(ISwimmer, abi = "itanium", grouped)]
+impl Otter {
+    fn swim(&self) -> i32 {
+        self.speed * 3
+    }
+    fn depth(&self) -> i32 {
+        12
+    }
+}
+
+impl Otter {
+    pub fn new(speed: i32) -> Self {
+        Otter {
+            vtable_i_walker: Self::VTABLE_I_WALKER,
+            vtable_i_swimmer: Self::VTABLE_I_SWIMMER,
+            speed,
+        }
+    }
+
+    fn swimmer_ptr(&self) -> *const c_void {
+        unsafe {
+            (self as *const Self as *const u8).add(std::mem::offset_of!(Self, vtable_i_swimmer))
+                as *const c_void
+        }
+    }
+}
+
+#[repr(C)]
+pub struct MsvcThing {
+    vtable_i_msvc_only: *const IMsvcOnlyVTable,
+}
+
+#[cppvtable_impl(IMsvcOnly, abi = "msvc")]
+impl MsvcThing {
+    fn value(&self) -> i32 {
+        7
+    }
+}
+
+#[test]
+fn test_interface_abi_constant() {
+    assert_eq!(<IWalker as VTableLayout>::ABI, CppAbi::Itanium);
+    assert_eq!(<IMsvcOnly as VTableLayout>::ABI, CppAbi::Msvc);
+}
+
+#[test]
+fn test_native_abi_matches_target() {
+    #[cfg(target_env = "msvc")]
+    assert_eq!(CppAbi::NATIVE, CppAbi::Msvc);
+    #[cfg(not(target_env = "msvc"))]
+    assert_eq!(CppAbi::NATIVE, CppAbi::Itanium);
+}
+
+#[test]
+fn test_itanium_vtable_struct_is_unchanged() {
+    // The prefix lives in the static, not in {Name}VTable itself
+    let ptr_size = std::mem::size_of::<*const c_void>();
+    assert_eq!(std::mem::size_of::<ISwimmerVTable>(), 2 * ptr_size);
+    assert_eq!(
+        std::mem::size_of::<ItaniumVTable<ISwimmerVTable>>(),
+        4 * ptr_size
+    );
+    assert_eq!(
+        std::mem::offset_of!(ItaniumVTable<ISwimmerVTable>, methods),
+        2 * ptr_size
+    );
+}
+
+#[test]
+fn test_primary_offset_to_top_is_zero() {
+    let otter = Otter::new(2);
+    let this = &otter as *const Otter as *const c_void;
+    unsafe {
+        assert_eq!(itanium::offset_to_top(this), 0);
+        assert!(itanium::type_info_ptr(this).is_null());
+        assert_eq!(itanium::complete_object(this), this);
+    }
+}
+
+#[test]
+fn test_secondary_offset_to_top_is_negative_offset() {
+    let otter = Otter::new(2);
+    let swimmer = otter.swimmer_ptr();
+    unsafe {
+        assert_eq!(
+            itanium::offset_to_top(swimmer),
+            -(std::mem::offset_of!(Otter, vtable_i_swimmer) as isize)
+        );
+        assert!(itanium::type_info_ptr(swimmer).is_null());
+        assert_eq!(
+            itanium::complete_object(swimmer),
+            &otter as *const Otter as *const c_void
+        );
+    }
+}
+
+#[test]
+fn test_secondary_vtable_follows_primary_in_group() {
+    // Like g++: the secondary vtable's prefix starts right after the primary's methods
+    let ptr_size = std::mem::size_of::<*const c_void>();
+    let primary_end = Otter::VTABLE_I_WALKER as usize + std::mem::size_of::<IWalkerVTable>();
+    let secondary_start = Otter::VTABLE_I_SWIMMER as usize - 2 * ptr_size;
+    assert_eq!(primary_end, secondary_start);
+}
+
+#[test]
+fn test_no_cxx_type_info_for_rust_objects() {
+    // C++ typeid and dynamic_cast (other than to void*) on Rust objects are unsupported:
+    // slot -1 holds no std::type_info for g++ to read
+    let otter = Otter::new(1);
+    unsafe {
+        assert!(itanium::type_info_ptr(&otter as *const Otter as *const c_void).is_null());
+        assert!(itanium::rtti::type_info_of(otter.swimmer_ptr()).is_none());
+    }
+}
+
+#[test]
+fn test_calls_through_itanium_vtables() {
+    let otter = Otter::new(5);
+    unsafe {
+        let walker = IWalker::from_ptr_mut(&otter as *const Otter as *mut c_void);
+        assert_eq!(walker.walk(), 5);
+
+        let swimmer = ISwimmer::from_ptr_mut(otter.swimmer_ptr() as *mut c_void);
+        assert_eq!(swimmer.swim(), 15);
+        assert_eq!(swimmer.depth(), 12);
+    }
+}
+
+#[test]
+fn test_msvc_vtable_has_no_prefix() {
+    let thing = MsvcThing {
+        vtable_i_msvc_only: MsvcThing::VTABLE_I_MSVC_ONLY,
+    };
+    unsafe {
+        let iface = IMsvcOnly::from_ptr_mut(&thing as *const MsvcThing as *mut c_void);
+        assert_eq!(iface.value(), 7);
+    }
+}
@@ -151,20 +151,11 @@ fn test_multiple_interface_info_consts() {
     let info_second = MultiImpl::INTERFACE_INFO_I_SECOND;
 
     // Different interface IDs
-    assert!(!std::ptr::eq(
-        info_first.interface_id,
-        info_second.interface_id
-    ));
+    assert_ne!(info_first.interface_id, info_second.interface_id);
 
     // IDs should match respective interfaces
-    assert!(std::ptr::eq(
-        info_first.interface_id,
-        IFirst::interface_id_ptr()
-    ));
-    assert!(std::ptr::eq(
-        info_second.interface_id,
-        ISecond::interface_id_ptr()
-    ));
+    assert_eq!(info_first.interface_id, IFirst::interface_id_ptr().into());
+    assert_eq!(info_second.interface_id, ISecond::interface_id_ptr().into());
 }
 
 #[test]
@@ -0,0 +1,149 @@
+//! Tests for `#[overload_of]` (overloaded virtual methods)
+
+use cppvtable::proc::{cppvtable, cppvtable_impl};
+use std::mem::offset_of;
+
+// =============================================================================
+// MSVC: overloads are grouped at the first one and reversed
+// =============================================================================
+
+/// C++:
+/// ```cpp
+/// class ICanvas {
+///     virtual int draw(int);
+///     virtual int clear();
+///     virtual int draw(float);
+///     virtual int draw(int, int);
+/// };
+/// ```
+#[cppvtable(abi = "msvc")]
+pub trait IMsvcCanvas {
+    #[overload_of = "draw"]
+    fn draw_int(&self, x: i32) -> i32;
+    fn clear(&self) -> i32;
+    #[overload_of = "draw"]
+    fn draw_float(&self, x: f32) -> i32;
+    #[overload_of = "draw"]
+    fn draw_point(&self, x: i32, y: i32) -> i32;
+}
+
+#[repr(C)]
+pub struct MsvcCanvas {
+    vtable_i_msvc_canvas: *const IMsvcCanvasVTable,
+}
+
+#[cppvtable_impl(IMsvcCanvas, abi = "msvc")]
+impl MsvcCanvas {
+    #[overload_of = "draw"]
+    fn draw_int(&self, x: i32) -> i32 {
+        x
+    }
+    fn clear(&self) -> i32 {
+        -1
+    }
+    #[overload_of = "draw"]
+    fn draw_float(&self, x: f32) -> i32 {
+        (x * 10.0) as i32
+    }
+    #[overload_of = "draw"]
+    fn draw_point(&self, x: i32, y: i32) -> i32 {
+        x * 100 + y
+    }
+}
+
+#[test]
+fn test_msvc_overload_order() {
+    let ptr_size = std::mem::size_of::<*const ()>();
+    assert_eq!(offset_of!(IMsvcCanvasVTable, draw_point), 0);
+    assert_eq!(offset_of!(IMsvcCanvasVTable, draw_float), ptr_size);
+    assert_eq!(offset_of!(IMsvcCanvasVTable, draw_int), 2 * ptr_size);
+    assert_eq!(offset_of!(IMsvcCanvasVTable, clear), 3 * ptr_size);
+}
+
+#[test]
+fn test_msvc_overload_calls() {
+    let mut canvas = MsvcCanvas {
+        vtable_i_msvc_canvas: MsvcCanvas::VTABLE_I_MSVC_CANVAS,
+    };
+    unsafe {
+        let iface = &mut *(&mut canvas as *mut MsvcCanvas as *mut IMsvcCanvas);
+        assert_eq!(iface.draw_int(7), 7);
+        assert_eq!(iface.draw_float(1.5), 15);
+        assert_eq!(iface.draw_point(3, 4), 304);
+        assert_eq!(iface.clear(), -1);
+    }
+}
+
+// =============================================================================
+// Itanium: declaration order
+// =============================================================================
+
+#[cppvtable(abi = "itanium")]
+pub trait IItaniumCanvas {
+    #[overload_of = "draw"]
+    fn draw_int(&self, x: i32) -> i32;
+    fn clear(&self) -> i32;
+    #[overload_of = "draw"]
+    fn draw_float(&self, x: f32) -> i32;
+}
+
+#[repr(C)]
+pub struct ItaniumCanvas {
+    vtable_i_itanium_canvas: *const IItaniumCanvasVTable,
+}
+
+#[cppvtable_impl(IItaniumCanvas, abi = "itanium")]
+impl ItaniumCanvas {
+    #[overload_of = "draw"]
+    fn draw_int(&self, x: i32) -> i32 {
+        x
+    }
+    fn clear(&self) -> i32 {
+        -1
+    }
+    #[overload_of = "draw"]
+    fn draw_float(&self, x: f32) -> i32 {
+        (x * 10.0) as i32
+    }
+}
+
+#[test]
+fn test_itanium_overload_order() {
+    let ptr_size = std::mem::size_of::<*const ()>();
+    assert_eq!(offset_of!(IItaniumCanvasVTable, draw_int), 0);
+    assert_eq!(offset_of!(IItaniumCanvasVTable, clear), ptr_size);
+    assert_eq!(offset_of!(IItaniumCanvasVTable, draw_float), 2 * ptr_size);
+}
+
+#[test]
+fn test_itanium_overload_calls() {
+    let mut canvas = ItaniumCanvas {
+        vtable_i_itanium_canvas: ItaniumCanvas::VTABLE_I_ITANIUM_CANVAS,
+    };
+    unsafe {
+        let iface = &mut *(&mut canvas as *mut ItaniumCanvas as *mut IItaniumCanvas);
+        assert_eq!(iface.draw_int(2), 2);
+        assert_eq!(iface.clear(), -1);
+        assert_eq!(iface.draw_float(0.5), 5);
+    }
+}
+
+// =============================================================================
+// The method named like the overload set belongs to it
+// =============================================================================
+
+#[cppvtable(abi = "msvc")]
+pub trait INamedOverloads {
+    fn first(&self) -> i32;
+    fn print(&self) -> i32;
+    #[overload_of = "print"]
+    fn print_value(&self, value: i32) -> i32;
+}
+
+#[test]
+fn test_overload_of_existing_name() {
+    let ptr_size = std::mem::size_of::<*const ()>();
+    assert_eq!(offset_of!(INamedOverloadsVTable, first), 0);
+    assert_eq!(offset_of!(INamedOverloadsVTable, print_value), ptr_size);
+    assert_eq!(offset_of!(INamedOverloadsVTable, print), 2 * ptr_size);
+}
@@ -0,0 +1,181 @@
+//! Tests for panic containment in generated entry points (`panic = "..."`)
+
+use cppvtable::com::{ComPtr, ComRefCount, E_UNEXPECTED, HRESULT, IUnknownVTable, S_OK};
+use cppvtable::panic::take_last_panic;
+use cppvtable::proc::{com_implement, com_interface, cppvtable, cppvtable_impl};
+use cppvtable::{IUnknown, PanicPolicy, VTableLayout};
+use std::ffi::c_void;
+use std::process::Command;
+
+// =============================================================================
+// panic = "default": the entry point returns Default::default()
+// =============================================================================
+
+#[cppvtable(panic = "default")]
+pub trait IGauge {
+    fn level(&self) -> i32;
+    #[slot(2)]
+    fn fill(&mut self, amount: i32);
+}
+
+#[repr(C)]
+pub struct Gauge {
+    vtable_i_gauge: *const IGaugeVTable,
+    level: i32,
+}
+
+#[cppvtable_impl(IGauge)]
+impl Gauge {
+    fn level(&self) -> i32 {
+        if self.level < 0 {
+            panic!("negative level {}", self.level);
+        }
+        self.level
+    }
+    fn fill(&mut self, amount: i32) {
+        self.level += amount;
+        assert!(self.level <= 100, "overflowing gauge");
+    }
+}
+
+impl Gauge {
+    fn new(level: i32) -> Self {
+        Self {
+            vtable_i_gauge: Self::VTABLE_I_GAUGE,
+            level,
+        }
+    }
+}
+
+#[test]
+fn test_panic_policy_constant() {
+    assert_eq!(<IGauge as VTableLayout>::PANIC_POLICY, PanicPolicy::Default);
+    assert_eq!(
+        <IScanner as VTableLayout>::PANIC_POLICY,
+        PanicPolicy::HResult
+    );
+    assert_eq!(<IValve as VTableLayout>::PANIC_POLICY, PanicPolicy::Abort);
+}
+
+#[test]
+fn test_default_policy_returns_default() {
+    let mut gauge = Gauge::new(-3);
+    let this = &mut gauge as *mut Gauge as *mut c_void;
+
+    unsafe {
+        let vtable = &*gauge.vtable_i_gauge;
+        assert_eq!((vtable.level)(this), 0);
+        let panic = take_last_panic().expect("panic was recorded");
+        assert_eq!(panic.location(), "IGauge::level");
+        assert_eq!(panic.message(), Some("negative level -3"));
+        assert!(take_last_panic().is_none());
+
+        // Unit methods just return, with the work done before the panic kept
+        (vtable.fill)(this, 200);
+        assert_eq!(gauge.level, 197);
+        assert_eq!(
+            take_last_panic().unwrap().message(),
+            Some("overflowing gauge")
+        );
+    }
+}
+
+#[test]
+fn test_reserved_slot_panic_is_contained() {
+    let mut gauge = Gauge::new(0);
+    let this = &mut gauge as *mut Gauge as *mut c_void;
+
+    unsafe {
+        ((*gauge.vtable_i_gauge).__reserved_slot_1)(this);
+    }
+    let panic = take_last_panic().unwrap();
+    assert_eq!(panic.location(), "IGauge::__reserved_slot_1");
+    assert_eq!(panic.message(), Some("Called reserved vtable slot 1"));
+}
+
+// =============================================================================
+// panic = "hresult": HRESULT methods return E_UNEXPECTED
+// =============================================================================
+
+#[com_interface("5c7e0b2a-31d4-4f6e-9a8b-0c1d2e3f4a51", panic = "hresult")]
+pub trait IScanner {
+    fn scan(&self, input: i32) -> HRESULT;
+}
+
+#[repr(C)]
+pub struct Scanner {
+    vtable_i_scanner: *const IScannerVTable,
+    ref_count: ComRefCount,
+}
+
+#[com_implement(IScanner, heap)]
+impl Scanner {
+    fn scan(&self, input: i32) -> HRESULT {
+        let parsed: u8 = input.try_into().expect("input out of range");
+        if parsed > 0 { S_OK } else { E_UNEXPECTED }
+    }
+}
+
+#[test]
+fn test_hresult_policy_returns_e_unexpected() {
+    let scanner = ComPtr::<IScanner>::new(Scanner {
+        vtable_i_scanner: Scanner::VTABLE_I_SCANNER,
+        ref_count: ComRefCount::new(),
+    });
+
+    unsafe {
+        assert_eq!(scanner.scan(7), S_OK);
+        assert!(take_last_panic().is_none());
+
+        assert_eq!(scanner.scan(-1), E_UNEXPECTED);
+        let panic = take_last_panic().unwrap();
+        assert_eq!(panic.location(), "IScanner::scan");
+        assert!(panic.message().unwrap().starts_with("input out of range"));
+    }
+}
+
+// =============================================================================
+// panic = "abort" (the default): report the panic and abort
+// =============================================================================
+
+#[cppvtable]
+pub trait IValve {
+    fn open(&mut self);
+}
+
+#[repr(C)]
+pub struct Valve {
+    vtable_i_valve: *const IValveVTable,
+}
+
+#[cppvtable_impl(IValve)]
+impl Valve {
+    fn open(&mut self) {
+        panic!("valve stuck");
+    }
+}
+
+#[test]
+fn test_abort_policy_aborts() {
+    // Run the panicking call in a child process, which must not survive it
+    if std::env::var_os("CPPVTABLE_PANIC_CHILD").is_some() {
+        let mut valve = Valve {
+            vtable_i_valve: Valve::VTABLE_I_VALVE,
+        };
+        unsafe { IValve::from_ptr_mut(&mut valve as *mut Valve as *mut c_void).open() };
+        return;
+    }
+
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["test_abort_policy_aborts", "--exact", "--nocapture"])
+        .env("CPPVTABLE_PANIC_CHILD", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("cppvtable: panic in IValve::open") && stderr.contains("valve stuck"),
+        "unexpected stderr: {}",
+        stderr
+    );
+}
@@ -0,0 +1,115 @@
+//! Tests for checked dispatch (`#[cppvtable_impl(IFoo, checked)]`): reentrant calls that
+//! would alias a `&mut self` call are rejected through the interface's panic policy
+
+use cppvtable::BorrowFlag;
+use cppvtable::panic::take_last_panic;
+use cppvtable::proc::{cppvtable, cppvtable_impl};
+use std::cell::Cell;
+use std::ffi::c_void;
+
+#[cppvtable(panic = "default")]
+pub trait IListener {
+    fn on_event(&mut self, depth: i32) -> i32;
+    fn event_count(&self) -> i32;
+    fn total(&self) -> i32;
+}
+
+/// Calls back into the listener through its vtable, the way C++ would
+unsafe fn call_back(this: *mut c_void) -> &'static mut IListener {
+    unsafe { IListener::from_ptr_mut(this) }
+}
+
+#[repr(C)]
+pub struct Listener {
+    vtable_i_listener: *const IListenerVTable,
+    borrow_flag: BorrowFlag,
+    events: Cell<i32>,
+    /// What the reentrant calls returned
+    nested: Vec<i32>,
+}
+
+#[cppvtable_impl(IListener, checked)]
+impl Listener {
+    fn on_event(&mut self, depth: i32) -> i32 {
+        self.events.set(self.events.get() + 1);
+        if depth > 0 {
+            let this = self as *mut Self as *mut c_void;
+            unsafe {
+                let nested = call_back(this).on_event(depth - 1);
+                let total = call_back(this).total();
+                let count = call_back(this).event_count();
+                self.nested.extend([nested, total, count]);
+            }
+        }
+        depth
+    }
+
+    #[reentrant]
+    fn event_count(&self) -> i32 {
+        self.events.get()
+    }
+
+    fn total(&self) -> i32 {
+        // Nested &self calls share the flag
+        let this = self as *const Self as *mut c_void;
+        self.events.get() + unsafe { call_back(this).event_count() }
+    }
+}
+
+impl Listener {
+    fn new() -> Self {
+        Self {
+            vtable_i_listener: Self::VTABLE_I_LISTENER,
+            borrow_flag: BorrowFlag::new(),
+            events: Cell::new(0),
+            nested: Vec::new(),
+        }
+    }
+}
+
+#[test]
+fn test_reentrant_calls_during_mut_call() {
+    let mut listener = Listener::new();
+    let this = &mut listener as *mut Listener as *mut c_void;
+
+    unsafe {
+        assert_eq!(call_back(this).on_event(1), 1);
+    }
+    // The nested on_event and total were rejected (Default), event_count is #[reentrant]
+    assert_eq!(listener.nested, [0, 0, 1]);
+    assert_eq!(listener.events.get(), 1);
+    assert!(!listener.borrow_flag.is_borrowed());
+
+    let panic = take_last_panic().unwrap();
+    assert_eq!(panic.location(), "IListener::total");
+    assert_eq!(
+        panic.message(),
+        Some("reentrant call to IListener::total while the object is mutably borrowed")
+    );
+}
+
+#[test]
+fn test_shared_calls_nest() {
+    let mut listener = Listener::new();
+    listener.events.set(2);
+    let this = &mut listener as *mut Listener as *mut c_void;
+
+    unsafe {
+        assert_eq!(call_back(this).total(), 4);
+        assert_eq!(call_back(this).on_event(0), 0);
+    }
+    assert!(take_last_panic().is_none());
+    assert!(!listener.borrow_flag.is_borrowed());
+}
+
+#[test]
+fn test_borrow_released_after_panic() {
+    let flag = BorrowFlag::new();
+    let result = std::panic::catch_unwind(|| {
+        let _borrow = flag.borrow_mut("test");
+        assert!(flag.is_borrowed_mut());
+        panic!("inside the call");
+    });
+    assert!(result.is_err());
+    assert!(!flag.is_borrowed());
+}
@@ -1,6 +1,10 @@
 //! Tests for RTTI (Runtime Type Information) system
 
-use cppvtable::rtti::{InterfaceInfo, TypeInfo, VTableWithRtti};
+use cppvtable::proc::{cppvtable, cppvtable_impl};
+use cppvtable::rtti::{
+    HasTypeInfo, InterfaceInfo, TypeInfo, VTableWithRtti, get_type_info, type_id_from_path,
+    type_info_of,
+};
 use std::ffi::c_void;
 
 // Define interface IDs using static addresses (pointer-based for const-compatibility)
@@ -19,12 +23,10 @@ fn test_type_info_creation() {
 
 #[test]
 fn test_interface_info_size() {
-    // InterfaceInfo should be 2 * pointer size
+    // InterfaceInfo holds an InterfaceId (tag + u128) and an offset, each padded to u128 alignment
     let size = std::mem::size_of::<InterfaceInfo>();
-    #[cfg(target_pointer_width = "64")]
-    assert_eq!(size, 16);
-    #[cfg(target_pointer_width = "32")]
-    assert_eq!(size, 8);
+    let align = std::mem::align_of::<u128>();
+    assert_eq!(size, std::mem::size_of::<u128>() + 2 * align);
 }
 
 #[test]
@@ -86,10 +88,184 @@ fn test_vtable_with_rtti_layout() {
         method2: extern "C" fn(),
     }
 
-    // VTableWithRtti should have RTTI pointer first, then methods
+    // VTableWithRtti should have offset-to-top and the RTTI pointer first, then methods
     let size = std::mem::size_of::<VTableWithRtti<TestVTable>>();
     let ptr_size = std::mem::size_of::<*const TypeInfo>();
     let vtable_size = std::mem::size_of::<TestVTable>();
 
-    assert_eq!(size, ptr_size + vtable_size);
+    assert_eq!(size, 2 * ptr_size + vtable_size);
+}
+
+// =============================================================================
+// #[cppvtable_impl(IFoo, rtti)]: the TypeInfo is wired into slot -1
+// =============================================================================
+
+#[cppvtable(abi = "msvc")]
+pub trait IWader {
+    fn swim(&self) -> i32;
+}
+
+#[cppvtable(abi = "msvc")]
+pub trait IGlider {
+    fn fly(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct Duck {
+    vtable_i_wader: *const IWaderVTable,
+    vtable_i_glider: *const IGliderVTable,
+    speed: i32,
+}
+
+#[cppvtable_impl(IWader, abi = "msvc", rtti(IWader, IGlider))]
+impl Duck {
+    fn swim(&self) -> i32 {
+        self.speed
+    }
+}
+
+#[cppvtable_impl(IGlider, abi = "msvc", rtti)]
+impl Duck {
+    fn fly(&self) -> i32 {
+        self.speed * 2
+    }
+}
+
+#[cppvtable(abi = "itanium")]
+pub trait IItaniumSwimmer {
+    fn swim(&self) -> i32;
+}
+
+#[cppvtable(abi = "itanium")]
+pub trait IItaniumFlyer {
+    fn fly(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct ItaniumDuck {
+    vtable_i_itanium_swimmer: *const IItaniumSwimmerVTable,
+    vtable_i_itanium_flyer: *const IItaniumFlyerVTable,
+    speed: i32,
+}
+
+#[cppvtable_impl(IItaniumSwimmer, abi = "itanium", rtti(IItaniumSwimmer, IItaniumFlyer))]
+impl ItaniumDuck {
+    fn swim(&self) -> i32 {
+        self.speed
+    }
+}
+
+#[cppvtable_impl(IItaniumFlyer, abi = "itanium", rtti)]
+impl ItaniumDuck {
+    fn fly(&self) -> i32 {
+        self.speed * 2
+    }
+}
+
+#[test]
+fn test_generated_type_info() {
+    let type_info = Duck::type_info();
+    assert_eq!(type_info.type_name, "Duck");
+    assert_eq!(
+        type_info.type_id,
+        type_id_from_path(concat!(module_path!(), "::Duck"))
+    );
+    assert_ne!(type_info.type_id, ItaniumDuck::type_info().type_id);
+    assert_eq!(type_info.interfaces.len(), 2);
+    assert!(type_info.implements(IWader::interface_id_ptr()));
+    assert!(type_info.implements(IGlider::interface_id_ptr()));
+    assert!(!type_info.implements(IItaniumFlyer::interface_id_ptr()));
+}
+
+#[test]
+fn test_type_info_through_each_vtable() {
+    let duck = Duck {
+        vtable_i_wader: Duck::VTABLE_I_WADER,
+        vtable_i_glider: Duck::VTABLE_I_GLIDER,
+        speed: 3,
+    };
+
+    unsafe {
+        let wader = &duck as *const Duck as *const c_void;
+        assert!(std::ptr::eq(type_info_of(wader), Duck::TYPE_INFO));
+        assert!(std::ptr::eq(
+            get_type_info(duck.vtable_i_glider as *const c_void),
+            Duck::TYPE_INFO
+        ));
+
+        let glider = type_info_of(wader).cast_to(wader, IGlider::interface_id_ptr());
+        assert_eq!(glider, &duck.vtable_i_glider as *const _ as *const c_void);
+        assert_eq!(IGlider::from_ptr_mut(glider as *mut c_void).fly(), 6);
+    }
+}
+
+#[test]
+fn test_itanium_type_info_slot() {
+    let duck = ItaniumDuck {
+        vtable_i_itanium_swimmer: ItaniumDuck::VTABLE_I_ITANIUM_SWIMMER,
+        vtable_i_itanium_flyer: ItaniumDuck::VTABLE_I_ITANIUM_FLYER,
+        speed: 5,
+    };
+
+    unsafe {
+        let swimmer = &duck as *const ItaniumDuck as *const c_void;
+        let flyer = &duck.vtable_i_itanium_flyer as *const _ as *const c_void;
+        assert!(std::ptr::eq(type_info_of(flyer), ItaniumDuck::TYPE_INFO));
+
+        // Casting from the secondary interface: go back to the object start first
+        let offset_to_top = *(duck.vtable_i_itanium_flyer as *const isize).offset(-2);
+        let top = (flyer as *const u8).offset(offset_to_top) as *const c_void;
+        assert_eq!(top, swimmer);
+        let cast = type_info_of(flyer).cast_to(top, IItaniumSwimmer::interface_id_ptr());
+        assert_eq!(IItaniumSwimmer::from_ptr_mut(cast as *mut c_void).swim(), 5);
+    }
+}
+
+// =============================================================================
+// Typed casts: query::<I>() and implements::<I>()
+// =============================================================================
+
+#[test]
+fn test_query_between_interfaces() {
+    let mut duck = Duck {
+        vtable_i_wader: Duck::VTABLE_I_WADER,
+        vtable_i_glider: Duck::VTABLE_I_GLIDER,
+        speed: 4,
+    };
+
+    unsafe {
+        let wader = IWader::from_ptr_mut(&mut duck as *mut Duck as *mut c_void);
+        assert!(wader.implements::<IGlider>());
+        assert!(!wader.implements::<IItaniumFlyer>());
+        assert!(wader.query::<IItaniumFlyer>().is_none());
+
+        let glider = wader.query_mut::<IGlider>().unwrap();
+        assert_eq!(glider.fly(), 8);
+
+        // From the secondary interface, back through the object start
+        let wader = glider.query_mut::<IWader>().unwrap();
+        assert_eq!(wader.swim(), 4);
+        assert!(std::ptr::eq(
+            wader as *const IWader as *const Duck,
+            &raw const duck
+        ));
+        assert!(wader.query::<IWader>().is_some());
+    }
+}
+
+#[test]
+fn test_query_itanium_secondary_interface() {
+    let mut duck = ItaniumDuck {
+        vtable_i_itanium_swimmer: ItaniumDuck::VTABLE_I_ITANIUM_SWIMMER,
+        vtable_i_itanium_flyer: ItaniumDuck::VTABLE_I_ITANIUM_FLYER,
+        speed: 6,
+    };
+
+    unsafe {
+        let flyer =
+            IItaniumFlyer::from_ptr_mut(&raw mut duck.vtable_i_itanium_flyer as *mut c_void);
+        assert!(flyer.implements::<IItaniumSwimmer>());
+        assert!(!flyer.implements::<IWader>());
+        assert_eq!(flyer.query_mut::<IItaniumSwimmer>().unwrap().swim(), 6);
+    }
 }
@@ -142,3 +142,53 @@ fn test_starts_at_three() {
     assert_eq!(tester.first_method(), 3);
     assert_eq!(tester.second_method(), 4);
 }
+
+/// Implementation that leaves the slots to the interface
+#[repr(C)]
+pub struct UnslottedTester {
+    vtable_i_slotted: *const ISlottedVTable,
+}
+
+#[cppvtable_impl(ISlotted)]
+impl UnslottedTester {
+    fn at_slot_6(&self) -> i32 {
+        6
+    }
+    fn at_slot_5(&self) -> i32 {
+        5
+    }
+    fn at_slot_1(&self) -> i32 {
+        1
+    }
+    fn at_slot_0(&self) -> i32 {
+        0
+    }
+}
+
+#[test]
+fn test_slots_only_on_interface() {
+    let tester = UnslottedTester {
+        vtable_i_slotted: UnslottedTester::VTABLE_I_SLOTTED,
+    };
+
+    unsafe {
+        let vtable = &*tester.vtable_i_slotted;
+        let this = &tester as *const UnslottedTester as *mut c_void;
+
+        assert_eq!((vtable.at_slot_0)(this), 0);
+        assert_eq!((vtable.at_slot_1)(this), 1);
+        assert_eq!((vtable.at_slot_5)(this), 5);
+        assert_eq!((vtable.at_slot_6)(this), 6);
+    }
+}
+
+#[test]
+fn test_interface_slot_metadata() {
+    assert_eq!(ISlotted::__slot_at_slot_1(), 1);
+    assert_eq!(ISlotted::__slot_at_slot_5(), 5);
+    assert_eq!(ISlotted::__slot_at_slot_6(), 6);
+    assert_eq!(
+        ISlotted::__METHODS,
+        ["at_slot_0", "at_slot_1", "at_slot_5", "at_slot_6"]
+    );
+}
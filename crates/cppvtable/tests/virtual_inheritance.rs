@@ -0,0 +1,321 @@
+//! Tests for virtual bases (`extends(virtual IBase)`): vbtables on MSVC, vbase offsets
+//! on Itanium, and casts that read the offset at runtime
+
+use cppvtable::proc::{cppvtable, cppvtable_impl};
+use cppvtable::rtti::RttiInterface;
+use cppvtable::{TypeInfo, VTableLayout, define_class};
+use std::ffi::c_void;
+use std::mem::{offset_of, size_of};
+
+const PTR: isize = size_of::<*const c_void>() as isize;
+
+// =============================================================================
+// MSVC: the virtual base stays out of the vtable, found through the vbptr
+// =============================================================================
+
+#[cppvtable(abi = "msvc")]
+pub trait IVcNamed {
+    fn id(&self) -> i32;
+}
+
+#[cppvtable(abi = "msvc", extends(virtual IVcNamed))]
+pub trait IVcLabeled {
+    fn label(&self) -> i32;
+}
+
+#[cppvtable(abi = "msvc", extends(virtual IVcNamed))]
+pub trait IVcTagged {
+    fn tag(&self) -> i32;
+    fn retag(&mut self, tag: i32);
+}
+
+/// Diamond: both interfaces share one `IVcNamed`
+#[repr(C)]
+pub struct VcBadge {
+    vtable_i_vc_labeled: *const IVcLabeledVTable,
+    vbptr_i_vc_labeled: *const i32,
+    vtable_i_vc_tagged: *const IVcTaggedVTable,
+    vbptr_i_vc_tagged: *const i32,
+    id: i32,
+    tag: i32,
+    vtable_i_vc_named: *const IVcNamedVTable,
+}
+
+#[cppvtable_impl(IVcNamed, abi = "msvc")]
+impl VcBadge {
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+#[cppvtable_impl(IVcLabeled, abi = "msvc", extends(virtual IVcNamed))]
+impl VcBadge {
+    fn label(&self) -> i32 {
+        self.id * 10
+    }
+}
+
+#[cppvtable_impl(IVcTagged, abi = "msvc", extends(virtual IVcNamed))]
+impl VcBadge {
+    fn tag(&self) -> i32 {
+        self.tag
+    }
+    fn retag(&mut self, tag: i32) {
+        self.tag = tag;
+    }
+}
+
+impl VcBadge {
+    fn new(id: i32, tag: i32) -> Self {
+        Self {
+            vtable_i_vc_labeled: Self::VTABLE_I_VC_LABELED,
+            vbptr_i_vc_labeled: Self::VBTABLE_I_VC_LABELED,
+            vtable_i_vc_tagged: Self::VTABLE_I_VC_TAGGED,
+            vbptr_i_vc_tagged: Self::VBTABLE_I_VC_TAGGED,
+            id,
+            tag,
+            vtable_i_vc_named: Self::VTABLE_I_VC_NAMED,
+        }
+    }
+}
+
+#[test]
+fn test_msvc_vtable_omits_virtual_base() {
+    assert_eq!(size_of::<IVcLabeledVTable>(), PTR as usize);
+    assert_eq!(size_of::<IVcTaggedVTable>(), 2 * PTR as usize);
+}
+
+#[test]
+fn test_msvc_vbtable_entries() {
+    unsafe {
+        let labeled = std::slice::from_raw_parts(VcBadge::VBTABLE_I_VC_LABELED, 2);
+        assert_eq!(labeled[0] as isize, -PTR);
+        assert_eq!(
+            labeled[1] as usize,
+            offset_of!(VcBadge, vtable_i_vc_named) - offset_of!(VcBadge, vbptr_i_vc_labeled)
+        );
+
+        let tagged = std::slice::from_raw_parts(VcBadge::VBTABLE_I_VC_TAGGED, 2);
+        assert_eq!(tagged[0] as isize, -PTR);
+        assert_eq!(
+            tagged[1] as usize,
+            offset_of!(VcBadge, vtable_i_vc_named) - offset_of!(VcBadge, vbptr_i_vc_tagged)
+        );
+    }
+}
+
+#[test]
+fn test_msvc_casts_to_shared_virtual_base() {
+    let mut badge = VcBadge::new(7, 3);
+    let base = &mut badge as *mut VcBadge as *mut u8;
+    let named = unsafe { base.add(offset_of!(VcBadge, vtable_i_vc_named)) } as *const u8;
+
+    unsafe {
+        let labeled = IVcLabeled::from_ptr_mut(
+            base.add(offset_of!(VcBadge, vtable_i_vc_labeled)) as *mut c_void
+        );
+        assert_eq!(labeled.label(), 70);
+        assert_eq!(
+            labeled.virtual_base_offset(),
+            offset_of!(VcBadge, vtable_i_vc_named) as isize
+        );
+        // Deref lands on the one shared base
+        let via_labeled: &IVcNamed = labeled;
+        assert_eq!(via_labeled as *const IVcNamed as *const u8, named);
+        assert_eq!(labeled.id(), 7);
+
+        let tagged = IVcTagged::from_ptr_mut(
+            base.add(offset_of!(VcBadge, vtable_i_vc_tagged)) as *mut c_void
+        );
+        tagged.retag(9);
+        assert_eq!(tagged.tag(), 9);
+        let via_tagged: &IVcNamed = tagged;
+        assert_eq!(via_tagged as *const IVcNamed as *const u8, named);
+        assert_eq!(tagged.id(), 7);
+    }
+}
+
+// =============================================================================
+// Itanium: the virtual base is the primary base, its offset sits in front of one
+// vcall offset per base function
+// =============================================================================
+
+#[cppvtable(abi = "itanium")]
+pub trait IGnuNamed {
+    fn id(&self) -> i32;
+}
+
+#[cppvtable(abi = "itanium", extends(virtual IGnuNamed))]
+pub trait IGnuLabeled {
+    fn label(&self) -> i32;
+}
+
+#[cppvtable(abi = "itanium", extends(virtual IGnuNamed))]
+pub trait IGnuTagged {
+    fn tag(&self) -> i32;
+}
+
+#[repr(C)]
+pub struct GnuBadge {
+    vtable_i_gnu_labeled: *const IGnuLabeledVTable,
+    vbptr_i_gnu_labeled: *const i32,
+    vtable_i_gnu_tagged: *const IGnuTaggedVTable,
+    vbptr_i_gnu_tagged: *const i32,
+    id: i32,
+    vtable_i_gnu_named: *const IGnuNamedVTable,
+}
+
+#[cppvtable_impl(IGnuNamed, abi = "itanium", grouped)]
+impl GnuBadge {
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+#[cppvtable_impl(IGnuLabeled, abi = "itanium", extends(virtual IGnuNamed), vtable_group(IGnuTagged, IGnuNamed))]
+impl GnuBadge {
+    fn label(&self) -> i32 {
+        self.id + 1
+    }
+}
+
+#[cppvtable_impl(IGnuTagged, abi = "itanium", extends(virtual IGnuNamed), grouped)]
+impl GnuBadge {
+    fn tag(&self) -> i32 {
+        self.id + 2
+    }
+}
+
+impl GnuBadge {
+    fn new(id: i32) -> Self {
+        Self {
+            vtable_i_gnu_labeled: Self::VTABLE_I_GNU_LABELED,
+            vbptr_i_gnu_labeled: Self::VBTABLE_I_GNU_LABELED,
+            vtable_i_gnu_tagged: Self::VTABLE_I_GNU_TAGGED,
+            vbptr_i_gnu_tagged: Self::VBTABLE_I_GNU_TAGGED,
+            id,
+            vtable_i_gnu_named: Self::VTABLE_I_GNU_NAMED,
+        }
+    }
+}
+
+#[test]
+fn test_itanium_vtable_embeds_virtual_base() {
+    assert_eq!(size_of::<IGnuLabeledVTable>(), 2 * PTR as usize);
+    assert_eq!(offset_of!(IGnuLabeledVTable, base), 0);
+    assert_eq!(<IGnuLabeled as VTableLayout>::FUNCTION_COUNT, 2);
+}
+
+#[test]
+fn test_itanium_vbase_offsets() {
+    let badge = GnuBadge::new(1);
+    let named = offset_of!(GnuBadge, vtable_i_gnu_named) as isize;
+    let tagged = offset_of!(GnuBadge, vtable_i_gnu_tagged) as isize;
+
+    unsafe {
+        let this = &badge as *const GnuBadge as *const c_void;
+        assert_eq!(cppvtable::itanium::virtual_base_offset(this, 1, 0), named);
+        assert_eq!(cppvtable::itanium::offset_to_top(this), 0);
+
+        let this = (this as *const u8).offset(tagged) as *const c_void;
+        assert_eq!(
+            cppvtable::itanium::virtual_base_offset(this, 1, 0),
+            named - tagged
+        );
+        assert_eq!(cppvtable::itanium::offset_to_top(this), -tagged);
+    }
+}
+
+#[test]
+fn test_itanium_casts_to_shared_virtual_base() {
+    let mut badge = GnuBadge::new(5);
+    let base = &mut badge as *mut GnuBadge as *mut u8;
+
+    unsafe {
+        let labeled = IGnuLabeled::from_ptr_mut(base as *mut c_void);
+        let tagged = IGnuTagged::from_ptr_mut(
+            base.add(offset_of!(GnuBadge, vtable_i_gnu_tagged)) as *mut c_void
+        );
+        assert_eq!(labeled.label(), 6);
+        assert_eq!(tagged.tag(), 7);
+
+        let via_tagged: *const IGnuNamed = &**tagged;
+        let via_labeled: &mut IGnuNamed = labeled;
+        assert!(std::ptr::eq(via_labeled, via_tagged));
+        assert_eq!(via_labeled.id(), 5);
+
+        // The embedded base slots forward to the same method
+        let this = base.add(offset_of!(GnuBadge, vtable_i_gnu_tagged)) as *mut c_void;
+        assert_eq!(((*badge.vtable_i_gnu_tagged).base.id)(this), 5);
+    }
+}
+
+// =============================================================================
+// define_class! and RTTI
+// =============================================================================
+
+define_class! {
+    pub class Card : IVcLabeled(virtual IVcNamed) {
+        id: i32,
+    }
+}
+
+#[cppvtable_impl(IVcNamed, abi = "msvc")]
+impl Card {
+    fn id(&self) -> i32 {
+        self.id
+    }
+}
+
+#[cppvtable_impl(IVcLabeled, abi = "msvc", extends(virtual IVcNamed))]
+impl Card {
+    fn label(&self) -> i32 {
+        -self.id
+    }
+}
+
+#[test]
+fn test_define_class_with_virtual_base() {
+    let mut card = Card {
+        vtable_i_vc_labeled: Card::VTABLE_I_VC_LABELED,
+        vbptr_i_vc_labeled: Card::VBTABLE_I_VC_LABELED,
+        id: 4,
+        vtable_i_vc_named: Card::VTABLE_I_VC_NAMED,
+    };
+
+    unsafe {
+        assert_eq!(card.as_i_vc_labeled_mut().label(), -4);
+        assert_eq!(card.as_i_vc_named_mut().id(), 4);
+    }
+    let via_deref: &IVcNamed = card.as_i_vc_labeled();
+    assert!(std::ptr::eq(via_deref, card.as_i_vc_named()));
+}
+
+#[test]
+fn test_rtti_lists_virtual_base_once() {
+    static INTERFACES: [cppvtable::InterfaceInfo; 3] = [
+        VcBadge::INTERFACE_INFO_I_VC_LABELED,
+        VcBadge::INTERFACE_INFO_I_VC_TAGGED,
+        VcBadge::INTERFACE_INFO_I_VC_NAMED,
+    ];
+    static TYPE_INFO: TypeInfo = TypeInfo::new(1, "VcBadge", &INTERFACES);
+
+    let badge = VcBadge::new(2, 0);
+    let object = &badge as *const VcBadge as *const c_void;
+
+    unsafe {
+        let named = TYPE_INFO.cast_to(object, IVcNamed::interface_id_ptr());
+        assert_eq!(
+            named,
+            (object as *const u8).add(offset_of!(VcBadge, vtable_i_vc_named)) as *const c_void
+        );
+        assert_eq!((*(named as *mut IVcNamed)).id(), 2);
+    }
+}
+
+#[test]
+fn test_virtual_base_is_not_in_the_chain() {
+    // The virtual base has its own vtable pointer, listed on its own
+    assert_eq!(<IVcLabeled as RttiInterface>::CHAIN.depth(), 1);
+    assert!(<IVcLabeled as RttiInterface>::CHAIN.base.is_none());
+}
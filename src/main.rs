@@ -5,7 +5,7 @@
 //! 2. Proc-macros: `#[cppvtable]` and `#[cppvtable_impl]`
 //!
 //! Also includes C++ interop tests using the `cpp` crate to verify
-//! vtable layout compatibility with actual MSVC-compiled C++ code.
+//! vtable layout compatibility with actual MSVC- or g++-compiled C++ code.
 
 #![allow(dead_code)]
 
@@ -21,12 +21,14 @@ use std::io::{self, Write};
 // C++ INTEROP: Define C++ classes and test vtable compatibility
 // =============================================================================
 
-// This block defines C++ code that will be compiled by MSVC
+// This block defines C++ code that will be compiled by MSVC or g++
 cpp! {{
     #include <cstdio>
     #include <cstring>
 
     // Pure virtual interface - should match our Rust IAnimal layout
+    // No virtual destructor: it would add slots IAnimal doesn't have, so the
+    // concrete classes are final and always deleted through their own type
     class ICppAnimal {
     public:
         virtual void speak() = 0;
@@ -34,12 +36,12 @@ cpp! {{
     };
 
     // Concrete C++ implementation
-    class CppDog : public ICppAnimal {
+    class CppDog final : public ICppAnimal {
     public:
         char name[32];
 
         CppDog(const char* n) {
-            strncpy_s(name, sizeof(name), n, _TRUNCATE);
+            snprintf(name, sizeof(name), "%s", n);
         }
 
         void speak() override {
@@ -51,7 +53,7 @@ cpp! {{
         }
     };
 
-    class CppCat : public ICppAnimal {
+    class CppCat final : public ICppAnimal {
     public:
         int lives;
 
@@ -102,10 +104,17 @@ fn cpp_call_legs(animal: *mut c_void) -> i32 {
     })
 }
 
-/// Delete a C++ ICppAnimal
-fn delete_cpp_animal(animal: *mut c_void) {
-    cpp!(unsafe [animal as "ICppAnimal*"] {
-        delete animal;
+/// Delete a C++ CppDog (ICppAnimal has no virtual destructor)
+fn delete_cpp_dog(dog: *mut c_void) {
+    cpp!(unsafe [dog as "CppDog*"] {
+        delete dog;
+    })
+}
+
+/// Delete a C++ CppCat
+fn delete_cpp_cat(cat: *mut c_void) {
+    cpp!(unsafe [cat as "CppCat*"] {
+        delete cat;
     })
 }
 
@@ -253,7 +262,8 @@ impl Cat {
 
 #[cppvtable]
 pub trait ICachedMetrics {
-    fn destructor(&mut self, flags: u8) -> *mut c_void;
+    #[destructor]
+    fn destructor(&mut self);
     fn get_metric(&mut self, metric_type: i32, param: i32, confidence_out: *mut f32) -> f32;
     fn compute_metric(&mut self, metric_type: i32, param: i32) -> i32;
 }
@@ -269,10 +279,8 @@ pub struct CachedMetrics {
 
 #[cppvtable_impl(ICachedMetrics)]
 impl CachedMetrics {
-    fn destructor(&mut self, flags: u8) -> *mut c_void {
-        println!("CachedMetrics destructor (flags: {})", flags);
-        self as *mut CachedMetrics as *mut c_void
-    }
+    #[destructor]
+    fn destructor(&mut self);
 
     fn get_metric(&mut self, metric_type: i32, param: i32, confidence_out: *mut f32) -> f32 {
         if !(0..2).contains(&metric_type) {
@@ -299,6 +307,12 @@ impl CachedMetrics {
     }
 }
 
+impl Drop for CachedMetrics {
+    fn drop(&mut self) {
+        println!("  CachedMetrics dropped");
+    }
+}
+
 impl Default for CachedMetrics {
     fn default() -> Self {
         Self::new()
@@ -484,8 +498,8 @@ fn main() {
         println!("  Rust sees CppCat legs: {}", cat_ref.legs());
 
         // Cleanup
-        delete_cpp_animal(cpp_dog);
-        delete_cpp_animal(cpp_cat);
+        delete_cpp_dog(cpp_dog);
+        delete_cpp_cat(cpp_cat);
     }
 
     // =========================================================================
@@ -533,10 +547,15 @@ fn main() {
 
     // Cached metrics (lazy computation pattern)
     println!("\n--- Cached metrics example ---");
-    let mut metrics = CachedMetrics::new();
-    let mut conf: f32 = 0.0;
-    let value = metrics.get_metric(0, 0, &mut conf);
-    println!("  Metric 0: {} (confidence: {})", value, conf);
+    let metrics = Box::into_raw(Box::new(CachedMetrics::new()));
+    unsafe {
+        let iface = &mut *(metrics as *mut ICachedMetrics);
+        let mut conf: f32 = 0.0;
+        let value = iface.get_metric(0, 0, &mut conf);
+        println!("  Metric 0: {} (confidence: {})", value, conf);
+        // Same as C++ `delete iface;` - runs Drop and frees the Box
+        iface.destructor_deleting();
+    }
 
     // Test #[slot(N)] attribute
     println!("\n--- Slot index test (proc-macro) ---");